@@ -28,6 +28,8 @@ export const update = async (
     payer,
     request.alias,
     request.addressBook,
+    request.status,
+    request.avatarUri,
     request.signCallback,
     request.cluster
   );
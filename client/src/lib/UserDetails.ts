@@ -18,20 +18,32 @@ export class AddressBook implements ChainStorage<string> {
 }
 
 export class UserDetails implements ChainStorage<UserDetailsData> {
-  constructor(readonly alias: string, readonly addressBook: AddressBook) {}
+  constructor(
+    readonly alias: string,
+    readonly addressBook: AddressBook,
+    readonly status = '',
+    readonly avatarUri = ''
+  ) {}
 
   static async fromChainData(
     userDetailsData: UserDetailsData
   ): Promise<UserDetails> {
     const addressBook = new AddressBook(userDetailsData.addressBook);
 
-    return new UserDetails(userDetailsData.alias, addressBook);
+    return new UserDetails(
+      userDetailsData.alias,
+      addressBook,
+      userDetailsData.status,
+      userDetailsData.avatarUri
+    );
   }
 
   toChainData(): UserDetailsData {
     return new UserDetailsData({
       alias: this.alias,
       addressBook: this.addressBook.toChainData(),
+      status: this.status,
+      avatarUri: this.avatarUri,
     });
   }
 }
@@ -23,6 +23,9 @@ export const CHANNEL_NONCE_SEED_STRING = 'solarium_channel';
 export const USER_DETAILS_ACCOUNT_NONCE_SEED_STRING =
   'solarium_userdetails_account';
 
+// must match get_blocklist_account_address_with_seed in state.rs
+export const BLOCKLIST_ACCOUNT_NONCE_SEED_STRING = 'solarium_blocklist_account';
+
 // The cluster reference for the sol-did client - used to generate
 // DIDs that can be unambiguously resolved to any solana cluster
 // e.g. did:sol:devnet:abcde
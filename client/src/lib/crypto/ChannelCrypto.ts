@@ -79,10 +79,12 @@ export const encryptCEKForVerificationMethod = async (
   const concatByteArray = u8a.concat([res.iv, res.tag, res.epPubKey]);
   const header = bytesToBase64(concatByteArray);
 
+  // the client does not rotate channel keys, so CEKs are for the first epoch
   return new CEKData({
     kid: shortenKID(key.id),
     header,
     encryptedKey: bytesToBase64(res.encryptedKey),
+    epoch: 0,
   });
 };
 
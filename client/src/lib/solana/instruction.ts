@@ -1,5 +1,6 @@
-import { Enum, Assignable, SCHEMA } from './solanaBorsh';
+import { Enum, Assignable, OptionalString, SCHEMA } from './solanaBorsh';
 import {
+  BLOCKLIST_ACCOUNT_NONCE_SEED_STRING,
   CEK_ACCOUNT_NONCE_SEED_STRING,
   CHANNEL_NONCE_SEED_STRING,
   DEFAULT_USER_DETAILS_SIZE,
@@ -28,6 +29,7 @@ export class InitializeDirectChannel extends Assignable {
 
 export class Post extends Assignable {
   message: string;
+  epoch: number;
 }
 
 export class AddToChannel extends Assignable {
@@ -49,8 +51,10 @@ export class CreateUserDetails extends Assignable {
 }
 
 export class UpdateUserDetails extends Assignable {
-  alias: string;
-  addressBook: string;
+  alias: OptionalString;
+  addressBook: OptionalString;
+  status: OptionalString;
+  avatarUri: OptionalString;
 }
 
 export class SolariumInstruction extends Enum {
@@ -81,8 +85,8 @@ export class SolariumInstruction extends Enum {
     });
   }
 
-  static post(message: string): SolariumInstruction {
-    return new SolariumInstruction({ post: new Post({ message }) });
+  static post(message: string, epoch: number): SolariumInstruction {
+    return new SolariumInstruction({ post: new Post({ message, epoch }) });
   }
 
   static addToChannel(CEKs: CEKData[]): SolariumInstruction {
@@ -113,12 +117,20 @@ export class SolariumInstruction extends Enum {
     });
   }
 
+  // fields that are not supplied are left unchanged
   static updateUserDetails(
-    alias: string,
-    addressBook = ''
+    alias?: string,
+    addressBook?: string,
+    status?: string,
+    avatarUri?: string
   ): SolariumInstruction {
     return new SolariumInstruction({
-      updateUserDetails: new UpdateUserDetails({ alias, addressBook }),
+      updateUserDetails: new UpdateUserDetails({
+        alias: OptionalString.of(alias),
+        addressBook: OptionalString.of(addressBook),
+        status: OptionalString.of(status),
+        avatarUri: OptionalString.of(avatarUri),
+      }),
     });
   }
 }
@@ -149,6 +161,17 @@ export async function getUserDetailsKey(did: PublicKey): Promise<PublicKey> {
   return publicKeyNonce[0];
 }
 
+export async function getBlocklistKey(did: PublicKey): Promise<PublicKey> {
+  const publicKeyNonce = await PublicKey.findProgramAddress(
+    [
+      did.toBuffer(),
+      Buffer.from(BLOCKLIST_ACCOUNT_NONCE_SEED_STRING, 'utf8'),
+    ],
+    PROGRAM_ID
+  );
+  return publicKeyNonce[0];
+}
+
 export async function getDirectChannelAccountKey(
   did1: PublicKey,
   did2: PublicKey
@@ -202,6 +225,7 @@ export async function initializeDirectChannel(
 ): Promise<TransactionInstruction> {
   const creatorCEKAccount = await getCekAccountKey(creatorDID, channel);
   const inviteeCEKAccount = await getCekAccountKey(inviteeDID, channel);
+  const inviteeBlocklistAccount = await getBlocklistKey(inviteeDID);
   const keys: AccountMeta[] = [
     { pubkey: payer, isSigner: true, isWritable: true },
     { pubkey: channel, isSigner: false, isWritable: true },
@@ -212,6 +236,7 @@ export async function initializeDirectChannel(
     { pubkey: inviteeCEKAccount, isSigner: false, isWritable: true },
     { pubkey: SYSVAR_RENT_PUBKEY, isSigner: false, isWritable: false },
     { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    { pubkey: inviteeBlocklistAccount, isSigner: false, isWritable: false },
   ];
   const data = SolariumInstruction.initializeDirectChannel(
     creatorCEKs,
@@ -243,7 +268,10 @@ export async function post(
     { pubkey: senderAuthority, isSigner: true, isWritable: false },
     { pubkey: senderCEKAccount, isSigner: false, isWritable: false },
   ];
-  const data = SolariumInstruction.post(message.content).encode();
+  const data = SolariumInstruction.post(
+    message.content,
+    message.epoch
+  ).encode();
   return new TransactionInstruction({
     keys,
     programId: PROGRAM_ID,
@@ -351,8 +379,10 @@ export async function createUserDetails(
 export async function updateUserDetails(
   did: PublicKey,
   authority: PublicKey,
-  alias: string,
-  addressBook: string
+  alias?: string,
+  addressBook?: string,
+  status?: string,
+  avatarUri?: string
 ): Promise<TransactionInstruction> {
   const userDetailsAccount = await getUserDetailsKey(did);
   const keys: AccountMeta[] = [
@@ -362,7 +392,9 @@ export async function updateUserDetails(
   ];
   const data = SolariumInstruction.updateUserDetails(
     alias,
-    addressBook
+    addressBook,
+    status,
+    avatarUri
   ).encode();
   return new TransactionInstruction({
     keys,
@@ -382,7 +414,7 @@ SCHEMA.set(SolariumInstruction, {
     ['addCEK', AddCEK],
     ['removeCEK', RemoveCEK],
     ['createUserDetails', CreateUserDetails],
-    ['updateUserDetails', updateUserDetails],
+    ['updateUserDetails', UpdateUserDetails],
   ],
 });
 SCHEMA.set(InitializeChannel, {
@@ -401,7 +433,10 @@ SCHEMA.set(InitializeDirectChannel, {
 });
 SCHEMA.set(Post, {
   kind: 'struct',
-  fields: [['message', 'string']],
+  fields: [
+    ['message', 'string'],
+    ['epoch', 'u32'],
+  ],
 });
 SCHEMA.set(AddToChannel, {
   kind: 'struct',
@@ -426,7 +461,9 @@ SCHEMA.set(CreateUserDetails, {
 SCHEMA.set(UpdateUserDetails, {
   kind: 'struct',
   fields: [
-    ['alias', 'string'],
-    ['addressBook', 'string'],
+    ['alias', OptionalString],
+    ['addressBook', OptionalString],
+    ['status', OptionalString],
+    ['avatarUri', OptionalString],
   ],
 });
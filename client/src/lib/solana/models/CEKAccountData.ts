@@ -1,5 +1,5 @@
 import { Assignable, AssignablePublicKey, SCHEMA } from '../solanaBorsh';
-import { CEKData } from './CEKData';
+import { CEKData, LegacyCEKData } from './CEKData';
 
 export class CEKAccountData extends Assignable {
   ownerDID: AssignablePublicKey;
@@ -27,6 +27,9 @@ SCHEMA.set(CEKAccountData, {
   fields: [
     ['ownerDID', AssignablePublicKey],
     ['channel', AssignablePublicKey],
-    ['ceks', [CEKData]],
+    [
+      'ceks',
+      { kind: 'versionedArray', type: CEKData, legacyType: LegacyCEKData },
+    ],
   ],
 });
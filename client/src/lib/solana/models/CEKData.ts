@@ -4,9 +4,30 @@ export class CEKData extends Assignable {
   header: string; // TODO
   kid: string;
   encryptedKey: string; // TODO Bytes?
+  epoch: number;
+}
+
+// The layout of a CEK before CEKs recorded their channel epoch
+export class LegacyCEKData extends Assignable {
+  header: string;
+  kid: string;
+  encryptedKey: string;
+
+  toCurrent(): CEKData {
+    return new CEKData({ ...this, epoch: 0 });
+  }
 }
 
 SCHEMA.set(CEKData, {
+  kind: 'struct',
+  fields: [
+    ['header', 'string'],
+    ['kid', 'string'],
+    ['encryptedKey', 'string'],
+    ['epoch', 'u32'],
+  ],
+});
+SCHEMA.set(LegacyCEKData, {
   kind: 'struct',
   fields: [
     ['header', 'string'],
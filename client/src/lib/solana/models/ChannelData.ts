@@ -1,19 +1,29 @@
-import { Assignable, SCHEMA } from '../solanaBorsh';
-import { MessageData } from './MessageData';
+import { Assignable, SCHEMA, VersionedArray } from '../solanaBorsh';
+import { LegacyMessageData, MessageData } from './MessageData';
 import { DEFAULT_MAX_MESSAGE_COUNT, MESSAGE_SIZE_BYTES } from '../../constants';
 
 export class ChannelData extends Assignable {
   name: string;
   messages: MessageData[];
+  epoch: number;
 
   static fromAccount(accountData: Buffer): ChannelData {
-    return ChannelData.decode<ChannelData>(accountData);
+    const channelData = ChannelData.decode<ChannelData>(accountData);
+
+    // A channel whose messages are in the legacy layout was last written before
+    // the epoch was added, and may be followed by stale bytes
+    if ((channelData.messages as VersionedArray<MessageData>).legacy) {
+      channelData.epoch = 0;
+    }
+
+    return channelData;
   }
 
   static empty(name: string): ChannelData {
     return new ChannelData({
       name,
       messages: [],
+      epoch: 0,
     });
   }
 
@@ -27,6 +37,14 @@ SCHEMA.set(ChannelData, {
   kind: 'struct',
   fields: [
     ['name', 'string'],
-    ['messages', [MessageData]],
+    [
+      'messages',
+      {
+        kind: 'versionedArray',
+        type: MessageData,
+        legacyType: LegacyMessageData,
+      },
+    ],
+    ['epoch', 'u32'],
   ],
 });
@@ -6,17 +6,39 @@ export class MessageData extends Assignable {
   timestamp: BN;
   sender: AssignablePublicKey;
   content: string;
+  epoch: number;
 
-  static for(senderDID: PublicKey, content: string): MessageData {
+  static for(senderDID: PublicKey, content: string, epoch = 0): MessageData {
     return new MessageData({
       timestamp: 0,
       sender: AssignablePublicKey.fromPublicKey(senderDID),
       content,
+      epoch,
     });
   }
 }
 
+// The layout of a message before messages recorded their channel epoch
+export class LegacyMessageData extends Assignable {
+  timestamp: BN;
+  sender: AssignablePublicKey;
+  content: string;
+
+  toCurrent(): MessageData {
+    return new MessageData({ ...this, epoch: 0 });
+  }
+}
+
 SCHEMA.set(MessageData, {
+  kind: 'struct',
+  fields: [
+    ['timestamp', 'u64'],
+    ['sender', AssignablePublicKey],
+    ['content', 'string'],
+    ['epoch', 'u32'],
+  ],
+});
+SCHEMA.set(LegacyMessageData, {
   kind: 'struct',
   fields: [
     ['timestamp', 'u64'],
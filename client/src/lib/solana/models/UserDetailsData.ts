@@ -1,10 +1,21 @@
-import { Assignable, SCHEMA } from '../solanaBorsh';
+import { Assignable, isLegacyString, SCHEMA } from '../solanaBorsh';
 
 export class UserDetailsData extends Assignable {
   alias: string;
   addressBook: string;
+  status: string;
+  avatarUri: string;
 
   static fromAccount(accountData: Buffer): UserDetailsData {
+    // An account whose address book is in the legacy layout was last written
+    // before the status was added, and may be followed by stale bytes
+    const aliasLength = accountData.readUInt32LE(0);
+    if (isLegacyString(accountData, 4 + aliasLength)) {
+      return LegacyUserDetailsData.decode<LegacyUserDetailsData>(
+        accountData
+      ).toCurrent();
+    }
+
     return UserDetailsData.decode<UserDetailsData>(accountData);
   }
 
@@ -12,11 +23,32 @@ export class UserDetailsData extends Assignable {
     return new UserDetailsData({
       alias,
       addressBook: '',
+      status: '',
+      avatarUri: '',
     });
   }
 }
 
+// The layout of user details accounts written before the status was added
+export class LegacyUserDetailsData extends Assignable {
+  alias: string;
+  addressBook: string;
+
+  toCurrent(): UserDetailsData {
+    return new UserDetailsData({ ...this, status: '', avatarUri: '' });
+  }
+}
+
 SCHEMA.set(UserDetailsData, {
+  kind: 'struct',
+  fields: [
+    ['alias', 'string'],
+    ['addressBook', { kind: 'versionedString' }],
+    ['status', 'string'],
+    ['avatarUri', 'string'],
+  ],
+});
+SCHEMA.set(LegacyUserDetailsData, {
   kind: 'struct',
   fields: [
     ['alias', 'string'],
@@ -42,6 +42,16 @@ export abstract class Enum extends Assignable {
 
 export const SCHEMA: Schema = new Map();
 
+// The bit of the length prefix that marks a list or string in the current layout,
+// see VersionedVec and VersionedString in program/src/borsh.rs
+export const LAYOUT_MARKER = 0x80000000;
+
+// A list read from a field of type
+// { kind: 'versionedArray', type: T, legacyType: L }, whose items may be stored
+// in a legacy layout. Legacy items are read as L, then converted to T
+// by L.toCurrent()
+export type VersionedArray<T> = T[] & { legacy: boolean };
+
 // TODO PR for leaving extra bytes, a lot of code copied from
 // https://github.com/near/borsh-js/blob/master/borsh-ts/index.ts
 
@@ -59,6 +69,29 @@ function deserializeField(
       return reader[`read${capitalizeFirstLetter(fieldType)}`]();
     }
 
+    if (fieldType.kind === 'versionedArray') {
+      const prefix = reader.readU32();
+      const legacy = (prefix & LAYOUT_MARKER) === 0;
+      const items = [];
+      for (let i = 0; i < (prefix & ~LAYOUT_MARKER); i++) {
+        items.push(
+          legacy
+            ? deserializeStruct(
+                schema,
+                fieldType.legacyType,
+                reader
+              ).toCurrent()
+            : deserializeStruct(schema, fieldType.type, reader)
+        );
+      }
+      return Object.assign(items, { legacy });
+    }
+
+    if (fieldType.kind === 'versionedString') {
+      const len = reader.readU32() & ~LAYOUT_MARKER;
+      return Buffer.from(reader.readFixedArray(len)).toString('utf8');
+    }
+
     if (fieldType instanceof Array) {
       if (typeof fieldType[0] === 'number') {
         return reader.readFixedArray(fieldType[0]);
@@ -126,6 +159,13 @@ export function deserializeExtraBytes<T extends Assignable>(
   return deserializeStruct(schema, classType, reader);
 }
 
+// Checks if the string at the given offset, read from a field of type
+// { kind: 'versionedString' }, is marked as the legacy layout of the struct
+// holding it, in which case the fields following it were not written
+export function isLegacyString(buffer: Buffer, offset: number): boolean {
+  return (buffer.readUInt32LE(offset) & LAYOUT_MARKER) === 0;
+}
+
 export class AssignablePublicKey extends Assignable {
   // The public key bytes
   bytes: number[];
@@ -159,3 +199,31 @@ SCHEMA.set(AssignablePublicKey, {
   kind: 'struct',
   fields: [['bytes', [32]]],
 });
+
+class None extends Assignable {}
+
+// Class representing a Rust Option<String>. Borsh encodes an Option as a u8 tag
+// followed by the value if present, which is the same as a two-variant enum.
+export class OptionalString extends Enum {
+  none: None;
+  some: string;
+
+  static of(value?: string): OptionalString {
+    return value === undefined
+      ? new OptionalString({ none: new None({}) })
+      : new OptionalString({ some: value });
+  }
+}
+
+SCHEMA.set(None, {
+  kind: 'struct',
+  fields: [],
+});
+SCHEMA.set(OptionalString, {
+  kind: 'enum',
+  field: 'enum',
+  values: [
+    ['none', None],
+    ['some', 'string'],
+  ],
+});
@@ -232,8 +232,10 @@ export class SolariumTransaction {
     did: PublicKey,
     authority: PublicKey,
     signCallback: SignCallback,
-    alias: string,
-    addressBook: string,
+    alias?: string,
+    addressBook?: string,
+    status?: string,
+    avatarUri?: string,
     cluster?: ExtendedCluster
   ): Promise<PublicKey> {
     const userDetails = await getUserDetailsKey(did);
@@ -243,7 +245,9 @@ export class SolariumTransaction {
       did,
       authority,
       alias,
-      addressBook
+      addressBook,
+      status,
+      avatarUri
     );
 
     await SolariumTransaction.signAndSendTransaction(
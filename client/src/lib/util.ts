@@ -189,6 +189,8 @@ export type UpdateUserDetailsRequest = TransactionRequest & {
   owner?: KeyMaterial;
   alias?: string;
   addressBook?: AddressBook;
+  status?: string;
+  avatarUri?: string;
 };
 
 export type GetUserDetailsRequest = SolanaRequest & {
@@ -76,6 +76,8 @@ export const createUserDetails = async (
  * @param payer
  * @param alias
  * @param addressBook
+ * @param status
+ * @param avatarUri
  * @param signCallback
  * @param cluster
  */
@@ -86,6 +88,8 @@ export const updateUserDetails = async (
   payer: Keypair | PublicKey,
   alias?: string,
   addressBook?: AddressBook,
+  status?: string,
+  avatarUri?: string,
   signCallback?: SignCallback,
   cluster?: ExtendedCluster
 ): Promise<void> => {
@@ -105,8 +109,10 @@ export const updateUserDetails = async (
     ownerDIDKey,
     pubkeyOf(owner),
     createSignedTx,
-    alias || existingUserDetails.alias,
-    (addressBook || existingUserDetails.addressBook).toChainData(),
+    alias,
+    addressBook?.toChainData(),
+    status,
+    avatarUri,
     cluster
   );
 };
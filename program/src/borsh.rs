@@ -1,7 +1,16 @@
 //! Borsh helpers
 
-use borsh::{maybestd::io::Error, BorshDeserialize, BorshSerialize};
-use std::io::{Result as IoResult, Write};
+use borsh::{
+    maybestd::io::{Error, ErrorKind},
+    schema::{Declaration, Definition},
+    BorshDeserialize, BorshSchema, BorshSerialize,
+};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io::{Result as IoResult, Write},
+    ops::{Deref, DerefMut},
+};
 
 /// Deserializes something and allows for incomplete reading
 pub fn try_from_slice_incomplete<T: BorshDeserialize>(data: &[u8]) -> Result<T, Error> {
@@ -34,3 +43,205 @@ pub fn get_instance_packed_len<T: BorshSerialize>(instance: &T) -> Result<usize,
     instance.serialize(&mut counter)?;
     Ok(counter.count)
 }
+
+/// A type whose Borsh layout has gained fields since accounts holding it were first written
+pub trait LegacyLayout: Sized {
+    /// Deserializes the type from its legacy layout, defaulting the fields added since
+    fn deserialize_legacy(buf: &mut &[u8]) -> IoResult<Self>;
+}
+
+/// A list whose items may be stored in a legacy layout.
+///
+/// Lists are stored with a u32 length prefix, the same layout as a Borsh Vec,
+/// so that lists written before their items gained new fields remain readable.
+/// A list in the current layout is marked by setting `LAYOUT_MARKER` in its length prefix,
+/// which the length of a legacy list, bounded by the size of an account, never sets.
+/// Legacy lists are rewritten in the current layout the next time they are serialized.
+///
+/// Lists are read with room for one more item, so that appending an item does not
+/// reallocate the list, as the program heap never frees the memory it leaves behind.
+#[derive(Clone, Debug)]
+pub struct VersionedVec<T> {
+    items: Vec<T>,
+    legacy: bool,
+}
+impl<T> VersionedVec<T> {
+    /// The bit of the length prefix that marks a list in the current layout
+    pub const LAYOUT_MARKER: u32 = 1 << 31;
+
+    /// Create an empty list with the given capacity
+    pub fn with_capacity(capacity: usize) -> Self {
+        Vec::with_capacity(capacity).into()
+    }
+
+    /// Checks if the list was read from the legacy layout
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+}
+impl<T> Default for VersionedVec<T> {
+    fn default() -> Self {
+        Vec::new().into()
+    }
+}
+impl<T> From<Vec<T>> for VersionedVec<T> {
+    fn from(items: Vec<T>) -> Self {
+        Self {
+            items,
+            legacy: false,
+        }
+    }
+}
+impl<T> Deref for VersionedVec<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Vec<T> {
+        &self.items
+    }
+}
+impl<T> DerefMut for VersionedVec<T> {
+    fn deref_mut(&mut self) -> &mut Vec<T> {
+        &mut self.items
+    }
+}
+impl<T: PartialEq> PartialEq for VersionedVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.items == other.items
+    }
+}
+impl<T: PartialEq> PartialEq<Vec<T>> for VersionedVec<T> {
+    fn eq(&self, other: &Vec<T>) -> bool {
+        self.items == *other
+    }
+}
+impl<T: BorshSerialize> BorshSerialize for VersionedVec<T> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        serialize_marked_len(self.items.len(), writer)?;
+        self.items
+            .iter()
+            .try_for_each(|item| item.serialize(writer))
+    }
+}
+impl<T: BorshDeserialize + LegacyLayout> BorshDeserialize for VersionedVec<T> {
+    fn deserialize(buf: &mut &[u8]) -> IoResult<Self> {
+        let (len, legacy) = deserialize_marked_len(buf)?;
+
+        // Every item takes at least one byte, which bounds the allocation for corrupt lengths
+        let mut items = Vec::with_capacity(len.min(buf.len()) + 1);
+        for _ in 0..len {
+            items.push(match legacy {
+                true => T::deserialize_legacy(buf)?,
+                false => T::deserialize(buf)?,
+            });
+        }
+        Ok(Self { items, legacy })
+    }
+}
+impl<T: BorshSchema> BorshSchema for VersionedVec<T> {
+    fn add_definitions_recursively(definitions: &mut HashMap<Declaration, Definition>) {
+        Vec::<T>::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> Declaration {
+        Vec::<T>::declaration()
+    }
+}
+
+/// A string that marks the layout of the struct holding it.
+///
+/// Strings are stored with a u32 length prefix, the same layout as a Borsh String,
+/// with `VersionedVec::LAYOUT_MARKER` set in the prefix when the fields following
+/// the string were written, so that structs whose layout has gained fields since
+/// can tell them from the stale bytes that follow older accounts.
+#[derive(Clone, Debug, Default)]
+pub struct VersionedString {
+    value: String,
+    legacy: bool,
+}
+impl VersionedString {
+    /// Checks if the string was read from the legacy layout
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+}
+impl From<String> for VersionedString {
+    fn from(value: String) -> Self {
+        Self {
+            value,
+            legacy: false,
+        }
+    }
+}
+impl Deref for VersionedString {
+    type Target = String;
+
+    fn deref(&self) -> &String {
+        &self.value
+    }
+}
+impl DerefMut for VersionedString {
+    fn deref_mut(&mut self) -> &mut String {
+        &mut self.value
+    }
+}
+impl PartialEq for VersionedString {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+impl PartialEq<&str> for VersionedString {
+    fn eq(&self, other: &&str) -> bool {
+        self.value == *other
+    }
+}
+impl BorshSerialize for VersionedString {
+    fn serialize<W: Write>(&self, writer: &mut W) -> IoResult<()> {
+        serialize_marked_len(self.value.len(), writer)?;
+        writer.write_all(self.value.as_bytes())
+    }
+}
+impl BorshDeserialize for VersionedString {
+    fn deserialize(buf: &mut &[u8]) -> IoResult<Self> {
+        let (len, legacy) = deserialize_marked_len(buf)?;
+        if len > buf.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "unexpected end of string",
+            ));
+        }
+        let (bytes, rest) = buf.split_at(len);
+        *buf = rest;
+
+        let value = String::from_utf8(bytes.to_vec())
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "string is not valid UTF-8"))?;
+        Ok(Self { value, legacy })
+    }
+}
+impl BorshSchema for VersionedString {
+    fn add_definitions_recursively(definitions: &mut HashMap<Declaration, Definition>) {
+        String::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> Declaration {
+        String::declaration()
+    }
+}
+
+/// Writes a length prefix marked as the current layout
+fn serialize_marked_len<W: Write>(len: usize, writer: &mut W) -> IoResult<()> {
+    let len = u32::try_from(len)
+        .ok()
+        .filter(|len| len & VersionedVec::<()>::LAYOUT_MARKER == 0)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "length is too large"))?;
+    (len | VersionedVec::<()>::LAYOUT_MARKER).serialize(writer)
+}
+
+/// Reads a length prefix, returning the length and whether it is in the legacy layout
+fn deserialize_marked_len(buf: &mut &[u8]) -> IoResult<(usize, bool)> {
+    let prefix = u32::deserialize(buf)?;
+    let legacy = prefix & VersionedVec::<()>::LAYOUT_MARKER == 0;
+    Ok((
+        (prefix & !VersionedVec::<()>::LAYOUT_MARKER) as usize,
+        legacy,
+    ))
+}
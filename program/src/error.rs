@@ -32,6 +32,127 @@ pub enum SolariumError {
     /// Incorrect account address derivation
     #[error("Incorrect account address derivation")]
     AddressDerivationMismatch,
+
+    /// The sender is not allowed to send notifications to this user
+    #[error("Sender not authorised to send notifications to this user")]
+    NotificationNotAuthorized,
+
+    /// The notifications allowlist is full
+    #[error("Notifications allowlist is full")]
+    AllowlistFull,
+
+    /// The sender has been blocked by the recipient
+    #[error("Sender has been blocked by the recipient")]
+    Blocked,
+
+    /// The blocklist is full
+    #[error("Blocklist is full")]
+    BlocklistFull,
+
+    /// The alias is too short or too long
+    #[error("Alias length is out of bounds")]
+    AliasInvalidLength,
+
+    /// The alias contains a character that is not allowed
+    #[error("Alias contains an invalid character")]
+    AliasInvalidCharacter,
+
+    /// The address book page has no room for another entry
+    #[error("Address book page is full")]
+    AddressBookPageFull,
+
+    /// An entry with this id already exists in the address book page
+    #[error("Contact entry already exists")]
+    ContactEntryExists,
+
+    /// No entry with this id exists in the address book page
+    #[error("Contact entry not found")]
+    ContactEntryNotFound,
+
+    /// The encrypted contact entry exceeds the maximum entry size
+    #[error("Contact entry is too large")]
+    ContactEntryTooLarge,
+
+    /// The one-time prekey pool has no room for more prekeys
+    #[error("One-time prekey pool is full")]
+    PreKeyPoolFull,
+
+    /// The one-time prekey pool is empty
+    #[error("No one-time prekeys left")]
+    PreKeysExhausted,
+
+    /// The message or CEK belongs to an earlier channel epoch
+    #[error("Stale channel epoch")]
+    StaleEpoch,
+
+    /// The epoch is neither the current channel epoch nor the next one
+    #[error("Invalid channel epoch")]
+    InvalidEpoch,
+
+    /// The CEK account already holds the maximum number of CEKs
+    #[error("Too many CEKs")]
+    TooManyCEKs,
+
+    /// A field of the CEK exceeds its maximum size
+    #[error("CEK is too large")]
+    CEKTooLarge,
+
+    /// The key ID of the CEK is not a verification method on the DID
+    #[error("Key is not on the DID")]
+    KeyNotOnDID,
+
+    /// The CEK account already holds a CEK with this key ID
+    #[error("CEK already exists")]
+    CEKAlreadyExists,
+
+    /// The CEK header is not a valid typed JWE header
+    #[error("Invalid CEK header")]
+    InvalidCEKHeader,
+
+    /// The channel does not have private membership
+    #[error("Channel is not private")]
+    ChannelNotPrivate,
+
+    /// The channel members page has no room for another member
+    #[error("Channel members page is full")]
+    ChannelMembersPageFull,
+
+    /// The DID is not listed on the channel members page
+    #[error("Channel member not found")]
+    ChannelMemberNotFound,
+
+    /// The invite code was not redeemed with its invite keypair
+    #[error("Invalid invite code key")]
+    InvalidInviteKey,
+
+    /// The invite code has expired
+    #[error("Invite code expired")]
+    InviteCodeExpired,
+
+    /// The invite code has been redeemed the maximum number of times
+    #[error("Invite code has no uses left")]
+    InviteCodeExhausted,
+
+    /// The notifications account size is zero or too large to allocate
+    #[error("Notifications size is out of bounds")]
+    NotificationsSizeOutOfBounds,
+
+    /// The status text is longer than UserDetails::MAX_STATUS_SIZE
+    #[error("Status is too long")]
+    StatusTooLong,
+
+    /// The avatar URI is longer than UserDetails::MAX_AVATAR_URI_SIZE
+    #[error("Avatar URI is too long")]
+    AvatarUriTooLong,
+
+    /// Members must be added to the last channel members page,
+    /// or to the page following it once the last page is full
+    #[error("Invalid channel members page")]
+    InvalidChannelMembersPage,
+
+    /// A member was given no CEKs
+    #[error("No CEKs given")]
+    NoCEKs,
 }
 impl From<SolariumError> for ProgramError {
     fn from(e: SolariumError) -> Self {
@@ -1,12 +1,20 @@
 //! Program instructions
 
-use crate::state::{get_notifications_account_address_with_seed, NotificationType};
+use crate::state::{
+    get_address_book_account_address_with_seed, get_alias_account_address_with_seed,
+    get_blocklist_account_address_with_seed, get_notifications_account_address_with_seed,
+    get_prekey_bundle_account_address_with_seed, ContactEntry, Notification, NotificationType,
+    OneTimePreKey, SignedPreKey,
+};
 use {
     crate::{
         id,
         state::{
-            get_cek_account_address_with_seed, get_userdetails_account_address_with_seed, CEKData,
-            Message,
+            get_cek_account_address_with_seed, get_channel_members_account_address_with_seed,
+            get_invite_code_account_address_with_seed, get_membership_seed,
+            get_pending_invite_account_address_with_seed,
+            get_private_cek_account_address_with_seed, get_userdetails_account_address_with_seed,
+            CEKData, Message,
         },
     },
     borsh::{BorshDeserialize, BorshSerialize},
@@ -17,6 +25,16 @@ use {
     },
 };
 
+/// Identifies the notifications to remove with a `SolariumInstruction::AckNotifications` instruction
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, PartialEq)]
+pub enum NotificationsAck {
+    /// Remove all notifications up to and including this index.
+    /// Note, indices shift when older notifications are pushed out by new ones.
+    UpToIndex(u16),
+    /// Remove all notifications relating to this public key
+    Pubkey(Pubkey),
+}
+
 /// Instructions supported by the program
 #[derive(Clone, Debug, BorshSerialize, BorshDeserialize, PartialEq)]
 pub enum SolariumInstruction {
@@ -31,6 +49,7 @@ pub enum SolariumInstruction {
     /// 4. `[writeable]` Unallocated creator CEK account, must be a program address
     /// 5. `[]` Rent sysvar
     /// 6. `[]` System program
+    /// 7. `[writable]` Unallocated channel members account for page 0, must be a program address
     InitializeChannel {
         // /// Size of the channel
         // size: u8,
@@ -55,6 +74,10 @@ pub enum SolariumInstruction {
     /// 6. `[writeable]` Unallocated invitee CEK account, must be a program address
     /// 7. `[]` Rent sysvar
     /// 8. `[]` System program
+    /// 9. `[]` Invitee blocklist account, must be a program address (may be unallocated).
+    ///    The channel is not created if the invitee has blocked the creator.
+    /// 10. `[writable]` (Optional) Invitee notifications account.
+    ///     If it is initialized, the invitee is notified of the new direct channel.
     InitializeDirectChannel {
         /// The initial set of CEKs that are added to the creator's CEK Account
         /// They should be signed by each key in the creator DID.
@@ -76,6 +99,9 @@ pub enum SolariumInstruction {
     Post {
         /// The encrypted message content
         message: String,
+        /// The channel epoch of the CEK that the message is encrypted with.
+        /// Must be the current epoch of the channel.
+        epoch: u32,
     },
 
     /// Create a new CEK Account for a DID and a channel
@@ -89,15 +115,25 @@ pub enum SolariumInstruction {
     /// 1. `[]` Invitee DID account - must be owned by the sol-did program
     /// 2. `[]` Inviter DID account - must be owned by the sol-did program
     /// 3. `[signer]` Inviter authority - must be a key on the inviter DID
-    /// 4. `[]` Inviter CEK account, this ensures the inviter has permissions to add new users
+    /// 4. `[]` Inviter CEK account, must hold a CEK for the current channel epoch
     /// 5. `[writable]` Unallocated CEK account, must be a program address
-    /// 6. `[]` Channel account, must be previously initialized
+    /// 6. `[writable]` Channel account, must be previously initialized
     /// 7. `[]` Rent sysvar
     /// 8. `[]` System program
+    /// 9. `[writable]` Channel members account for `members_page`, must be a program address.
+    ///    It is created if it is unallocated.
+    /// 10. `[]` Invitee blocklist account, must be a program address (may be unallocated).
+    ///     The invitee is not notified if they have blocked the inviter.
+    /// 11. `[writable]` (Optional) Invitee notifications account.
+    ///     If it is initialized, the invitee is notified of the new channel.
     AddToChannel {
         /// The initial set of CEKs that are added to the invited user's CEK Account
         /// They should be signed by each key in the DID.
         ceks: Vec<CEKData>,
+
+        /// The page of the channel members list that the invitee is added to,
+        /// which must be `ChannelData::next_members_page()`
+        members_page: u16,
     },
 
     /// Add a CEK to an existing CEKAccount
@@ -143,7 +179,8 @@ pub enum SolariumInstruction {
         size: u32,
     },
 
-    /// Updates a UserDetails account
+    /// Updates a UserDetails account.
+    /// Only the fields that are set are changed.
     ///
     /// Accounts expected by this instruction:
     ///
@@ -152,9 +189,13 @@ pub enum SolariumInstruction {
     /// 2. `[writable]` UserDetails account, must be owned by the owner DID
     UpdateUserDetails {
         /// The user's new public alias
-        alias: String,
+        alias: Option<String>,
         /// The user's encrypted address book
-        address_book: String,
+        address_book: Option<String>,
+        /// The user's public status text
+        status: Option<String>,
+        /// A URI for the user's public avatar image
+        avatar_uri: Option<String>,
     },
 
     /// Creates a Notifications account
@@ -168,15 +209,27 @@ pub enum SolariumInstruction {
     /// 4. `[]` Rent sysvar
     /// 5. `[]` System program
     CreateNotifications {
-        /// The size of the notifications cache
-        size: u8,
+        /// The size of the notifications cache, between 1 and Notifications::MAX_SIZE.
+        /// Larger caches may need to be reached with `ResizeNotifications`,
+        /// as an instruction can only allocate MAX_PERMITTED_DATA_INCREASE bytes.
+        size: u16,
     },
 
     /// Add a notification for a user
     ///
+    /// The sender must be a member of the channel referenced by the notification,
+    /// unless the owner has allowlisted the sender or opened their notifications account.
+    /// Contact requests may be sent by any DID, and must refer to the sender.
+    /// The owner can block senders to stop receiving notifications from them.
+    ///
     /// Accounts expected by this instruction:
     ///
     /// 0. `[writable]` Notifications account, must be previously initialized
+    /// 1. `[]` Sender DID account
+    /// 2. `[signer]` Sender signer account (must be an authority on the sender DID)
+    /// 3. `[]` Owner DID account - the DID that the notifications account belongs to
+    /// 4. `[]` Owner blocklist account, must be a program address (may be unallocated)
+    /// 5. `[]` (Optional) Sender CEK account for the channel referenced by the notification
     AddNotification {
         /// The notification type
         notification_type: NotificationType,
@@ -184,250 +237,1942 @@ pub enum SolariumInstruction {
         /// The key should be interpreted in relation to the notification type.
         pubkey: Pubkey,
     },
-}
 
-/// Create a `SolariumInstruction::InitializeChannel` instruction
-pub fn initialize_channel(
-    funder_account: &Pubkey,
-    channel: &Pubkey,
-    name: String,
-    creator_did: &Pubkey,
-    creator_authority: &Pubkey,
-    ceks: Vec<CEKData>,
-) -> Instruction {
-    let (creator_cek_account, _) = get_cek_account_address_with_seed(&id(), creator_did, channel);
-    Instruction::new_with_borsh(
-        id(),
-        &SolariumInstruction::InitializeChannel { name, ceks },
-        vec![
-            AccountMeta::new(*funder_account, true),
-            AccountMeta::new(*channel, false),
-            AccountMeta::new_readonly(*creator_did, false),
-            AccountMeta::new_readonly(*creator_authority, true),
-            AccountMeta::new(creator_cek_account, false),
-            AccountMeta::new_readonly(sysvar::rent::id(), false),
-            AccountMeta::new_readonly(system_program::id(), false),
-        ],
-    )
-}
+    /// Post a message to the provided channel account, notifying the mentioned DIDs
+    ///
+    /// Each mentioned DID must be a member of the channel and must not have blocked the sender.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable]` Channel account, must be previously initialized
+    /// 1. `[]` Sender DID account
+    /// 2. `[signer]` Sender signer account (must be an authority on the sender DID)
+    /// 3. `[]` Sender CEK account for this channel (proves permissions to write to this channel)
+    /// 4. `[]` Mentioned DID CEK account for this channel (proves the DID is a member)
+    /// 5. `[writable]` Mentioned DID notifications account, must be previously initialized
+    /// 6. `[]` Mentioned DID blocklist account, must be a program address (may be unallocated)
+    ///
+    /// Accounts 4 to 6 are repeated for each mentioned DID, in the order given in `mentions`.
+    PostWithMentions {
+        /// The encrypted message content
+        message: String,
+        /// The DIDs mentioned in the message
+        mentions: Vec<Pubkey>,
+        /// The channel epoch of the CEK that the message is encrypted with.
+        /// Must be the current epoch of the channel.
+        epoch: u32,
+    },
 
-/// Create a `SolariumInstruction::InitializeChannel` instruction
-pub fn initialize_direct_channel(
-    funder_account: &Pubkey,
-    channel: &Pubkey,
-    creator_did: &Pubkey,
-    creator_authority: &Pubkey,
-    invitee_did: &Pubkey,
-    creator_ceks: Vec<CEKData>,
-    invitee_ceks: Vec<CEKData>,
-) -> Instruction {
-    let (creator_cek_account, _) = get_cek_account_address_with_seed(&id(), creator_did, channel);
-    let (invitee_cek_account, _) = get_cek_account_address_with_seed(&id(), invitee_did, channel);
-    Instruction::new_with_borsh(
-        id(),
-        &SolariumInstruction::InitializeDirectChannel {
-            creator_ceks,
-            invitee_ceks,
-        },
-        vec![
-            AccountMeta::new(*funder_account, true),
-            AccountMeta::new(*channel, false),
-            AccountMeta::new_readonly(*creator_did, false),
-            AccountMeta::new_readonly(*creator_authority, true),
-            AccountMeta::new(creator_cek_account, false),
-            AccountMeta::new_readonly(*invitee_did, false),
-            AccountMeta::new(invitee_cek_account, false),
-            AccountMeta::new_readonly(sysvar::rent::id(), false),
-            AccountMeta::new_readonly(system_program::id(), false),
-        ],
-    )
-}
+    /// Set the policy determining who can send notifications to a user
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Notifications account, must be owned by the owner DID
+    SetNotificationsPolicy {
+        /// If set, any DID can send notifications to the user
+        open: bool,
+        /// DIDs that can send notifications to the user without being members of the referenced channel
+        allowlist: Vec<Pubkey>,
+    },
 
-/// Create a `SolariumInstruction::Post` instruction
-pub fn post(channel: &Pubkey, sender_authority: &Pubkey, message: &Message) -> Instruction {
-    let (sender_cek_account, _) =
-        get_cek_account_address_with_seed(&id(), &message.sender, channel);
-    Instruction::new_with_borsh(
-        id(),
-        &SolariumInstruction::Post {
-            message: message.content.to_string(),
-        },
-        vec![
-            AccountMeta::new(*channel, false),
-            AccountMeta::new_readonly(message.sender, false),
-            AccountMeta::new_readonly(*sender_authority, true),
-            AccountMeta::new_readonly(sender_cek_account, false),
-        ],
-    )
-}
+    /// Block a DID from sending notifications or creating direct channels with the owner
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Owner DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Owner authority - must be a key on the owner DID
+    /// 3. `[writable]` Blocklist account, must be a program address derived from the owner DID.
+    ///    Allocated on first use.
+    /// 4. `[]` Rent sysvar
+    /// 5. `[]` System program
+    Block {
+        /// The DID to block
+        did: Pubkey,
+    },
 
-/// Create a `SolariumInstruction::AddToChannel` instruction
-pub fn add_to_channel(
-    funder_account: &Pubkey,
-    channel: &Pubkey,
-    invitee_did: &Pubkey,
-    inviter_did: &Pubkey,
-    inviter_authority: &Pubkey,
-    ceks: Vec<CEKData>,
-) -> Instruction {
-    let (inviter_cek_account, _) = get_cek_account_address_with_seed(&id(), inviter_did, channel);
-    let (invitee_cek_account, _) = get_cek_account_address_with_seed(&id(), invitee_did, channel);
-    Instruction::new_with_borsh(
-        id(),
-        &SolariumInstruction::AddToChannel { ceks },
-        vec![
-            AccountMeta::new(*funder_account, true),
-            AccountMeta::new_readonly(*invitee_did, false),
-            AccountMeta::new_readonly(*inviter_did, false),
-            AccountMeta::new_readonly(*inviter_authority, true),
-            AccountMeta::new_readonly(inviter_cek_account, false),
-            AccountMeta::new(invitee_cek_account, false),
-            AccountMeta::new_readonly(*channel, false),
-            AccountMeta::new_readonly(sysvar::rent::id(), false),
-            AccountMeta::new_readonly(system_program::id(), false),
-        ],
-    )
-}
+    /// Unblock a previously blocked DID
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Blocklist account, must be owned by the owner DID
+    Unblock {
+        /// The DID to unblock
+        did: Pubkey,
+    },
 
-/// Create a `SolariumInstruction::AddCEK` instruction
-pub fn add_cek(
-    owner_did: &Pubkey,
-    owner_authority: &Pubkey,
-    channel: &Pubkey,
-    cek: CEKData,
-) -> Instruction {
-    let (owner_cek_account, _) = get_cek_account_address_with_seed(&id(), owner_did, channel);
-    Instruction::new_with_borsh(
-        id(),
-        &SolariumInstruction::AddCEK { cek },
-        vec![
-            AccountMeta::new_readonly(*owner_did, false),
-            AccountMeta::new_readonly(*owner_authority, true),
-            AccountMeta::new(owner_cek_account, false),
-        ],
-    )
-}
+    /// Remove handled notifications from a user's notifications account
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Notifications account, must be owned by the owner DID
+    AckNotifications {
+        /// The notifications to remove
+        ack: NotificationsAck,
+    },
 
-/// Create a `SolariumInstruction::RemoveCEK` instruction
-pub fn remove_cek(
-    owner_did: &Pubkey,
-    owner_authority: &Pubkey,
-    channel: &Pubkey,
-    kid: String,
-) -> Instruction {
-    let (owner_cek_account, _) = get_cek_account_address_with_seed(&id(), owner_did, channel);
-    Instruction::new_with_borsh(
-        id(),
-        &SolariumInstruction::RemoveCEK { kid },
-        vec![
-            AccountMeta::new_readonly(*owner_did, false),
-            AccountMeta::new_readonly(*owner_authority, true),
-            AccountMeta::new(owner_cek_account, false),
-        ],
-    )
-}
+    /// Remove all notifications from a user's notifications account
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Notifications account, must be owned by the owner DID
+    ClearNotifications,
 
-/// Create a `SolariumInstruction::CreateUserDetails` instruction
-pub fn create_user_details(
-    funder_account: &Pubkey,
-    owner_did: &Pubkey,
-    owner_authority: &Pubkey,
-    alias: String,
-    size: u32,
-) -> Instruction {
-    let (owner_userdetails_account, _) =
-        get_userdetails_account_address_with_seed(&id(), owner_did);
-    Instruction::new_with_borsh(
-        id(),
-        &SolariumInstruction::CreateUserDetails {
-            alias,
-            address_book: "".to_string(),
-            size,
-        },
-        vec![
-            AccountMeta::new(*funder_account, true),
-            AccountMeta::new_readonly(*owner_did, false),
-            AccountMeta::new_readonly(*owner_authority, true),
-            AccountMeta::new(owner_userdetails_account, false),
-            AccountMeta::new_readonly(sysvar::rent::id(), false),
-            AccountMeta::new_readonly(system_program::id(), false),
-        ],
-    )
-}
+    /// Change the capacity of a Notifications account
+    ///
+    /// If the new size is smaller than the number of notifications, the oldest are removed.
+    /// Rent is topped up from, or refunded to, the funding account.
+    /// The account can grow by at most MAX_PERMITTED_DATA_INCREASE bytes per instruction.
+    /// Accounts created before the notification sender and timestamp were recorded
+    /// must be resized before they can receive notifications again.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Owner DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Owner authority - must be a key on the owner DID
+    /// 3. `[writable]` Notifications account, must be owned by the owner DID
+    /// 4. `[]` Rent sysvar
+    /// 5. `[]` System program
+    ResizeNotifications {
+        /// The new size of the notifications cache, between 1 and Notifications::MAX_SIZE
+        size: u16,
+    },
 
-/// Create a `SolariumInstruction::UpdateUserDetails` instruction
-pub fn update_user_details(
-    owner_did: &Pubkey,
-    owner_authority: &Pubkey,
-    alias: String,
-    address_book: String,
-) -> Instruction {
-    let (owner_userdetails_account, _) =
-        get_userdetails_account_address_with_seed(&id(), owner_did);
-    Instruction::new_with_borsh(
-        id(),
-        &SolariumInstruction::UpdateUserDetails {
-            alias,
-            address_book,
-        },
-        vec![
-            AccountMeta::new_readonly(*owner_did, false),
-            AccountMeta::new_readonly(*owner_authority, true),
-            AccountMeta::new(owner_userdetails_account, false),
-        ],
-    )
-}
+    /// Close a Notifications account, refunding its rent to the owner authority
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[writable, signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Notifications account, must be owned by the owner DID
+    CloseNotifications,
 
-/// Create a `SolariumInstruction::CreateNotifications` instruction
-pub fn create_notifications(
-    funder_account: &Pubkey,
+    /// Change the size of a UserDetails account
+    ///
+    /// Rent is topped up from, or refunded to, the funding account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Owner DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Owner authority - must be a key on the owner DID
+    /// 3. `[writable]` UserDetails account, must be owned by the owner DID
+    /// 4. `[]` Rent sysvar
+    /// 5. `[]` System program
+    ResizeUserDetails {
+        /// The new size of the userDetails account
+        size: u32,
+    },
+
+    /// Close a UserDetails account, refunding its rent to the owner authority
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[writable, signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` UserDetails account, must be owned by the owner DID
+    CloseUserDetails,
+
+    /// Claim a unique alias for a DID
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Owner DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Owner authority - must be a key on the owner DID
+    /// 3. `[writable]` Unallocated alias record account, must be a program address derived from the alias
+    /// 4. `[]` Rent sysvar
+    /// 5. `[]` System program
+    ClaimAlias {
+        /// The alias to claim
+        alias: String,
+    },
+
+    /// Release an alias, closing the alias record and refunding its rent
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Alias record account, must be owned by the owner DID
+    /// 3. `[writable]` Recipient of the refunded rent
+    ReleaseAlias,
+
+    /// Transfer an alias to another DID
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Alias record account, must be owned by the owner DID
+    TransferAlias {
+        /// The DID that the alias is transferred to
+        new_owner_did: Pubkey,
+    },
+
+    /// Add an encrypted entry to a page of the owner's address book
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Owner DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Owner authority - must be a key on the owner DID
+    /// 3. `[writable]` Address book page account, must be a program address derived from the owner DID
+    ///    and the page index. Allocated on first use.
+    /// 4. `[]` Rent sysvar
+    /// 5. `[]` System program
+    AddContactEntry {
+        /// The index of the address book page
+        page: u16,
+        /// The entry to add
+        entry: ContactEntry,
+    },
+
+    /// Replace the encrypted data of an entry in a page of the owner's address book
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Address book page account, must be owned by the owner DID
+    UpdateContactEntry {
+        /// The updated entry, identified by its id
+        entry: ContactEntry,
+    },
+
+    /// Remove an entry from a page of the owner's address book
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` Address book page account, must be owned by the owner DID
+    RemoveContactEntry {
+        /// The id of the entry to remove
+        id: u32,
+    },
+
+    /// Create a prekey bundle account for a DID
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Owner DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Owner authority - must be a key on the owner DID
+    /// 3. `[writable]` PreKeyBundle account, must be a program address derived from the owner DID
+    /// 4. `[]` Rent sysvar
+    /// 5. `[]` System program
+    CreatePreKeyBundle {
+        /// The owner's long-term identity public key
+        identity_key: [u8; 32],
+        /// The owner's signed prekey
+        signed_prekey: SignedPreKey,
+        /// The initial pool of one-time prekeys
+        one_time_prekeys: Vec<OneTimePreKey>,
+    },
+
+    /// Hand out the oldest one-time prekey from a prekey bundle, removing it from the pool.
+    /// The prekey is borsh-serialized into the transaction return data.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable]` PreKeyBundle account of the owner
+    /// 1. `[]` Requester DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Requester authority - must be a key on the requester DID
+    ConsumeOneTimePreKey,
+
+    /// Add one-time prekeys to a prekey bundle, optionally rotating the signed prekey
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` PreKeyBundle account, must be owned by the owner DID
+    RefillPreKeys {
+        /// A new signed prekey to replace the current one
+        signed_prekey: Option<SignedPreKey>,
+        /// One-time prekeys to add to the pool
+        one_time_prekeys: Vec<OneTimePreKey>,
+    },
+
+    /// Rotate the CEK of a channel, replacing the CEKs of its members with CEKs for a new epoch.
+    ///
+    /// The first transaction of a rotation increments the channel epoch.
+    /// Channels with more members than fit in one transaction are rotated
+    /// by sending further transactions for the same epoch,
+    /// which only replace the CEKs of members that hold no CEK for the epoch yet.
+    /// Members whose CEKs are not replaced can no longer post to the channel.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable]` Channel account, must be previously initialized
+    /// 1. `[]` Rotator DID account
+    /// 2. `[signer]` Rotator signer account (must be an authority on the rotator DID)
+    /// 3. `[]` Rotator CEK account for this channel, must hold a CEK for the current channel epoch
+    /// 4. `[]` Member DID account, which must hold the keys of the member CEKs
+    /// 5. `[writable]` Member CEK account for this channel
+    /// 6. `[writable]` Member notifications account, notified of the rotation if initialized
+    ///
+    /// Accounts 4 to 6 are repeated for each member, in the order given in `member_ceks`.
+    RotateChannelKey {
+        /// The new epoch, or the current epoch to continue a rotation
+        epoch: u32,
+        /// The new CEKs of each member, encrypted with each key in the member DID.
+        /// Each member must be given at least one CEK.
+        member_ceks: Vec<Vec<CEKData>>,
+    },
+
+    /// Remove the CEKs for keys that are no longer verification methods on the owner DID.
+    /// Anyone can prune a CEK account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[writable]` CEK account, must be owned by the owner DID
+    PruneStaleCEKs,
+
+    /// Replace a CEK in an existing CEKAccount in a single step
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` CEK account, must be owned by the owner DID
+    ReplaceCEK {
+        /// The key id of the CEK to replace
+        kid: String,
+        /// The CEK that replaces it
+        cek: CEKData,
+    },
+
+    /// Add CEKs to several CEKAccounts of the same owner, e.g. for a new device on the owner DID
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` CEK account, must be owned by the owner DID
+    ///
+    /// Account 2 is repeated for each CEK, in the order given in `ceks`.
+    AddCEKs {
+        /// The CEKs to add, one per CEK account
+        ceks: Vec<CEKData>,
+    },
+
+    /// Add a CEK to another member's CEK account, e.g. to restore their access after a device loss.
+    /// The CEK must be for a key on the member DID that does not already have a CEK in the account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Provider DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Provider authority - must be a key on the provider DID
+    /// 2. `[]` Provider CEK account for the channel, must hold a CEK for the current channel epoch
+    /// 3. `[]` Member DID account - must be owned by the sol-did program
+    /// 4. `[writable]` Member CEK account for the channel
+    /// 5. `[]` Channel account
+    ///
+    /// The provider must hold a CEK for the current channel epoch, and `cek` must belong to it.
+    ProvisionMemberCEK {
+        /// The CEK to add, encrypted with a key on the member DID
+        cek: CEKData,
+    },
+
+    /// Create a new channel with private membership.
+    /// Member CEK accounts are derived from blinded membership seeds (see `get_membership_seed`)
+    /// so that membership cannot be checked from a DID and the channel address alone.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[writable]` Unallocated channel account, must be a program address
+    /// 2. `[]` Creator DID account - must be owned by the sol-did program
+    /// 3. `[signer]` Creator authority - must be a key on the creator DID
+    /// 4. `[signer]` Creator membership key for the channel
+    /// 5. `[writeable]` Unallocated creator private CEK account, must be a program address
+    /// 6. `[]` Rent sysvar
+    /// 7. `[]` System program
+    InitializePrivateChannel {
+        /// The channel name
+        name: String,
+
+        /// The initial set of CEKs that are added to the creator's private CEK Account
+        ceks: Vec<CEKData>,
+    },
+
+    /// Create a new private CEK Account for a private channel, adding a member to it.
+    /// The invitee is identified only by their blinded membership seed,
+    /// so neither the invitee DID nor their notifications account is passed.
+    /// The seed cannot be checked against the invitee DID here, but the account is derived
+    /// from the channel, so it only proves membership of the channel that the inviter is a member of.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Inviter DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Inviter authority - must be a key on the inviter DID
+    /// 3. `[signer]` Inviter membership key for the channel
+    /// 4. `[]` Inviter private CEK account, must hold a CEK for the current channel epoch
+    /// 5. `[writable]` Unallocated invitee private CEK account, must be a program address
+    /// 6. `[]` Channel account, must be a private channel
+    /// 7. `[]` Rent sysvar
+    /// 8. `[]` System program
+    AddToPrivateChannel {
+        /// The blinded membership seed of the invitee
+        invitee_membership_seed: [u8; 32],
+
+        /// The initial set of CEKs that are added to the invitee's private CEK Account
+        ceks: Vec<CEKData>,
+    },
+
+    /// Post a message to a private channel.
+    /// The sender proves membership by signing with their membership key,
+    /// which the program hashes with the sender DID and channel to find their private CEK account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable]` Channel account, must be a private channel
+    /// 1. `[]` Sender DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Sender authority - must be a key on the sender DID
+    /// 3. `[signer]` Sender membership key for the channel
+    /// 4. `[]` Sender private CEK account
+    PostPrivate {
+        /// The encrypted message
+        message: String,
+
+        /// The channel epoch of the CEK that the message is encrypted with
+        epoch: u32,
+    },
+
+    /// Leave a group channel, closing the member's CEK account
+    /// and removing them from the channel members list.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Member DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Member authority - must be a key on the member DID
+    /// 2. `[writable]` Member CEK account for the channel
+    /// 3. `[writable]` Channel account
+    /// 4. `[writable]` Channel members account for `members_page`
+    /// 5. `[writable]` Recipient of the lamports of the closed CEK account
+    LeaveChannel {
+        /// The page of the channel members list that lists the member
+        members_page: u16,
+    },
+
+    /// Invite a DID to a group channel.
+    /// Unlike `AddToChannel`, the invitee's CEK account is only created when they accept the invite.
+    /// Until then, the CEKs are held in a pending invite account, funded by the inviter.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Invitee DID account - must be owned by the sol-did program
+    /// 2. `[]` Inviter DID account - must be owned by the sol-did program
+    /// 3. `[signer]` Inviter authority - must be a key on the inviter DID
+    /// 4. `[]` Inviter CEK account, must hold a CEK for the current channel epoch
+    /// 5. `[writable]` Unallocated pending invite account, must be a program address
+    /// 6. `[]` Channel account, must be previously initialized
+    /// 7. `[]` Rent sysvar
+    /// 8. `[]` System program
+    /// 9. `[]` Invitee blocklist account, must be a program address (may be unallocated).
+    ///    The invitee is not notified if they have blocked the inviter.
+    /// 10. `[writable]` (Optional) Invitee notifications account.
+    ///     If it is initialized, the invitee is notified of the invite.
+    InviteToChannel {
+        /// The CEKs for the invitee's CEK account, one per key in the invitee DID
+        ceks: Vec<CEKData>,
+    },
+
+    /// Accept an invite to a group channel, creating the invitee's CEK account
+    /// from the pending invite and closing the invite.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account for the CEK account, must be a system account
+    /// 1. `[]` Invitee DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Invitee authority - must be a key on the invitee DID
+    /// 3. `[writable]` Unallocated invitee CEK account, must be a program address
+    /// 4. `[writable]` Channel account
+    /// 5. `[writable]` Pending invite account
+    /// 6. `[writable]` The funder of the pending invite, which receives its lamports
+    /// 7. `[]` Rent sysvar
+    /// 8. `[]` System program
+    /// 9. `[writable]` Channel members account for `members_page`, must be a program address.
+    ///    It is created if it is unallocated.
+    AcceptInvite {
+        /// The page of the channel members list that the invitee is added to,
+        /// which must be `ChannelData::next_members_page()`
+        members_page: u16,
+    },
+
+    /// Decline an invite to a group channel, closing the invite
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Invitee DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Invitee authority - must be a key on the invitee DID
+    /// 2. `[writable]` Pending invite account
+    /// 3. `[writable]` The funder of the pending invite, which receives its lamports
+    DeclineInvite,
+
+    /// Revoke an unanswered invite to a group channel, closing the invite
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Inviter DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Inviter authority - must be a key on the inviter DID
+    /// 2. `[writable]` Pending invite account
+    /// 3. `[writable]` The funder of the pending invite, which receives its lamports
+    RevokeInvite,
+
+    /// Create a shareable invite code for a group channel.
+    /// Anyone who holds the invite keypair can redeem the code until it expires or runs out of uses.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Creator DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Creator authority - must be a key on the creator DID
+    /// 3. `[]` Creator CEK account, must hold a CEK for the current channel epoch
+    /// 4. `[writable]` Unallocated invite code account, must be a program address
+    /// 5. `[]` Channel account, must be previously initialized
+    /// 6. `[]` Rent sysvar
+    /// 7. `[]` System program
+    CreateInviteCode {
+        /// The public key of the invite keypair, which is shared with the invitees
+        invite_pubkey: Pubkey,
+
+        /// The unix timestamp after which the invite code can no longer be redeemed.
+        /// It must not be in the past.
+        expiry: i64,
+
+        /// The maximum number of times the invite code can be redeemed, at least one
+        max_uses: u16,
+    },
+
+    /// Redeem an invite code, creating a CEK account without CEKs for the redeemer.
+    /// A member of the channel then adds the CEKs with `ProvisionMemberCEK`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable, signer]` Funding account, must be a system account
+    /// 1. `[]` Redeemer DID account - must be owned by the sol-did program
+    /// 2. `[signer]` Redeemer authority - must be a key on the redeemer DID
+    /// 3. `[writable]` Unallocated redeemer CEK account, must be a program address
+    /// 4. `[writable]` Channel account
+    /// 5. `[writable]` Invite code account
+    /// 6. `[]` Rent sysvar
+    /// 7. `[]` System program
+    /// 8. `[writable]` Channel members account for `members_page`, must be a program address.
+    ///    It is created if it is unallocated.
+    /// 9. `[signer]` Invite keypair of the invite code
+    RedeemInvite {
+        /// The page of the channel members list that the redeemer is added to,
+        /// which must be `ChannelData::next_members_page()`
+        members_page: u16,
+    },
+
+    /// Close an invite code, refunding its funder
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Creator DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Creator authority - must be a key on the creator DID
+    /// 2. `[writable]` Invite code account
+    /// 3. `[writable]` The funder of the invite code, which receives its lamports
+    CloseInviteCode,
+
+    /// Add a CEK to an existing CEKAccount, first removing the CEKs for keys
+    /// that are no longer verification methods on the owner DID
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Owner DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Owner authority - must be a key on the owner DID
+    /// 2. `[writable]` CEK account, must be owned by the owner DID
+    AddCEKAndPrune {
+        /// A new CEK to add to the account
+        cek: CEKData,
+    },
+
+    /// Remove a member from a group channel, closing their CEK account
+    /// and removing them from the channel members list.
+    /// Group channels have no admins, so any member holding a CEK for the current channel epoch
+    /// can remove any other member. The rent of the CEK account is refunded to the removed member.
+    /// The remaining members should rotate the channel key afterwards,
+    /// so that the removed member cannot read new messages.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Remover DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Remover authority - must be a key on the remover DID
+    /// 2. `[]` Remover CEK account for the channel
+    /// 3. `[]` Member DID account
+    /// 4. `[writable]` Member CEK account for the channel
+    /// 5. `[writable]` Channel account
+    /// 6. `[writable]` Channel members account for `members_page`
+    /// 7. `[writable]` Member DID authority, the recipient of the lamports of the closed CEK account
+    /// 8. `[writable]` (Optional) Member notifications account.
+    ///    If it is initialized, the member is notified of their removal.
+    RemoveFromChannel {
+        /// The page of the channel members list that lists the member
+        members_page: u16,
+    },
+
+    /// Leave a private channel, closing the member's private CEK account
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Member DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Member authority - must be a key on the member DID
+    /// 2. `[signer]` Member membership key for the channel
+    /// 3. `[writable]` Member private CEK account
+    /// 4. `[]` Channel account, must be a private channel
+    /// 5. `[writable]` Recipient of the lamports of the closed private CEK account
+    LeavePrivateChannel,
+
+    /// Add a CEK for a new key on the member DID to the member's private CEK account
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[]` Member DID account - must be owned by the sol-did program
+    /// 1. `[signer]` Member authority - must be a key on the member DID
+    /// 2. `[signer]` Member membership key for the channel
+    /// 3. `[writable]` Member private CEK account
+    /// 4. `[]` Channel account, must be a private channel
+    AddPrivateCEK {
+        /// The CEK to add, for the current channel epoch
+        cek: CEKData,
+    },
+
+    /// Rotate the CEK of a private channel, replacing the CEKs of its members with CEKs
+    /// for a new epoch, in the same way as `RotateChannelKey`.
+    /// Members are identified only by their private CEK accounts, so their DIDs are not passed,
+    /// the key IDs of their CEKs are not checked and they are not notified of the rotation.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. `[writable]` Channel account, must be a private channel
+    /// 1. `[]` Rotator DID account
+    /// 2. `[signer]` Rotator signer account (must be an authority on the rotator DID)
+    /// 3. `[signer]` Rotator membership key for the channel
+    /// 4. `[]` Rotator private CEK account, must hold a CEK for the current channel epoch
+    /// 5. `[writable]` Member private CEK account for this channel
+    ///
+    /// Account 5 is repeated for each member, in the order given in `member_ceks`.
+    RotatePrivateChannelKey {
+        /// The new epoch, or the current epoch to continue a rotation
+        epoch: u32,
+        /// The new CEKs of each member, encrypted with each key in the member DID.
+        /// Each member must be given at least one CEK.
+        member_ceks: Vec<Vec<CEKData>>,
+    },
+}
+
+/// Create a `SolariumInstruction::InitializeChannel` instruction
+pub fn initialize_channel(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    name: String,
+    creator_did: &Pubkey,
+    creator_authority: &Pubkey,
+    ceks: Vec<CEKData>,
+) -> Instruction {
+    let (creator_cek_account, _) = get_cek_account_address_with_seed(&id(), creator_did, channel);
+    let (channel_members_account, _) =
+        get_channel_members_account_address_with_seed(&id(), channel, 0);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::InitializeChannel { name, ceks },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new_readonly(*creator_did, false),
+            AccountMeta::new_readonly(*creator_authority, true),
+            AccountMeta::new(creator_cek_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(channel_members_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::InitializeChannel` instruction
+pub fn initialize_direct_channel(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    creator_did: &Pubkey,
+    creator_authority: &Pubkey,
+    invitee_did: &Pubkey,
+    creator_ceks: Vec<CEKData>,
+    invitee_ceks: Vec<CEKData>,
+) -> Instruction {
+    let (creator_cek_account, _) = get_cek_account_address_with_seed(&id(), creator_did, channel);
+    let (invitee_cek_account, _) = get_cek_account_address_with_seed(&id(), invitee_did, channel);
+    let (invitee_blocklist_account, _) =
+        get_blocklist_account_address_with_seed(&id(), invitee_did);
+    let (invitee_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), invitee_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::InitializeDirectChannel {
+            creator_ceks,
+            invitee_ceks,
+        },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new_readonly(*creator_did, false),
+            AccountMeta::new_readonly(*creator_authority, true),
+            AccountMeta::new(creator_cek_account, false),
+            AccountMeta::new_readonly(*invitee_did, false),
+            AccountMeta::new(invitee_cek_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(invitee_blocklist_account, false),
+            AccountMeta::new(invitee_notifications_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::Post` instruction
+pub fn post(channel: &Pubkey, sender_authority: &Pubkey, message: &Message) -> Instruction {
+    let (sender_cek_account, _) =
+        get_cek_account_address_with_seed(&id(), &message.sender, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::Post {
+            message: message.content.to_string(),
+            epoch: message.epoch,
+        },
+        vec![
+            AccountMeta::new(*channel, false),
+            AccountMeta::new_readonly(message.sender, false),
+            AccountMeta::new_readonly(*sender_authority, true),
+            AccountMeta::new_readonly(sender_cek_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::AddToChannel` instruction
+pub fn add_to_channel(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    invitee_did: &Pubkey,
+    inviter_did: &Pubkey,
+    inviter_authority: &Pubkey,
+    members_page: u16,
+    ceks: Vec<CEKData>,
+) -> Instruction {
+    let (inviter_cek_account, _) = get_cek_account_address_with_seed(&id(), inviter_did, channel);
+    let (invitee_cek_account, _) = get_cek_account_address_with_seed(&id(), invitee_did, channel);
+    let (channel_members_account, _) =
+        get_channel_members_account_address_with_seed(&id(), channel, members_page);
+    let (invitee_blocklist_account, _) =
+        get_blocklist_account_address_with_seed(&id(), invitee_did);
+    let (invitee_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), invitee_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AddToChannel { ceks, members_page },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*invitee_did, false),
+            AccountMeta::new_readonly(*inviter_did, false),
+            AccountMeta::new_readonly(*inviter_authority, true),
+            AccountMeta::new_readonly(inviter_cek_account, false),
+            AccountMeta::new(invitee_cek_account, false),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(channel_members_account, false),
+            AccountMeta::new_readonly(invitee_blocklist_account, false),
+            AccountMeta::new(invitee_notifications_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::AddCEK` instruction
+pub fn add_cek(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    channel: &Pubkey,
+    cek: CEKData,
+) -> Instruction {
+    let (owner_cek_account, _) = get_cek_account_address_with_seed(&id(), owner_did, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AddCEK { cek },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_cek_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::RemoveCEK` instruction
+pub fn remove_cek(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    channel: &Pubkey,
+    kid: String,
+) -> Instruction {
+    let (owner_cek_account, _) = get_cek_account_address_with_seed(&id(), owner_did, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::RemoveCEK { kid },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_cek_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::CreateUserDetails` instruction
+pub fn create_user_details(
+    funder_account: &Pubkey,
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    alias: String,
+    size: u32,
+) -> Instruction {
+    let (owner_userdetails_account, _) =
+        get_userdetails_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::CreateUserDetails {
+            alias,
+            address_book: "".to_string(),
+            size,
+        },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_userdetails_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::UpdateUserDetails` instruction
+pub fn update_user_details(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    alias: Option<String>,
+    address_book: Option<String>,
+    status: Option<String>,
+    avatar_uri: Option<String>,
+) -> Instruction {
+    let (owner_userdetails_account, _) =
+        get_userdetails_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::UpdateUserDetails {
+            alias,
+            address_book,
+            status,
+            avatar_uri,
+        },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_userdetails_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::CreateNotifications` instruction
+pub fn create_notifications(
+    funder_account: &Pubkey,
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    size: u16,
+) -> Instruction {
+    let (owner_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::CreateNotifications { size },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_notifications_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::AddNotification` instruction
+pub fn add_notification(
+    notification_type: NotificationType,
+    pubkey: &Pubkey,
+    owner_did: &Pubkey,
+    sender_did: &Pubkey,
+    sender_authority: &Pubkey,
+) -> Instruction {
+    let (owner_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), owner_did);
+    let (owner_blocklist_account, _) = get_blocklist_account_address_with_seed(&id(), owner_did);
+    let notification = Notification::new(notification_type, *pubkey, *sender_did);
+    let mut accounts = vec![
+        AccountMeta::new(owner_notifications_account, false),
+        AccountMeta::new_readonly(*sender_did, false),
+        AccountMeta::new_readonly(*sender_authority, true),
+        AccountMeta::new_readonly(*owner_did, false),
+        AccountMeta::new_readonly(owner_blocklist_account, false),
+    ];
+    if let Some(channel) = notification.channel(&id(), owner_did) {
+        let (sender_cek_account, _) =
+            get_cek_account_address_with_seed(&id(), sender_did, &channel);
+        accounts.push(AccountMeta::new_readonly(sender_cek_account, false));
+    }
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AddNotification {
+            pubkey: notification.pubkey,
+            notification_type: notification.notification_type,
+        },
+        accounts,
+    )
+}
+
+/// Create a `SolariumInstruction::SetNotificationsPolicy` instruction
+pub fn set_notifications_policy(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    open: bool,
+    allowlist: Vec<Pubkey>,
+) -> Instruction {
+    let (owner_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::SetNotificationsPolicy { open, allowlist },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_notifications_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::PostWithMentions` instruction
+pub fn post_with_mentions(
+    channel: &Pubkey,
+    sender_authority: &Pubkey,
+    message: &Message,
+    mentions: Vec<Pubkey>,
+) -> Instruction {
+    let (sender_cek_account, _) =
+        get_cek_account_address_with_seed(&id(), &message.sender, channel);
+    let mut accounts = vec![
+        AccountMeta::new(*channel, false),
+        AccountMeta::new_readonly(message.sender, false),
+        AccountMeta::new_readonly(*sender_authority, true),
+        AccountMeta::new_readonly(sender_cek_account, false),
+    ];
+    for mentioned_did in mentions.iter() {
+        let (mentioned_cek_account, _) =
+            get_cek_account_address_with_seed(&id(), mentioned_did, channel);
+        let (mentioned_notifications_account, _) =
+            get_notifications_account_address_with_seed(&id(), mentioned_did);
+        let (mentioned_blocklist_account, _) =
+            get_blocklist_account_address_with_seed(&id(), mentioned_did);
+        accounts.push(AccountMeta::new_readonly(mentioned_cek_account, false));
+        accounts.push(AccountMeta::new(mentioned_notifications_account, false));
+        accounts.push(AccountMeta::new_readonly(
+            mentioned_blocklist_account,
+            false,
+        ));
+    }
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::PostWithMentions {
+            message: message.content.to_string(),
+            mentions,
+            epoch: message.epoch,
+        },
+        accounts,
+    )
+}
+
+/// Create a `SolariumInstruction::AckNotifications` instruction
+pub fn ack_notifications(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    ack: NotificationsAck,
+) -> Instruction {
+    let (owner_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AckNotifications { ack },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_notifications_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::ClearNotifications` instruction
+pub fn clear_notifications(owner_did: &Pubkey, owner_authority: &Pubkey) -> Instruction {
+    let (owner_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::ClearNotifications,
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_notifications_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::ResizeNotifications` instruction
+pub fn resize_notifications(
+    funder_account: &Pubkey,
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    size: u16,
+) -> Instruction {
+    let (owner_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::ResizeNotifications { size },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_notifications_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::CloseNotifications` instruction
+pub fn close_notifications(owner_did: &Pubkey, owner_authority: &Pubkey) -> Instruction {
+    let (owner_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::CloseNotifications,
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new(*owner_authority, true),
+            AccountMeta::new(owner_notifications_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::ResizeUserDetails` instruction
+pub fn resize_user_details(
+    funder_account: &Pubkey,
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    size: u32,
+) -> Instruction {
+    let (owner_userdetails_account, _) =
+        get_userdetails_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::ResizeUserDetails { size },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_userdetails_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::CloseUserDetails` instruction
+pub fn close_user_details(owner_did: &Pubkey, owner_authority: &Pubkey) -> Instruction {
+    let (owner_userdetails_account, _) =
+        get_userdetails_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::CloseUserDetails,
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new(*owner_authority, true),
+            AccountMeta::new(owner_userdetails_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::ClaimAlias` instruction
+pub fn claim_alias(
+    funder_account: &Pubkey,
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    alias: String,
+) -> Instruction {
+    let (alias_account, _) = get_alias_account_address_with_seed(&id(), &alias);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::ClaimAlias { alias },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(alias_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::ReleaseAlias` instruction
+pub fn release_alias(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    alias: &str,
+    recipient: &Pubkey,
+) -> Instruction {
+    let (alias_account, _) = get_alias_account_address_with_seed(&id(), alias);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::ReleaseAlias,
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(alias_account, false),
+            AccountMeta::new(*recipient, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::TransferAlias` instruction
+pub fn transfer_alias(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    alias: &str,
+    new_owner_did: &Pubkey,
+) -> Instruction {
+    let (alias_account, _) = get_alias_account_address_with_seed(&id(), alias);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::TransferAlias {
+            new_owner_did: *new_owner_did,
+        },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(alias_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::Block` instruction
+pub fn block(
+    funder_account: &Pubkey,
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    did: &Pubkey,
+) -> Instruction {
+    let (owner_blocklist_account, _) = get_blocklist_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::Block { did: *did },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_blocklist_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::Unblock` instruction
+pub fn unblock(owner_did: &Pubkey, owner_authority: &Pubkey, did: &Pubkey) -> Instruction {
+    let (owner_blocklist_account, _) = get_blocklist_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::Unblock { did: *did },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_blocklist_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::AddContactEntry` instruction
+pub fn add_contact_entry(
+    funder_account: &Pubkey,
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    page: u16,
+    entry: ContactEntry,
+) -> Instruction {
+    let (address_book_account, _) =
+        get_address_book_account_address_with_seed(&id(), owner_did, page);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AddContactEntry { page, entry },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(address_book_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::UpdateContactEntry` instruction
+pub fn update_contact_entry(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    page: u16,
+    entry: ContactEntry,
+) -> Instruction {
+    let (address_book_account, _) =
+        get_address_book_account_address_with_seed(&id(), owner_did, page);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::UpdateContactEntry { entry },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(address_book_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::RemoveContactEntry` instruction
+pub fn remove_contact_entry(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    page: u16,
+    entry_id: u32,
+) -> Instruction {
+    let (address_book_account, _) =
+        get_address_book_account_address_with_seed(&id(), owner_did, page);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::RemoveContactEntry { id: entry_id },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(address_book_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::CreatePreKeyBundle` instruction
+pub fn create_prekey_bundle(
+    funder_account: &Pubkey,
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    identity_key: [u8; 32],
+    signed_prekey: SignedPreKey,
+    one_time_prekeys: Vec<OneTimePreKey>,
+) -> Instruction {
+    let (prekey_bundle_account, _) = get_prekey_bundle_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::CreatePreKeyBundle {
+            identity_key,
+            signed_prekey,
+            one_time_prekeys,
+        },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(prekey_bundle_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::ConsumeOneTimePreKey` instruction
+pub fn consume_one_time_prekey(
+    owner_did: &Pubkey,
+    requester_did: &Pubkey,
+    requester_authority: &Pubkey,
+) -> Instruction {
+    let (prekey_bundle_account, _) = get_prekey_bundle_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::ConsumeOneTimePreKey,
+        vec![
+            AccountMeta::new(prekey_bundle_account, false),
+            AccountMeta::new_readonly(*requester_did, false),
+            AccountMeta::new_readonly(*requester_authority, true),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::RefillPreKeys` instruction
+pub fn refill_prekeys(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    signed_prekey: Option<SignedPreKey>,
+    one_time_prekeys: Vec<OneTimePreKey>,
+) -> Instruction {
+    let (prekey_bundle_account, _) = get_prekey_bundle_account_address_with_seed(&id(), owner_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::RefillPreKeys {
+            signed_prekey,
+            one_time_prekeys,
+        },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(prekey_bundle_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::AddCEKAndPrune` instruction
+pub fn add_cek_and_prune(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    channel: &Pubkey,
+    cek: CEKData,
+) -> Instruction {
+    let (owner_cek_account, _) = get_cek_account_address_with_seed(&id(), owner_did, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AddCEKAndPrune { cek },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_cek_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::RemoveFromChannel` instruction
+pub fn remove_from_channel(
+    remover_did: &Pubkey,
+    remover_authority: &Pubkey,
+    member_did: &Pubkey,
+    channel: &Pubkey,
+    members_page: u16,
+    member_authority: &Pubkey,
+) -> Instruction {
+    let (remover_cek_account, _) = get_cek_account_address_with_seed(&id(), remover_did, channel);
+    let (member_cek_account, _) = get_cek_account_address_with_seed(&id(), member_did, channel);
+    let (channel_members_account, _) =
+        get_channel_members_account_address_with_seed(&id(), channel, members_page);
+    let (member_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), member_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::RemoveFromChannel { members_page },
+        vec![
+            AccountMeta::new_readonly(*remover_did, false),
+            AccountMeta::new_readonly(*remover_authority, true),
+            AccountMeta::new_readonly(remover_cek_account, false),
+            AccountMeta::new_readonly(*member_did, false),
+            AccountMeta::new(member_cek_account, false),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new(channel_members_account, false),
+            AccountMeta::new(*member_authority, false),
+            AccountMeta::new(member_notifications_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::PruneStaleCEKs` instruction
+pub fn prune_stale_ceks(owner_did: &Pubkey, channel: &Pubkey) -> Instruction {
+    let (owner_cek_account, _) = get_cek_account_address_with_seed(&id(), owner_did, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::PruneStaleCEKs,
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new(owner_cek_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::ReplaceCEK` instruction
+pub fn replace_cek(
+    owner_did: &Pubkey,
+    owner_authority: &Pubkey,
+    channel: &Pubkey,
+    kid: String,
+    cek: CEKData,
+) -> Instruction {
+    let (owner_cek_account, _) = get_cek_account_address_with_seed(&id(), owner_did, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::ReplaceCEK { kid, cek },
+        vec![
+            AccountMeta::new_readonly(*owner_did, false),
+            AccountMeta::new_readonly(*owner_authority, true),
+            AccountMeta::new(owner_cek_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::ProvisionMemberCEK` instruction
+pub fn provision_member_cek(
+    provider_did: &Pubkey,
+    provider_authority: &Pubkey,
+    member_did: &Pubkey,
+    channel: &Pubkey,
+    cek: CEKData,
+) -> Instruction {
+    let (provider_cek_account, _) = get_cek_account_address_with_seed(&id(), provider_did, channel);
+    let (member_cek_account, _) = get_cek_account_address_with_seed(&id(), member_did, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::ProvisionMemberCEK { cek },
+        vec![
+            AccountMeta::new_readonly(*provider_did, false),
+            AccountMeta::new_readonly(*provider_authority, true),
+            AccountMeta::new_readonly(provider_cek_account, false),
+            AccountMeta::new_readonly(*member_did, false),
+            AccountMeta::new(member_cek_account, false),
+            AccountMeta::new_readonly(*channel, false),
+        ],
+    )
+}
+
+/// The maximum size of a serialized transaction in bytes
+pub const MAX_TRANSACTION_SIZE: usize = 1232;
+
+/// Create `SolariumInstruction::AddCEKs` instructions adding a CEK to the owner's CEK account
+/// for each channel. The CEKs are packed into as few instructions as possible, such that each
+/// instruction fits in a transaction signed by the owner authority and a separate fee payer.
+pub fn add_ceks(
     owner_did: &Pubkey,
     owner_authority: &Pubkey,
-    size: u8,
+    channel_ceks: Vec<(Pubkey, CEKData)>,
+) -> Vec<Instruction> {
+    let mut batches: Vec<Vec<(Pubkey, CEKData)>> = vec![];
+    let mut batch: Vec<(Pubkey, CEKData)> = vec![];
+    for channel_cek in channel_ceks {
+        batch.push(channel_cek);
+        if batch.len() > 1 && add_ceks_transaction_size(&batch) > MAX_TRANSACTION_SIZE {
+            let overflow = batch.pop().unwrap();
+            batches.push(batch);
+            batch = vec![overflow];
+        }
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+        .into_iter()
+        .map(|batch| {
+            let mut accounts = vec![
+                AccountMeta::new_readonly(*owner_did, false),
+                AccountMeta::new_readonly(*owner_authority, true),
+            ];
+            let mut ceks = vec![];
+            for (channel, cek) in batch {
+                let (owner_cek_account, _) =
+                    get_cek_account_address_with_seed(&id(), owner_did, &channel);
+                accounts.push(AccountMeta::new(owner_cek_account, false));
+                ceks.push(cek);
+            }
+            Instruction::new_with_borsh(id(), &SolariumInstruction::AddCEKs { ceks }, accounts)
+        })
+        .collect()
+}
+
+/// Estimate the size of a transaction holding only an AddCEKs instruction for these CEKs
+fn add_ceks_transaction_size(channel_ceks: &[(Pubkey, CEKData)]) -> usize {
+    let ceks = channel_ceks.iter().map(|(_, cek)| cek.clone()).collect();
+    // new_with_borsh makes the same assumption that serialization succeeds
+    let data_size = SolariumInstruction::AddCEKs { ceks }
+        .try_to_vec()
+        .unwrap()
+        .len();
+    // the program and the instruction accounts, plus the fee payer, which is not in the instruction
+    let instruction_account_count = 2 + channel_ceks.len();
+    let account_count = instruction_account_count + 2;
+    let compact_len = |len: usize| match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    };
+
+    let signatures_size = compact_len(2) + 2 * 64;
+    let message_header_size = 3;
+    let account_keys_size = compact_len(account_count) + 32 * account_count;
+    let recent_blockhash_size = 32;
+    let instructions_size = compact_len(1)
+        + 1
+        + compact_len(instruction_account_count)
+        + instruction_account_count
+        + compact_len(data_size)
+        + data_size;
+
+    signatures_size
+        + message_header_size
+        + account_keys_size
+        + recent_blockhash_size
+        + instructions_size
+}
+
+/// Create a `SolariumInstruction::RotateChannelKey` instruction.
+/// `member_ceks` holds the new CEKs for each member DID.
+pub fn rotate_channel_key(
+    channel: &Pubkey,
+    rotator_did: &Pubkey,
+    rotator_authority: &Pubkey,
+    epoch: u32,
+    member_ceks: Vec<(Pubkey, Vec<CEKData>)>,
 ) -> Instruction {
-    let (owner_notifications_account, _) =
-        get_notifications_account_address_with_seed(&id(), owner_did);
+    let (rotator_cek_account, _) = get_cek_account_address_with_seed(&id(), rotator_did, channel);
+    let mut accounts = vec![
+        AccountMeta::new(*channel, false),
+        AccountMeta::new_readonly(*rotator_did, false),
+        AccountMeta::new_readonly(*rotator_authority, true),
+        AccountMeta::new_readonly(rotator_cek_account, false),
+    ];
+    for (member_did, _) in member_ceks.iter() {
+        let (member_cek_account, _) = get_cek_account_address_with_seed(&id(), member_did, channel);
+        let (member_notifications_account, _) =
+            get_notifications_account_address_with_seed(&id(), member_did);
+        accounts.push(AccountMeta::new_readonly(*member_did, false));
+        accounts.push(AccountMeta::new(member_cek_account, false));
+        accounts.push(AccountMeta::new(member_notifications_account, false));
+    }
     Instruction::new_with_borsh(
         id(),
-        &SolariumInstruction::CreateNotifications { size },
+        &SolariumInstruction::RotateChannelKey {
+            epoch,
+            member_ceks: member_ceks.into_iter().map(|(_, ceks)| ceks).collect(),
+        },
+        accounts,
+    )
+}
+
+/// Create a `SolariumInstruction::InitializePrivateChannel` instruction
+pub fn initialize_private_channel(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    name: String,
+    creator_did: &Pubkey,
+    creator_authority: &Pubkey,
+    creator_membership_key: &Pubkey,
+    ceks: Vec<CEKData>,
+) -> Instruction {
+    let membership_seed = get_membership_seed(creator_did, channel, creator_membership_key);
+    let (creator_cek_account, _) =
+        get_private_cek_account_address_with_seed(&id(), channel, &membership_seed);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::InitializePrivateChannel { name, ceks },
         vec![
             AccountMeta::new(*funder_account, true),
-            AccountMeta::new_readonly(*owner_did, false),
-            AccountMeta::new_readonly(*owner_authority, true),
-            AccountMeta::new(owner_notifications_account, false),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new_readonly(*creator_did, false),
+            AccountMeta::new_readonly(*creator_authority, true),
+            AccountMeta::new_readonly(*creator_membership_key, true),
+            AccountMeta::new(creator_cek_account, false),
             AccountMeta::new_readonly(sysvar::rent::id(), false),
             AccountMeta::new_readonly(system_program::id(), false),
         ],
     )
 }
 
-/// Create a `SolariumInstruction::AddNotification` instruction
-pub fn add_notification(
-    notification_type: NotificationType,
-    pubkey: &Pubkey,
-    owner_did: &Pubkey,
-    sender_did: &Pubkey,
+/// Create a `SolariumInstruction::AddToPrivateChannel` instruction
+pub fn add_to_private_channel(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    inviter_did: &Pubkey,
+    inviter_authority: &Pubkey,
+    inviter_membership_key: &Pubkey,
+    invitee_membership_seed: [u8; 32],
+    ceks: Vec<CEKData>,
+) -> Instruction {
+    let inviter_membership_seed = get_membership_seed(inviter_did, channel, inviter_membership_key);
+    let (inviter_cek_account, _) =
+        get_private_cek_account_address_with_seed(&id(), channel, &inviter_membership_seed);
+    let (invitee_cek_account, _) =
+        get_private_cek_account_address_with_seed(&id(), channel, &invitee_membership_seed);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AddToPrivateChannel {
+            invitee_membership_seed,
+            ceks,
+        },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*inviter_did, false),
+            AccountMeta::new_readonly(*inviter_authority, true),
+            AccountMeta::new_readonly(*inviter_membership_key, true),
+            AccountMeta::new_readonly(inviter_cek_account, false),
+            AccountMeta::new(invitee_cek_account, false),
+            AccountMeta::new_readonly(*channel, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::PostPrivate` instruction
+pub fn post_private(
+    channel: &Pubkey,
     sender_authority: &Pubkey,
+    sender_membership_key: &Pubkey,
+    message: &Message,
 ) -> Instruction {
-    let (owner_notifications_account, _) =
-        get_notifications_account_address_with_seed(&id(), owner_did);
+    let membership_seed = get_membership_seed(&message.sender, channel, sender_membership_key);
+    let (sender_cek_account, _) =
+        get_private_cek_account_address_with_seed(&id(), channel, &membership_seed);
     Instruction::new_with_borsh(
         id(),
-        &SolariumInstruction::AddNotification {
-            pubkey: *pubkey,
-            notification_type,
+        &SolariumInstruction::PostPrivate {
+            message: message.content.to_string(),
+            epoch: message.epoch,
         },
         vec![
-            AccountMeta::new(owner_notifications_account, false),
-            AccountMeta::new_readonly(*sender_did, false),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new_readonly(message.sender, false),
             AccountMeta::new_readonly(*sender_authority, true),
+            AccountMeta::new_readonly(*sender_membership_key, true),
+            AccountMeta::new_readonly(sender_cek_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::LeaveChannel` instruction
+pub fn leave_channel(
+    member_did: &Pubkey,
+    member_authority: &Pubkey,
+    channel: &Pubkey,
+    members_page: u16,
+    recipient: &Pubkey,
+) -> Instruction {
+    let (member_cek_account, _) = get_cek_account_address_with_seed(&id(), member_did, channel);
+    let (channel_members_account, _) =
+        get_channel_members_account_address_with_seed(&id(), channel, members_page);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::LeaveChannel { members_page },
+        vec![
+            AccountMeta::new_readonly(*member_did, false),
+            AccountMeta::new_readonly(*member_authority, true),
+            AccountMeta::new(member_cek_account, false),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new(channel_members_account, false),
+            AccountMeta::new(*recipient, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::InviteToChannel` instruction
+pub fn invite_to_channel(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    invitee_did: &Pubkey,
+    inviter_did: &Pubkey,
+    inviter_authority: &Pubkey,
+    ceks: Vec<CEKData>,
+) -> Instruction {
+    let (inviter_cek_account, _) = get_cek_account_address_with_seed(&id(), inviter_did, channel);
+    let (pending_invite_account, _) =
+        get_pending_invite_account_address_with_seed(&id(), invitee_did, channel);
+    let (invitee_blocklist_account, _) =
+        get_blocklist_account_address_with_seed(&id(), invitee_did);
+    let (invitee_notifications_account, _) =
+        get_notifications_account_address_with_seed(&id(), invitee_did);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::InviteToChannel { ceks },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*invitee_did, false),
+            AccountMeta::new_readonly(*inviter_did, false),
+            AccountMeta::new_readonly(*inviter_authority, true),
+            AccountMeta::new_readonly(inviter_cek_account, false),
+            AccountMeta::new(pending_invite_account, false),
+            AccountMeta::new_readonly(*channel, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(invitee_blocklist_account, false),
+            AccountMeta::new(invitee_notifications_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::AcceptInvite` instruction
+pub fn accept_invite(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    invitee_did: &Pubkey,
+    invitee_authority: &Pubkey,
+    invite_funder: &Pubkey,
+    members_page: u16,
+) -> Instruction {
+    let (invitee_cek_account, _) = get_cek_account_address_with_seed(&id(), invitee_did, channel);
+    let (pending_invite_account, _) =
+        get_pending_invite_account_address_with_seed(&id(), invitee_did, channel);
+    let (channel_members_account, _) =
+        get_channel_members_account_address_with_seed(&id(), channel, members_page);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AcceptInvite { members_page },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*invitee_did, false),
+            AccountMeta::new_readonly(*invitee_authority, true),
+            AccountMeta::new(invitee_cek_account, false),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new(pending_invite_account, false),
+            AccountMeta::new(*invite_funder, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(channel_members_account, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::DeclineInvite` instruction
+pub fn decline_invite(
+    channel: &Pubkey,
+    invitee_did: &Pubkey,
+    invitee_authority: &Pubkey,
+    invite_funder: &Pubkey,
+) -> Instruction {
+    let (pending_invite_account, _) =
+        get_pending_invite_account_address_with_seed(&id(), invitee_did, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::DeclineInvite,
+        vec![
+            AccountMeta::new_readonly(*invitee_did, false),
+            AccountMeta::new_readonly(*invitee_authority, true),
+            AccountMeta::new(pending_invite_account, false),
+            AccountMeta::new(*invite_funder, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::RevokeInvite` instruction
+pub fn revoke_invite(
+    channel: &Pubkey,
+    inviter_did: &Pubkey,
+    inviter_authority: &Pubkey,
+    invitee_did: &Pubkey,
+    invite_funder: &Pubkey,
+) -> Instruction {
+    let (pending_invite_account, _) =
+        get_pending_invite_account_address_with_seed(&id(), invitee_did, channel);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::RevokeInvite,
+        vec![
+            AccountMeta::new_readonly(*inviter_did, false),
+            AccountMeta::new_readonly(*inviter_authority, true),
+            AccountMeta::new(pending_invite_account, false),
+            AccountMeta::new(*invite_funder, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::CreateInviteCode` instruction
+pub fn create_invite_code(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    creator_did: &Pubkey,
+    creator_authority: &Pubkey,
+    invite_pubkey: &Pubkey,
+    expiry: i64,
+    max_uses: u16,
+) -> Instruction {
+    let (creator_cek_account, _) = get_cek_account_address_with_seed(&id(), creator_did, channel);
+    let (invite_code_account, _) =
+        get_invite_code_account_address_with_seed(&id(), channel, invite_pubkey);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::CreateInviteCode {
+            invite_pubkey: *invite_pubkey,
+            expiry,
+            max_uses,
+        },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*creator_did, false),
+            AccountMeta::new_readonly(*creator_authority, true),
+            AccountMeta::new_readonly(creator_cek_account, false),
+            AccountMeta::new(invite_code_account, false),
+            AccountMeta::new_readonly(*channel, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
         ],
     )
 }
 
+/// Create a `SolariumInstruction::RedeemInvite` instruction
+pub fn redeem_invite(
+    funder_account: &Pubkey,
+    channel: &Pubkey,
+    redeemer_did: &Pubkey,
+    redeemer_authority: &Pubkey,
+    invite_pubkey: &Pubkey,
+    members_page: u16,
+) -> Instruction {
+    let (redeemer_cek_account, _) = get_cek_account_address_with_seed(&id(), redeemer_did, channel);
+    let (invite_code_account, _) =
+        get_invite_code_account_address_with_seed(&id(), channel, invite_pubkey);
+    let (channel_members_account, _) =
+        get_channel_members_account_address_with_seed(&id(), channel, members_page);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::RedeemInvite { members_page },
+        vec![
+            AccountMeta::new(*funder_account, true),
+            AccountMeta::new_readonly(*redeemer_did, false),
+            AccountMeta::new_readonly(*redeemer_authority, true),
+            AccountMeta::new(redeemer_cek_account, false),
+            AccountMeta::new(*channel, false),
+            AccountMeta::new(invite_code_account, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(channel_members_account, false),
+            AccountMeta::new_readonly(*invite_pubkey, true),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::CloseInviteCode` instruction
+pub fn close_invite_code(
+    channel: &Pubkey,
+    creator_did: &Pubkey,
+    creator_authority: &Pubkey,
+    invite_pubkey: &Pubkey,
+    invite_funder: &Pubkey,
+) -> Instruction {
+    let (invite_code_account, _) =
+        get_invite_code_account_address_with_seed(&id(), channel, invite_pubkey);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::CloseInviteCode,
+        vec![
+            AccountMeta::new_readonly(*creator_did, false),
+            AccountMeta::new_readonly(*creator_authority, true),
+            AccountMeta::new(invite_code_account, false),
+            AccountMeta::new(*invite_funder, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::LeavePrivateChannel` instruction
+pub fn leave_private_channel(
+    member_did: &Pubkey,
+    member_authority: &Pubkey,
+    member_membership_key: &Pubkey,
+    channel: &Pubkey,
+    recipient: &Pubkey,
+) -> Instruction {
+    let membership_seed = get_membership_seed(member_did, channel, member_membership_key);
+    let (member_cek_account, _) =
+        get_private_cek_account_address_with_seed(&id(), channel, &membership_seed);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::LeavePrivateChannel,
+        vec![
+            AccountMeta::new_readonly(*member_did, false),
+            AccountMeta::new_readonly(*member_authority, true),
+            AccountMeta::new_readonly(*member_membership_key, true),
+            AccountMeta::new(member_cek_account, false),
+            AccountMeta::new_readonly(*channel, false),
+            AccountMeta::new(*recipient, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::AddPrivateCEK` instruction
+pub fn add_private_cek(
+    member_did: &Pubkey,
+    member_authority: &Pubkey,
+    member_membership_key: &Pubkey,
+    channel: &Pubkey,
+    cek: CEKData,
+) -> Instruction {
+    let membership_seed = get_membership_seed(member_did, channel, member_membership_key);
+    let (member_cek_account, _) =
+        get_private_cek_account_address_with_seed(&id(), channel, &membership_seed);
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::AddPrivateCEK { cek },
+        vec![
+            AccountMeta::new_readonly(*member_did, false),
+            AccountMeta::new_readonly(*member_authority, true),
+            AccountMeta::new_readonly(*member_membership_key, true),
+            AccountMeta::new(member_cek_account, false),
+            AccountMeta::new_readonly(*channel, false),
+        ],
+    )
+}
+
+/// Create a `SolariumInstruction::RotatePrivateChannelKey` instruction.
+/// `member_ceks` holds the new CEKs for each member, identified by their blinded membership seed.
+pub fn rotate_private_channel_key(
+    channel: &Pubkey,
+    rotator_did: &Pubkey,
+    rotator_authority: &Pubkey,
+    rotator_membership_key: &Pubkey,
+    epoch: u32,
+    member_ceks: Vec<([u8; 32], Vec<CEKData>)>,
+) -> Instruction {
+    let rotator_membership_seed = get_membership_seed(rotator_did, channel, rotator_membership_key);
+    let (rotator_cek_account, _) =
+        get_private_cek_account_address_with_seed(&id(), channel, &rotator_membership_seed);
+    let mut accounts = vec![
+        AccountMeta::new(*channel, false),
+        AccountMeta::new_readonly(*rotator_did, false),
+        AccountMeta::new_readonly(*rotator_authority, true),
+        AccountMeta::new_readonly(*rotator_membership_key, true),
+        AccountMeta::new_readonly(rotator_cek_account, false),
+    ];
+    for (membership_seed, _) in member_ceks.iter() {
+        let (member_cek_account, _) =
+            get_private_cek_account_address_with_seed(&id(), channel, membership_seed);
+        accounts.push(AccountMeta::new(member_cek_account, false));
+    }
+    Instruction::new_with_borsh(
+        id(),
+        &SolariumInstruction::RotatePrivateChannelKey {
+            epoch,
+            member_ceks: member_ceks.into_iter().map(|(_, ceks)| ceks).collect(),
+        },
+        accounts,
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::state::{CEKHeader, JweAlgorithm, JweHeader};
     use solana_program::program_error::ProgramError;
 
     #[test]
@@ -438,4 +2183,54 @@ mod tests {
             .into();
         assert!(matches!(err, ProgramError::BorshIoError(_)));
     }
+
+    #[test]
+    fn add_ceks_fits_each_instruction_in_a_transaction() {
+        let owner_did = Pubkey::new_unique();
+        let owner_authority = Pubkey::new_unique();
+        let cek = CEKData {
+            header: CEKHeader::Jwe(JweHeader {
+                alg: JweAlgorithm::EcdhEsXc20pkw,
+                epk: [1; 32],
+                nonce: [2; 24],
+                tag: [3; 16],
+            }),
+            kid: "x".repeat(CEKData::MAX_KID_SIZE as usize),
+            encrypted_key: "x".repeat(CEKData::MAX_ENCRYPTED_KEY_SIZE as usize),
+            epoch: 0,
+        };
+        assert_eq!(cek.validate(), Ok(()));
+        let channel_ceks: Vec<(Pubkey, CEKData)> = (0..10)
+            .map(|_| (Pubkey::new_unique(), cek.clone()))
+            .collect();
+
+        // the largest batch of the largest CEKs that fits in a transaction
+        let max_batch_size = (1..=channel_ceks.len())
+            .take_while(|&len| {
+                add_ceks_transaction_size(&channel_ceks[..len]) <= MAX_TRANSACTION_SIZE
+            })
+            .last()
+            .unwrap();
+        assert!(max_batch_size < channel_ceks.len());
+
+        let instructions = add_ceks(&owner_did, &owner_authority, channel_ceks.clone());
+
+        let mut packed_count = 0;
+        for instruction in instructions.iter() {
+            let cek_count = instruction.accounts.len() - 2;
+            let batch = &channel_ceks[packed_count..packed_count + cek_count];
+            assert!(add_ceks_transaction_size(batch) <= MAX_TRANSACTION_SIZE);
+            packed_count += cek_count;
+
+            // every batch but the last is filled to the maximum batch size
+            if packed_count < channel_ceks.len() {
+                assert_eq!(cek_count, max_batch_size);
+            }
+        }
+        assert_eq!(packed_count, channel_ceks.len());
+        assert_eq!(
+            instructions.len(),
+            channel_ceks.len().div_ceil(max_batch_size)
+        );
+    }
 }
@@ -1,28 +1,40 @@
 //! Program state processor
 
 use crate::state::{
-    direct_channel_address_order, get_notifications_account_address_with_seed,
-    get_userdetails_account_address_with_seed, Notification, NotificationType, Notifications,
-    UserDetails, CHANNEL_ADDRESS_SEED, NOTIFICATIONS_ACCOUNT_ADDRESS_SEED,
-    USERDETAILS_ACCOUNT_ADDRESS_SEED,
+    direct_channel_address_order, get_address_book_account_address_with_seed,
+    get_alias_account_address_with_seed, get_blocklist_account_address_with_seed,
+    get_channel_members_account_address_with_seed, get_invite_code_account_address_with_seed,
+    get_notifications_account_address_with_seed, get_pending_invite_account_address_with_seed,
+    get_prekey_bundle_account_address_with_seed, get_userdetails_account_address_with_seed,
+    key_id_fragment, nfkc_alias, validate_alias, AddressBookPage, AliasRecord, Blocklist,
+    ChannelMembersPage, ContactEntry, InviteCode, Notification, NotificationType, Notifications,
+    OneTimePreKey, PendingInvite, PreKeyBundle, SignedPreKey, UserDetails,
+    ADDRESS_BOOK_ACCOUNT_ADDRESS_SEED, ALIAS_ACCOUNT_ADDRESS_SEED, BLOCKLIST_ACCOUNT_ADDRESS_SEED,
+    CHANNEL_ADDRESS_SEED, CHANNEL_MEMBERS_ACCOUNT_ADDRESS_SEED, INVITE_CODE_ACCOUNT_ADDRESS_SEED,
+    NOTIFICATIONS_ACCOUNT_ADDRESS_SEED, PENDING_INVITE_ACCOUNT_ADDRESS_SEED,
+    PREKEY_BUNDLE_ACCOUNT_ADDRESS_SEED, USERDETAILS_ACCOUNT_ADDRESS_SEED,
 };
 use {
     crate::{
         borsh as program_borsh,
         error::SolariumError,
-        instruction::SolariumInstruction,
+        instruction::{NotificationsAck, SolariumInstruction},
         state::{
-            get_cek_account_address_with_seed, get_channel_address_with_seed, CEKAccountData,
-            CEKData, ChannelData, Message, CEK_ACCOUNT_ADDRESS_SEED,
+            get_cek_account_address_with_seed, get_channel_address_with_seed, get_membership_seed,
+            get_private_cek_account_address_with_seed, CEKAccountData, CEKData, ChannelData,
+            Message, PrivateCEKAccountData, CEK_ACCOUNT_ADDRESS_SEED,
+            PRIVATE_CEK_ACCOUNT_ADDRESS_SEED,
         },
     },
     borsh::{BorshDeserialize, BorshSerialize},
-    sol_did::validate_owner,
+    sol_did::{state::SolData, validate_owner},
     solana_program::{
         account_info::{next_account_info, AccountInfo},
+        clock::Clock,
         entrypoint::ProgramResult,
+        hash::hash,
         msg,
-        program::invoke_signed,
+        program::{invoke, invoke_signed, set_return_data},
         program_error::ProgramError,
         program_pack::IsInitialized,
         pubkey::Pubkey,
@@ -40,7 +52,7 @@ fn check_authority_of_cek(
     did: &AccountInfo,
     cek_account_info: &AccountInfo,
 ) -> ProgramResult {
-    check_authority_of_did(authority_info, did).unwrap();
+    check_authority_of_did(authority_info, did)?;
 
     let cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
         *cek_account_info.data.borrow(),
@@ -90,6 +102,40 @@ fn check_cek_account(
     Ok(())
 }
 
+/// Checks that the DID is a member of the channel,
+/// i.e. that the CEK account is the one derived for the DID and channel, and is initialized
+fn check_membership(
+    program_id: &Pubkey,
+    did: &Pubkey,
+    cek_account_info: &AccountInfo,
+    channel: &Pubkey,
+) -> ProgramResult {
+    let (cek_account_address, _) = get_cek_account_address_with_seed(program_id, did, channel);
+    if cek_account_address != *cek_account_info.key {
+        msg!("Error: cek account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    if cek_account_info.owner != program_id {
+        msg!("Error: cek account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+        *cek_account_info.data.borrow(),
+    )?;
+    if cek_account.channel != *channel {
+        msg!("Error: cek account is not for the correct channel");
+        return Err(SolariumError::CEKIncorrectChannel.into());
+    }
+    if cek_account.owner_did != *did {
+        msg!("Error: cek account is not owned by the DID");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    Ok(())
+}
+
 fn initialize_channel(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
@@ -105,6 +151,7 @@ fn initialize_channel(
     let creator_cek_account_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
+    let channel_members_info = next_account_info(account_info_iter)?;
 
     let rent = &Rent::from_account_info(rent_info)?;
 
@@ -121,7 +168,7 @@ fn initialize_channel(
     }
 
     // Check that the authority is valid for the DID
-    check_authority_of_did(creator_authority_info, creator_did_info).unwrap();
+    check_authority_of_did(creator_authority_info, creator_did_info)?;
 
     create_cek_account(
         program_id,
@@ -132,13 +179,23 @@ fn initialize_channel(
         channel_info,
         system_program_info.clone(),
         rent,
-    )
-    .unwrap();
+    )?;
 
-    let channel = ChannelData::new(name);
+    let mut channel = ChannelData::new(name);
+    add_channel_member(
+        program_id,
+        funder_info.clone(),
+        channel_info,
+        &mut channel,
+        channel_members_info.clone(),
+        0,
+        creator_did_info.key,
+        system_program_info.clone(),
+        rent,
+    )?;
 
     channel
-        .serialize(&mut *channel_info.data.borrow_mut())
+        .serialize(&mut &mut channel_info.data.borrow_mut()[..])
         .map_err(|e| e.into())
 }
 
@@ -159,6 +216,8 @@ fn initialize_direct_channel(
     let invitee_cek_account_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
+    let invitee_blocklist_info = next_account_info(account_info_iter)?;
+    let invitee_notifications_info = next_account_info(account_info_iter).ok();
 
     let rent = &Rent::from_account_info(rent_info)?;
 
@@ -181,7 +240,16 @@ fn initialize_direct_channel(
 
     msg!("Checking creator authority");
     // Check that the authority is valid for the DID
-    check_authority_of_did(creator_authority_info, creator_did_info).unwrap();
+    check_authority_of_did(creator_authority_info, creator_did_info)?;
+
+    msg!("Checking invitee blocklist");
+    // Check that the invitee has not blocked the creator
+    check_not_blocked(
+        program_id,
+        invitee_did_info.key,
+        invitee_blocklist_info,
+        creator_did_info.key,
+    )?;
 
     msg!("Creating creator cek account");
     create_cek_account(
@@ -193,8 +261,7 @@ fn initialize_direct_channel(
         channel_info,
         system_program_info.clone(),
         rent,
-    )
-    .unwrap();
+    )?;
 
     msg!("Creating invitee cek account");
     create_cek_account(
@@ -206,8 +273,7 @@ fn initialize_direct_channel(
         channel_info,
         system_program_info.clone(),
         rent,
-    )
-    .unwrap();
+    )?;
 
     msg!("Creating channel");
 
@@ -225,7 +291,7 @@ fn initialize_direct_channel(
             funder_info.key,
             channel_info.key,
             1.max(rent.minimum_balance(size as usize)),
-            size as u64,
+            size,
             program_id,
         ),
         &[
@@ -233,28 +299,132 @@ fn initialize_direct_channel(
             channel_info.clone(),
             system_program_info.clone(),
         ],
-        &[&channel_signer_seeds],
+        &[channel_signer_seeds],
     )?;
 
     msg!("Serializing");
-    let name = format!(
-        "{}/{}",
-        creator_did_info.key.to_string(),
-        invitee_did_info.key.to_string()
-    );
-    let channel = ChannelData::new(name);
-    channel
-        .serialize(&mut *channel_info.data.borrow_mut())
-        .map_err(|e| e.into())
+    let name = format!("{}/{}", creator_did_info.key, invitee_did_info.key);
+    let mut channel = ChannelData::new(name);
+    channel.member_count = 2;
+    channel.serialize(&mut &mut channel_info.data.borrow_mut()[..])?;
+
+    if let Some(invitee_notifications_info) = invitee_notifications_info {
+        msg!("Notifying invitee");
+        let notification = Notification::new(
+            NotificationType::DirectChannel,
+            *creator_did_info.key,
+            *creator_did_info.key,
+        );
+        notify_invitee(
+            program_id,
+            invitee_did_info.key,
+            invitee_notifications_info,
+            notification,
+        )?;
+    }
+
+    Ok(())
 }
 
-fn post(program_id: &Pubkey, accounts: &[AccountInfo], message: String) -> ProgramResult {
+fn post(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    message: String,
+    epoch: u32,
+) -> ProgramResult {
     msg!("SolariumInstruction::Post");
     let account_info_iter = &mut accounts.iter();
     let channel_info = next_account_info(account_info_iter)?;
     let sender_did_info = next_account_info(account_info_iter)?;
     let sender_authority_info = next_account_info(account_info_iter)?;
     let sender_cek_account_info = next_account_info(account_info_iter)?;
+
+    post_message(
+        program_id,
+        channel_info,
+        sender_did_info,
+        sender_authority_info,
+        sender_cek_account_info,
+        message,
+        epoch,
+    )
+}
+
+fn post_with_mentions(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    message: String,
+    mentions: Vec<Pubkey>,
+    epoch: u32,
+) -> ProgramResult {
+    msg!("SolariumInstruction::PostWithMentions");
+    let account_info_iter = &mut accounts.iter();
+    let channel_info = next_account_info(account_info_iter)?;
+    let sender_did_info = next_account_info(account_info_iter)?;
+    let sender_authority_info = next_account_info(account_info_iter)?;
+    let sender_cek_account_info = next_account_info(account_info_iter)?;
+
+    post_message(
+        program_id,
+        channel_info,
+        sender_did_info,
+        sender_authority_info,
+        sender_cek_account_info,
+        message,
+        epoch,
+    )?;
+
+    // Mention notifications are authorised by the sender's membership of the channel,
+    // which post_message has checked
+    for mentioned_did in mentions.iter() {
+        let mentioned_cek_account_info = next_account_info(account_info_iter)?;
+        let mentioned_notifications_info = next_account_info(account_info_iter)?;
+        let mentioned_blocklist_info = next_account_info(account_info_iter)?;
+
+        // Check that the mentioned DID is a member of the channel
+        check_membership(
+            program_id,
+            mentioned_did,
+            mentioned_cek_account_info,
+            channel_info.key,
+        )?;
+
+        // Check that the notifications account belongs to the mentioned DID
+        let (notifications_address, _) =
+            get_notifications_account_address_with_seed(program_id, mentioned_did);
+        if notifications_address != *mentioned_notifications_info.key {
+            msg!("Error: notifications account address derivation mismatch");
+            return Err(SolariumError::AddressDerivationMismatch.into());
+        }
+
+        // Check that the mentioned DID has not blocked the sender
+        check_not_blocked(
+            program_id,
+            mentioned_did,
+            mentioned_blocklist_info,
+            sender_did_info.key,
+        )?;
+
+        let notification = Notification::new(
+            NotificationType::Mention,
+            *channel_info.key,
+            *sender_did_info.key,
+        );
+        push_notification(program_id, mentioned_notifications_info, notification)?;
+    }
+
+    Ok(())
+}
+
+fn post_message(
+    program_id: &Pubkey,
+    channel_info: &AccountInfo,
+    sender_did_info: &AccountInfo,
+    sender_authority_info: &AccountInfo,
+    sender_cek_account_info: &AccountInfo,
+    message: String,
+    epoch: u32,
+) -> ProgramResult {
     let mut channel =
         program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
 
@@ -265,30 +435,187 @@ fn post(program_id: &Pubkey, accounts: &[AccountInfo], message: String) -> Progr
 
     // Check that the sender of the message is valid
     // the sender signer is an authority on the DID.
-    validate_owner(sender_did_info, &[sender_authority_info]).unwrap();
+    validate_owner(sender_did_info, &[sender_authority_info])?;
 
     // check that the sender is allowed to post to this channel
     check_authority_of_cek(
         program_id,
         sender_authority_info,
         sender_did_info,
-        &sender_cek_account_info,
-    )
-    .unwrap();
+        sender_cek_account_info,
+    )?;
+
+    // check that the sender is a member of this channel and holds a CEK for the current epoch,
+    // and that the message is encrypted with it
+    check_membership(
+        program_id,
+        sender_did_info.key,
+        sender_cek_account_info,
+        channel_info.key,
+    )?;
+    check_epoch(channel.epoch, epoch)?;
+    let sender_cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+        *sender_cek_account_info.data.borrow(),
+    )?;
+    if !sender_cek_account.has_epoch(channel.epoch) {
+        msg!("Error: sender has no CEK for the current channel epoch");
+        return Err(SolariumError::StaleEpoch.into());
+    }
 
-    let message_info = Message::new(*sender_did_info.key, message);
+    let message_info = Message::new(*sender_did_info.key, message).with_epoch(epoch);
 
     channel.post(message_info);
 
     channel
-        .serialize(&mut *channel_info.data.borrow_mut())
+        .serialize(&mut &mut channel_info.data.borrow_mut()[..])
         .map_err(|e| e.into())
 }
 
+/// Checks that an epoch is the current epoch of a channel
+fn check_epoch(channel_epoch: u32, epoch: u32) -> ProgramResult {
+    if epoch < channel_epoch {
+        msg!(
+            "Error: epoch {} is stale, the channel is at epoch {}",
+            epoch,
+            channel_epoch
+        );
+        return Err(SolariumError::StaleEpoch.into());
+    }
+    if epoch > channel_epoch {
+        msg!(
+            "Error: epoch {} is ahead of the channel epoch {}",
+            epoch,
+            channel_epoch
+        );
+        return Err(SolariumError::InvalidEpoch.into());
+    }
+    Ok(())
+}
+
+/// Checks that a CEK account holds a CEK for the current epoch of the channel,
+/// so that members whose CEKs were not replaced by a rotation cannot act on its behalf
+fn check_current_epoch_cek(cek_account_info: &AccountInfo, channel: &ChannelData) -> ProgramResult {
+    let cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+        *cek_account_info.data.borrow(),
+    )?;
+    if !cek_account.has_epoch(channel.epoch) {
+        msg!("Error: member has no CEK for the current channel epoch");
+        return Err(SolariumError::StaleEpoch.into());
+    }
+
+    Ok(())
+}
+
+fn rotate_channel_key(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    epoch: u32,
+    member_ceks: Vec<Vec<CEKData>>,
+) -> ProgramResult {
+    msg!("SolariumInstruction::RotateChannelKey");
+    let account_info_iter = &mut accounts.iter();
+    let channel_info = next_account_info(account_info_iter)?;
+    let rotator_did_info = next_account_info(account_info_iter)?;
+    let rotator_authority_info = next_account_info(account_info_iter)?;
+    let rotator_cek_account_info = next_account_info(account_info_iter)?;
+
+    if channel_info.owner != program_id {
+        msg!("Error: channel is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    if !channel.is_initialized() {
+        msg!("Channel account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    // Check that the rotator is a member of the channel holding a CEK for the current epoch,
+    // so that members whose CEKs were not replaced by a rotation cannot start or continue one
+    check_authority_of_did(rotator_authority_info, rotator_did_info)?;
+    check_membership(
+        program_id,
+        rotator_did_info.key,
+        rotator_cek_account_info,
+        channel_info.key,
+    )?;
+    check_current_epoch_cek(rotator_cek_account_info, &channel)?;
+
+    if channel.epoch.checked_add(1) == Some(epoch) {
+        // Start a new rotation
+        channel.epoch = epoch;
+        channel.serialize(&mut &mut channel_info.data.borrow_mut()[..])?;
+    } else {
+        // Continue a rotation
+        check_epoch(channel.epoch, epoch)?;
+    }
+
+    for ceks in member_ceks {
+        let member_did_info = next_account_info(account_info_iter)?;
+        let member_cek_account_info = next_account_info(account_info_iter)?;
+        let member_notifications_info = next_account_info(account_info_iter)?;
+
+        if ceks.is_empty() {
+            msg!("Error: each member must be given at least one CEK");
+            return Err(SolariumError::NoCEKs.into());
+        }
+        if ceks.iter().any(|cek| cek.epoch != epoch) {
+            msg!("Error: CEKs must belong to the new epoch");
+            return Err(SolariumError::InvalidEpoch.into());
+        }
+
+        check_membership(
+            program_id,
+            member_did_info.key,
+            member_cek_account_info,
+            channel_info.key,
+        )?;
+
+        // Only CEKs for keys on the member DID can be given, as with provisioned CEKs
+        let key_ids = get_did_key_ids(member_did_info)?;
+        if let Some(cek) = ceks.iter().find(|cek| {
+            !key_ids
+                .iter()
+                .any(|key_id| key_id == key_id_fragment(&cek.kid))
+        }) {
+            msg!("Error: key {} is not on the member DID", cek.kid);
+            return Err(SolariumError::KeyNotOnDID.into());
+        }
+
+        // Members that already hold a CEK for the epoch were rotated by an earlier transaction,
+        // and their CEKs are not replaced, so that a continued rotation cannot overwrite them
+        let mut member_cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+            *member_cek_account_info.data.borrow(),
+        )?;
+        if member_cek_account.has_epoch(epoch) {
+            msg!("Error: the member already holds a CEK for the epoch");
+            return Err(SolariumError::CEKAlreadyExists.into());
+        }
+        member_cek_account.replace_all(ceks)?;
+        member_cek_account.serialize(&mut &mut member_cek_account_info.data.borrow_mut()[..])?;
+
+        let notification = Notification::new(
+            NotificationType::KeyRotated,
+            *channel_info.key,
+            *rotator_did_info.key,
+        );
+        notify_invitee(
+            program_id,
+            &member_cek_account.owner_did,
+            member_notifications_info,
+            notification,
+        )?;
+    }
+
+    Ok(())
+}
+
 fn add_to_channel(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     ceks: Vec<CEKData>,
+    members_page: u16,
 ) -> ProgramResult {
     msg!("SolariumInstruction::AddToChannel");
     let account_info_iter = &mut accounts.iter();
@@ -301,11 +628,14 @@ fn add_to_channel(
     let channel_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
+    let channel_members_info = next_account_info(account_info_iter)?;
+    let invitee_blocklist_info = next_account_info(account_info_iter)?;
+    let invitee_notifications_info = next_account_info(account_info_iter).ok();
 
     let rent = &Rent::from_account_info(rent_info)?;
 
     // Check that the inviter has permissions to invite to this channel
-    check_cek_account(program_id, inviter_cek_account_info, channel_info).unwrap();
+    check_cek_account(program_id, inviter_cek_account_info, channel_info)?;
 
     // Check that the inviter signer is valid for the DID
     // and that the inviter DID owns the inviter CEK account
@@ -314,8 +644,16 @@ fn add_to_channel(
         inviter_authority_info,
         inviter_did_info,
         inviter_cek_account_info,
-    )
-    .unwrap();
+    )?;
+
+    // Check that the inviter holds the current CEK,
+    // and that the invitee CEKs belong to the current channel epoch
+    let mut channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    check_current_epoch_cek(inviter_cek_account_info, &channel)?;
+    for cek in ceks.iter() {
+        check_epoch(channel.epoch, cek.epoch)?;
+    }
 
     create_cek_account(
         program_id,
@@ -326,300 +664,2716 @@ fn add_to_channel(
         channel_info,
         system_program_info.clone(),
         rent,
+    )?;
+
+    add_channel_member(
+        program_id,
+        funder_info.clone(),
+        channel_info,
+        &mut channel,
+        channel_members_info.clone(),
+        members_page,
+        invitee_did_info.key,
+        system_program_info.clone(),
+        rent,
+    )?;
+    channel.serialize(&mut &mut channel_info.data.borrow_mut()[..])?;
+
+    notify_group_channel_invitee(
+        program_id,
+        NotificationType::GroupChannel,
+        invitee_did_info,
+        inviter_did_info,
+        channel_info,
+        invitee_blocklist_info,
+        invitee_notifications_info,
     )
 }
 
-#[allow(clippy::too_many_arguments)]
-fn create_cek_account<'a>(
+/// Notifies the invitee to a group channel if they passed their notifications account,
+/// unless they have blocked the inviter
+fn notify_group_channel_invitee(
     program_id: &Pubkey,
-    ceks: Vec<CEKData>,
-    funder_info: AccountInfo<'a>,
+    notification_type: NotificationType,
     invitee_did_info: &AccountInfo,
-    invitee_cek_account_info: AccountInfo<'a>,
+    inviter_did_info: &AccountInfo,
     channel_info: &AccountInfo,
-    system_program_info: AccountInfo<'a>,
-    rent: &Rent,
+    invitee_blocklist_info: &AccountInfo,
+    invitee_notifications_info: Option<&AccountInfo>,
 ) -> ProgramResult {
-    let (cek_account_address, cek_account_bump_seed) =
-        get_cek_account_address_with_seed(program_id, invitee_did_info.key, channel_info.key);
+    if let Some(invitee_notifications_info) = invitee_notifications_info {
+        // Do not notify the invitee if they have blocked the inviter
+        if !is_blocked(
+            program_id,
+            invitee_did_info.key,
+            invitee_blocklist_info,
+            inviter_did_info.key,
+        )? {
+            let notification =
+                Notification::new(notification_type, *channel_info.key, *inviter_did_info.key);
+            notify_invitee(
+                program_id,
+                invitee_did_info.key,
+                invitee_notifications_info,
+                notification,
+            )?;
+        }
+    }
 
-    // Check that we are not overwriting an existing cek account
-    let data_len = invitee_cek_account_info.data.borrow().len();
-    if data_len > 0 {
-        msg!("CEK account already initialized");
-        return Err(ProgramError::AccountAlreadyInitialized);
+    Ok(())
+}
+
+fn invite_to_channel(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    ceks: Vec<CEKData>,
+) -> ProgramResult {
+    msg!("SolariumInstruction::InviteToChannel");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let invitee_did_info = next_account_info(account_info_iter)?;
+    let inviter_did_info = next_account_info(account_info_iter)?;
+    let inviter_authority_info = next_account_info(account_info_iter)?;
+    let inviter_cek_account_info = next_account_info(account_info_iter)?;
+    let pending_invite_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let invitee_blocklist_info = next_account_info(account_info_iter)?;
+    let invitee_notifications_info = next_account_info(account_info_iter).ok();
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the inviter has permissions to invite to this channel
+    check_cek_account(program_id, inviter_cek_account_info, channel_info)?;
+
+    // Check that the inviter signer is valid for the DID
+    // and that the inviter DID owns the inviter CEK account
+    check_authority_of_cek(
+        program_id,
+        inviter_authority_info,
+        inviter_did_info,
+        inviter_cek_account_info,
+    )?;
+
+    // Check that the inviter holds the current CEK,
+    // and that the invitee CEKs belong to the current channel epoch
+    let channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    check_current_epoch_cek(inviter_cek_account_info, &channel)?;
+    for cek in ceks.iter() {
+        check_epoch(channel.epoch, cek.epoch)?;
     }
 
-    // Check that the new cek account address has been derived correctly
-    // for the invitee and channel
-    if cek_account_address != *invitee_cek_account_info.key {
-        msg!("Error: cek account address derivation mismatch");
+    let (pending_invite_address, pending_invite_bump_seed) =
+        get_pending_invite_account_address_with_seed(
+            program_id,
+            invitee_did_info.key,
+            channel_info.key,
+        );
+    if pending_invite_address != *pending_invite_info.key {
+        msg!("Error: pending invite address derivation mismatch");
         return Err(SolariumError::AddressDerivationMismatch.into());
     }
 
-    // Create the new cek account for the invitee
-    let mut cek_account = CEKAccountData::new(*invitee_did_info.key, *channel_info.key);
-    cek_account.add_all(ceks);
+    // Check that the invitee does not already have an invite to this channel
+    if !pending_invite_info.data_is_empty() {
+        msg!("Pending invite already exists");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
 
-    let max_cek_size: u64 = 100;
-    let size = (CEKAccountData::MAX_CEKS as u64 * max_cek_size) + 32 + 32;
-    let cek_account_signer_seeds: &[&[_]] = &[
+    let pending_invite = PendingInvite::new(
+        *channel_info.key,
+        *inviter_did_info.key,
+        *invitee_did_info.key,
+        *funder_info.key,
+        ceks,
+    )?;
+
+    let size = PendingInvite::size_bytes()?;
+    let pending_invite_signer_seeds: &[&[_]] = &[
         &invitee_did_info.key.to_bytes(),
         &channel_info.key.to_bytes(),
-        CEK_ACCOUNT_ADDRESS_SEED,
-        &[cek_account_bump_seed],
+        PENDING_INVITE_ACCOUNT_ADDRESS_SEED,
+        &[pending_invite_bump_seed],
     ];
 
     invoke_signed(
         &system_instruction::create_account(
             funder_info.key,
-            invitee_cek_account_info.key,
+            pending_invite_info.key,
             1.max(rent.minimum_balance(size as usize)),
-            size as u64,
+            size,
             program_id,
         ),
         &[
             funder_info.clone(),
-            invitee_cek_account_info.clone(),
+            pending_invite_info.clone(),
             system_program_info.clone(),
         ],
-        &[&cek_account_signer_seeds],
+        &[pending_invite_signer_seeds],
     )?;
 
-    cek_account
-        .serialize(&mut *invitee_cek_account_info.data.borrow_mut())
-        .map_err(|e| e.into())
+    pending_invite.serialize(&mut &mut pending_invite_info.data.borrow_mut()[..])?;
+
+    notify_group_channel_invitee(
+        program_id,
+        NotificationType::ChannelInvite,
+        invitee_did_info,
+        inviter_did_info,
+        channel_info,
+        invitee_blocklist_info,
+        invitee_notifications_info,
+    )
 }
 
-fn update_user_details(
+fn accept_invite(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    alias: String,
-    address_book: String,
+    members_page: u16,
 ) -> ProgramResult {
-    msg!("SolariumInstruction::UpdateUserDetails");
+    msg!("SolariumInstruction::AcceptInvite");
     let account_info_iter = &mut accounts.iter();
-    let did_info = next_account_info(account_info_iter)?;
+    let funder_info = next_account_info(account_info_iter)?;
+    let invitee_did_info = next_account_info(account_info_iter)?;
+    let invitee_authority_info = next_account_info(account_info_iter)?;
+    let invitee_cek_account_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let pending_invite_info = next_account_info(account_info_iter)?;
+    let invite_funder_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let channel_members_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the signer is an authority on the invitee DID
+    check_authority_of_did(invitee_authority_info, invitee_did_info)?;
+
+    let pending_invite = load_pending_invite(program_id, pending_invite_info, invite_funder_info)?;
+    if pending_invite.invitee_did != *invitee_did_info.key {
+        msg!("Error: pending invite is not for the invitee DID");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+    if pending_invite.channel != *channel_info.key {
+        msg!("Error: pending invite is not for this channel");
+        return Err(SolariumError::CEKIncorrectChannel.into());
+    }
+
+    // The channel key may have been rotated since the invite was sent
+    let mut channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    for cek in pending_invite.ceks.iter() {
+        check_epoch(channel.epoch, cek.epoch)?;
+    }
+
+    create_cek_account(
+        program_id,
+        pending_invite.ceks,
+        funder_info.clone(),
+        invitee_did_info,
+        invitee_cek_account_info.clone(),
+        channel_info,
+        system_program_info.clone(),
+        rent,
+    )?;
+
+    add_channel_member(
+        program_id,
+        funder_info.clone(),
+        channel_info,
+        &mut channel,
+        channel_members_info.clone(),
+        members_page,
+        invitee_did_info.key,
+        system_program_info.clone(),
+        rent,
+    )?;
+    channel.serialize(&mut &mut channel_info.data.borrow_mut()[..])?;
+
+    close_account(pending_invite_info, invite_funder_info)
+}
+
+fn decline_invite(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::DeclineInvite");
+    let account_info_iter = &mut accounts.iter();
+    let invitee_did_info = next_account_info(account_info_iter)?;
+    let invitee_authority_info = next_account_info(account_info_iter)?;
+    let pending_invite_info = next_account_info(account_info_iter)?;
+    let invite_funder_info = next_account_info(account_info_iter)?;
+
+    // Check that the signer is an authority on the invitee DID
+    check_authority_of_did(invitee_authority_info, invitee_did_info)?;
+
+    let pending_invite = load_pending_invite(program_id, pending_invite_info, invite_funder_info)?;
+    if pending_invite.invitee_did != *invitee_did_info.key {
+        msg!("Error: pending invite is not for the invitee DID");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    close_account(pending_invite_info, invite_funder_info)
+}
+
+fn revoke_invite(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::RevokeInvite");
+    let account_info_iter = &mut accounts.iter();
+    let inviter_did_info = next_account_info(account_info_iter)?;
+    let inviter_authority_info = next_account_info(account_info_iter)?;
+    let pending_invite_info = next_account_info(account_info_iter)?;
+    let invite_funder_info = next_account_info(account_info_iter)?;
+
+    // Check that the signer is an authority on the inviter DID
+    check_authority_of_did(inviter_authority_info, inviter_did_info)?;
+
+    let pending_invite = load_pending_invite(program_id, pending_invite_info, invite_funder_info)?;
+    if pending_invite.inviter_did != *inviter_did_info.key {
+        msg!("Error: pending invite was not sent by the inviter DID");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    close_account(pending_invite_info, invite_funder_info)
+}
+
+/// Loads a pending invite, checking its address and that the lamports of the invite are returned to its funder
+fn load_pending_invite(
+    program_id: &Pubkey,
+    pending_invite_info: &AccountInfo,
+    invite_funder_info: &AccountInfo,
+) -> Result<PendingInvite, ProgramError> {
+    if pending_invite_info.owner != program_id {
+        msg!("Error: Pending invite is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let pending_invite = program_borsh::try_from_slice_incomplete::<PendingInvite>(
+        *pending_invite_info.data.borrow(),
+    )?;
+    if !pending_invite.is_initialized() {
+        msg!("Pending invite not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    let (pending_invite_address, _) = get_pending_invite_account_address_with_seed(
+        program_id,
+        &pending_invite.invitee_did,
+        &pending_invite.channel,
+    );
+    if pending_invite_address != *pending_invite_info.key {
+        msg!("Error: Pending invite address is not derived from the invitee DID and channel");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    if pending_invite.funder != *invite_funder_info.key {
+        msg!("Error: The recipient is not the funder of the pending invite");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    Ok(pending_invite)
+}
+
+fn create_invite_code(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    invite_pubkey: Pubkey,
+    expiry: i64,
+    max_uses: u16,
+) -> ProgramResult {
+    msg!("SolariumInstruction::CreateInviteCode");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let creator_did_info = next_account_info(account_info_iter)?;
+    let creator_authority_info = next_account_info(account_info_iter)?;
+    let creator_cek_account_info = next_account_info(account_info_iter)?;
+    let invite_code_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the creator has permissions to invite to this channel
+    check_cek_account(program_id, creator_cek_account_info, channel_info)?;
+
+    // Check that the creator signer is valid for the DID
+    // and that the creator DID owns the creator CEK account
+    check_authority_of_cek(
+        program_id,
+        creator_authority_info,
+        creator_did_info,
+        creator_cek_account_info,
+    )?;
+
+    // Check that the creator holds the current CEK, so that they can provision it to redeemers
+    let channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    check_current_epoch_cek(creator_cek_account_info, &channel)?;
+
+    // Check that the invite code can be redeemed at least once
+    if max_uses == 0 {
+        msg!("Error: invite code must allow at least one use");
+        return Err(SolariumError::InviteCodeExhausted.into());
+    }
+    if expiry < Clock::get()?.unix_timestamp {
+        msg!("Error: invite code expiry is in the past");
+        return Err(SolariumError::InviteCodeExpired.into());
+    }
+
+    let (invite_code_address, invite_code_bump_seed) =
+        get_invite_code_account_address_with_seed(program_id, channel_info.key, &invite_pubkey);
+    if invite_code_address != *invite_code_info.key {
+        msg!("Error: invite code address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    if !invite_code_info.data_is_empty() {
+        msg!("Invite code already exists");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let size = InviteCode::size_bytes();
+    let invite_code_signer_seeds: &[&[_]] = &[
+        &channel_info.key.to_bytes(),
+        &invite_pubkey.to_bytes(),
+        INVITE_CODE_ACCOUNT_ADDRESS_SEED,
+        &[invite_code_bump_seed],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            invite_code_info.key,
+            1.max(rent.minimum_balance(size as usize)),
+            size,
+            program_id,
+        ),
+        &[
+            funder_info.clone(),
+            invite_code_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[invite_code_signer_seeds],
+    )?;
+
+    let invite_code = InviteCode::new(
+        *channel_info.key,
+        *creator_did_info.key,
+        *funder_info.key,
+        invite_pubkey,
+        expiry,
+        max_uses,
+    );
+
+    invite_code
+        .serialize(&mut &mut invite_code_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn redeem_invite(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    members_page: u16,
+) -> ProgramResult {
+    msg!("SolariumInstruction::RedeemInvite");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let redeemer_did_info = next_account_info(account_info_iter)?;
+    let redeemer_authority_info = next_account_info(account_info_iter)?;
+    let redeemer_cek_account_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let invite_code_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let channel_members_info = next_account_info(account_info_iter)?;
+    let invite_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the signer is an authority on the redeemer DID
+    check_authority_of_did(redeemer_authority_info, redeemer_did_info)?;
+
+    if !invite_info.is_signer {
+        msg!("Invite keypair signature missing");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut invite_code = load_invite_code(program_id, invite_code_info)?;
+    if invite_code.channel != *channel_info.key {
+        msg!("Error: invite code is not for this channel");
+        return Err(SolariumError::CEKIncorrectChannel.into());
+    }
+
+    // Check the invite key, expiry and remaining uses of the invite code
+    invite_code.redeem(invite_info.key, Clock::get()?.unix_timestamp)?;
+    invite_code.serialize(&mut &mut invite_code_info.data.borrow_mut()[..])?;
+
+    // The CEKs are added later by a member of the channel
+    create_cek_account(
+        program_id,
+        vec![],
+        funder_info.clone(),
+        redeemer_did_info,
+        redeemer_cek_account_info.clone(),
+        channel_info,
+        system_program_info.clone(),
+        rent,
+    )?;
+
+    let mut channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    add_channel_member(
+        program_id,
+        funder_info.clone(),
+        channel_info,
+        &mut channel,
+        channel_members_info.clone(),
+        members_page,
+        redeemer_did_info.key,
+        system_program_info.clone(),
+        rent,
+    )?;
+    channel
+        .serialize(&mut &mut channel_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn close_invite_code(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::CloseInviteCode");
+    let account_info_iter = &mut accounts.iter();
+    let creator_did_info = next_account_info(account_info_iter)?;
+    let creator_authority_info = next_account_info(account_info_iter)?;
+    let invite_code_info = next_account_info(account_info_iter)?;
+    let invite_funder_info = next_account_info(account_info_iter)?;
+
+    // Check that the signer is an authority on the creator DID
+    check_authority_of_did(creator_authority_info, creator_did_info)?;
+
+    let invite_code = load_invite_code(program_id, invite_code_info)?;
+    if invite_code.creator_did != *creator_did_info.key {
+        msg!("Error: invite code was not created by the creator DID");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+    if invite_code.funder != *invite_funder_info.key {
+        msg!("Error: The recipient is not the funder of the invite code");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    close_account(invite_code_info, invite_funder_info)
+}
+
+fn load_invite_code(
+    program_id: &Pubkey,
+    invite_code_info: &AccountInfo,
+) -> Result<InviteCode, ProgramError> {
+    if invite_code_info.owner != program_id {
+        msg!("Error: Invite code is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let invite_code =
+        program_borsh::try_from_slice_incomplete::<InviteCode>(*invite_code_info.data.borrow())?;
+    if !invite_code.is_initialized() {
+        msg!("Invite code not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    Ok(invite_code)
+}
+
+/// Removes the member from the channel, closing their CEK account
+fn leave_channel(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    members_page: u16,
+) -> ProgramResult {
+    msg!("SolariumInstruction::LeaveChannel");
+    let account_info_iter = &mut accounts.iter();
+    let member_did_info = next_account_info(account_info_iter)?;
+    let member_authority_info = next_account_info(account_info_iter)?;
+    let member_cek_account_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let channel_members_info = next_account_info(account_info_iter)?;
+    let recipient_info = next_account_info(account_info_iter)?;
+
+    // Check that the signer is an authority on the DID
+    // and that the DID is a member of the channel
+    check_authority_of_did(member_authority_info, member_did_info)?;
+    check_membership(
+        program_id,
+        member_did_info.key,
+        member_cek_account_info,
+        channel_info.key,
+    )?;
+
+    let mut channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    remove_channel_member(
+        program_id,
+        channel_info,
+        &mut channel,
+        channel_members_info,
+        members_page,
+        member_did_info.key,
+    )?;
+    channel.serialize(&mut &mut channel_info.data.borrow_mut()[..])?;
+
+    close_account(member_cek_account_info, recipient_info)
+}
+
+/// Removes a member from the channel on behalf of another member, closing the member's CEK account
+/// and notifying the member if they passed their notifications account
+fn remove_from_channel(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    members_page: u16,
+) -> ProgramResult {
+    msg!("SolariumInstruction::RemoveFromChannel");
+    let account_info_iter = &mut accounts.iter();
+    let remover_did_info = next_account_info(account_info_iter)?;
+    let remover_authority_info = next_account_info(account_info_iter)?;
+    let remover_cek_account_info = next_account_info(account_info_iter)?;
+    let member_did_info = next_account_info(account_info_iter)?;
+    let member_cek_account_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let channel_members_info = next_account_info(account_info_iter)?;
+    let member_authority_info = next_account_info(account_info_iter)?;
+    let member_notifications_info = next_account_info(account_info_iter).ok();
+
+    if channel_info.owner != program_id {
+        msg!("Error: channel is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    if !channel.is_initialized() {
+        msg!("Channel account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    // Check that both the remover and the member are members of the channel,
+    // and that the remover holds the current CEK
+    check_authority_of_did(remover_authority_info, remover_did_info)?;
+    check_membership(
+        program_id,
+        remover_did_info.key,
+        remover_cek_account_info,
+        channel_info.key,
+    )?;
+    check_membership(
+        program_id,
+        member_did_info.key,
+        member_cek_account_info,
+        channel_info.key,
+    )?;
+    check_current_epoch_cek(remover_cek_account_info, &channel)?;
+
+    // The rent of the CEK account is refunded to the removed member rather than the remover
+    if get_did_authority(member_did_info)? != *member_authority_info.key {
+        msg!("Error: the CEK account must be refunded to the member DID authority");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    remove_channel_member(
+        program_id,
+        channel_info,
+        &mut channel,
+        channel_members_info,
+        members_page,
+        member_did_info.key,
+    )?;
+    channel.serialize(&mut &mut channel_info.data.borrow_mut()[..])?;
+
+    close_account(member_cek_account_info, member_authority_info)?;
+
+    if let Some(member_notifications_info) = member_notifications_info {
+        let notification = Notification::new(
+            NotificationType::RemovedFromChannel,
+            *channel_info.key,
+            *remover_did_info.key,
+        );
+        notify_invitee(
+            program_id,
+            member_did_info.key,
+            member_notifications_info,
+            notification,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Adds the DID to a page of the channel members list, creating the page if it is unallocated,
+/// and counts the new member on the channel.
+/// The page must be the last page, or the page following it once the last page is full.
+#[allow(clippy::too_many_arguments)]
+fn add_channel_member<'a>(
+    program_id: &Pubkey,
+    funder_info: AccountInfo<'a>,
+    channel_info: &AccountInfo,
+    channel: &mut ChannelData,
+    channel_members_info: AccountInfo<'a>,
+    page: u16,
+    did: &Pubkey,
+    system_program_info: AccountInfo<'a>,
+    rent: &Rent,
+) -> ProgramResult {
+    let (channel_members_address, channel_members_bump_seed) =
+        get_channel_members_account_address_with_seed(program_id, channel_info.key, page);
+    if channel_members_address != *channel_members_info.key {
+        msg!("Error: channel members account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    if let Err(error) = channel.add_member(page) {
+        msg!(
+            "Error: members must be added to channel members page {}",
+            channel.next_members_page()
+        );
+        return Err(error.into());
+    }
+
+    // Create the page the first time a member is added to it
+    let mut channel_members_page = if channel_members_info.data_is_empty() {
+        let size = ChannelMembersPage::size_bytes();
+        let channel_members_account_signer_seeds: &[&[_]] = &[
+            &channel_info.key.to_bytes(),
+            &page.to_le_bytes(),
+            CHANNEL_MEMBERS_ACCOUNT_ADDRESS_SEED,
+            &[channel_members_bump_seed],
+        ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                funder_info.key,
+                channel_members_info.key,
+                1.max(rent.minimum_balance(size as usize)),
+                size,
+                program_id,
+            ),
+            &[
+                funder_info.clone(),
+                channel_members_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[channel_members_account_signer_seeds],
+        )?;
+
+        ChannelMembersPage::new(*channel_info.key, page)
+    } else {
+        load_channel_members_page(program_id, channel_info, &channel_members_info)?
+    };
+
+    channel_members_page.add(*did)?;
+
+    channel_members_page
+        .serialize(&mut &mut channel_members_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+/// Removes the DID from a page of the channel members list,
+/// and uncounts the member on the channel
+fn remove_channel_member(
+    program_id: &Pubkey,
+    channel_info: &AccountInfo,
+    channel: &mut ChannelData,
+    channel_members_info: &AccountInfo,
+    page: u16,
+    did: &Pubkey,
+) -> ProgramResult {
+    let (channel_members_address, _) =
+        get_channel_members_account_address_with_seed(program_id, channel_info.key, page);
+    if channel_members_address != *channel_members_info.key {
+        msg!("Error: channel members account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    let mut channel_members_page =
+        load_channel_members_page(program_id, channel_info, channel_members_info)?;
+    channel_members_page.remove(did)?;
+    channel_members_page.serialize(&mut &mut channel_members_info.data.borrow_mut()[..])?;
+
+    channel.remove_member(page).map_err(|e| e.into())
+}
+
+fn load_channel_members_page(
+    program_id: &Pubkey,
+    channel_info: &AccountInfo,
+    channel_members_info: &AccountInfo,
+) -> Result<ChannelMembersPage, ProgramError> {
+    if channel_members_info.owner != program_id {
+        msg!("Error: Channel members page is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let channel_members_page = program_borsh::try_from_slice_incomplete::<ChannelMembersPage>(
+        *channel_members_info.data.borrow(),
+    )?;
+    if !channel_members_page.is_initialized() {
+        msg!("Channel members page not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    if channel_members_page.channel != *channel_info.key {
+        msg!("Error: Channel members page is not for this channel");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    Ok(channel_members_page)
+}
+
+/// Adds a notification to the invitee's notifications account,
+/// if the invitee has created one
+fn notify_invitee(
+    program_id: &Pubkey,
+    invitee_did: &Pubkey,
+    invitee_notifications_info: &AccountInfo,
+    notification: Notification,
+) -> ProgramResult {
+    let (notifications_address, _) =
+        get_notifications_account_address_with_seed(program_id, invitee_did);
+    if notifications_address != *invitee_notifications_info.key {
+        msg!("Error: notifications account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    if invitee_notifications_info.data_is_empty() {
+        msg!("Invitee has no notifications account");
+        return Ok(());
+    }
+
+    push_notification(program_id, invitee_notifications_info, notification)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_cek_account<'a>(
+    program_id: &Pubkey,
+    ceks: Vec<CEKData>,
+    funder_info: AccountInfo<'a>,
+    invitee_did_info: &AccountInfo,
+    invitee_cek_account_info: AccountInfo<'a>,
+    channel_info: &AccountInfo,
+    system_program_info: AccountInfo<'a>,
+    rent: &Rent,
+) -> ProgramResult {
+    let (cek_account_address, cek_account_bump_seed) =
+        get_cek_account_address_with_seed(program_id, invitee_did_info.key, channel_info.key);
+
+    // Check that we are not overwriting an existing cek account
+    let data_len = invitee_cek_account_info.data.borrow().len();
+    if data_len > 0 {
+        msg!("CEK account already initialized");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    // Check that the new cek account address has been derived correctly
+    // for the invitee and channel
+    if cek_account_address != *invitee_cek_account_info.key {
+        msg!("Error: cek account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    // Create the new cek account for the invitee
+    let mut cek_account = CEKAccountData::new(*invitee_did_info.key, *channel_info.key);
+    cek_account.add_all(ceks)?;
+
+    let size = CEKAccountData::size_bytes()?;
+    let cek_account_signer_seeds: &[&[_]] = &[
+        &invitee_did_info.key.to_bytes(),
+        &channel_info.key.to_bytes(),
+        CEK_ACCOUNT_ADDRESS_SEED,
+        &[cek_account_bump_seed],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            invitee_cek_account_info.key,
+            1.max(rent.minimum_balance(size as usize)),
+            size,
+            program_id,
+        ),
+        &[
+            funder_info.clone(),
+            invitee_cek_account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[cek_account_signer_seeds],
+    )?;
+
+    cek_account
+        .serialize(&mut &mut invitee_cek_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn initialize_private_channel(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    name: String,
+    ceks: Vec<CEKData>,
+) -> ProgramResult {
+    msg!("SolariumInstruction::InitializePrivateChannel");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let creator_did_info = next_account_info(account_info_iter)?;
+    let creator_authority_info = next_account_info(account_info_iter)?;
+    let creator_membership_key_info = next_account_info(account_info_iter)?;
+    let creator_cek_account_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    if channel_info.owner != program_id {
+        msg!("Error: channel is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    if channel.is_initialized() {
+        msg!("Error: Attempt to create a channel for an address that is already in use");
+        return Err(SolariumError::AlreadyInUse.into());
+    }
+
+    // Check that the authority is valid for the DID, and that the membership key signed
+    check_authority_of_did(creator_authority_info, creator_did_info)?;
+    check_membership_key(creator_membership_key_info)?;
+
+    let membership_seed = get_membership_seed(
+        creator_did_info.key,
+        channel_info.key,
+        creator_membership_key_info.key,
+    );
+    create_private_cek_account(
+        program_id,
+        channel_info.key,
+        membership_seed,
+        ceks,
+        funder_info.clone(),
+        creator_cek_account_info.clone(),
+        system_program_info.clone(),
+        rent,
+    )?;
+
+    let channel = ChannelData::new_private(name);
+    channel
+        .serialize(&mut &mut channel_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn add_to_private_channel(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    invitee_membership_seed: [u8; 32],
+    ceks: Vec<CEKData>,
+) -> ProgramResult {
+    msg!("SolariumInstruction::AddToPrivateChannel");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let inviter_did_info = next_account_info(account_info_iter)?;
+    let inviter_authority_info = next_account_info(account_info_iter)?;
+    let inviter_membership_key_info = next_account_info(account_info_iter)?;
+    let inviter_cek_account_info = next_account_info(account_info_iter)?;
+    let invitee_cek_account_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    let channel = load_private_channel(program_id, channel_info)?;
+
+    // Check that the inviter signer is valid for the DID, that the inviter is a member
+    // and that the inviter holds the current CEK
+    check_authority_of_did(inviter_authority_info, inviter_did_info)?;
+    let inviter_cek_account = check_private_membership(
+        program_id,
+        inviter_did_info.key,
+        inviter_membership_key_info,
+        inviter_cek_account_info,
+        channel_info.key,
+    )?;
+    if !inviter_cek_account.has_epoch(channel.epoch) {
+        msg!("Error: inviter has no CEK for the current channel epoch");
+        return Err(SolariumError::StaleEpoch.into());
+    }
+
+    // Check that the invitee CEKs belong to the current channel epoch
+    for cek in ceks.iter() {
+        check_epoch(channel.epoch, cek.epoch)?;
+    }
+
+    // The invitee account is derived from the channel as well as the seed,
+    // so it can only be used to prove membership of this channel
+    if invitee_membership_seed == [0; 32] {
+        msg!("Error: the invitee membership seed is not set");
+        return Err(ProgramError::InvalidArgument);
+    }
+    create_private_cek_account(
+        program_id,
+        channel_info.key,
+        invitee_membership_seed,
+        ceks,
+        funder_info.clone(),
+        invitee_cek_account_info.clone(),
+        system_program_info.clone(),
+        rent,
+    )
+}
+
+fn post_private(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    message: String,
+    epoch: u32,
+) -> ProgramResult {
+    msg!("SolariumInstruction::PostPrivate");
+    let account_info_iter = &mut accounts.iter();
+    let channel_info = next_account_info(account_info_iter)?;
+    let sender_did_info = next_account_info(account_info_iter)?;
+    let sender_authority_info = next_account_info(account_info_iter)?;
+    let sender_membership_key_info = next_account_info(account_info_iter)?;
+    let sender_cek_account_info = next_account_info(account_info_iter)?;
+
+    let mut channel = load_private_channel(program_id, channel_info)?;
+
+    // Check that the sender signer is an authority on the DID
+    check_authority_of_did(sender_authority_info, sender_did_info)?;
+
+    // check that the sender is a member of this channel and holds a CEK for the current epoch,
+    // and that the message is encrypted with it
+    let sender_cek_account = check_private_membership(
+        program_id,
+        sender_did_info.key,
+        sender_membership_key_info,
+        sender_cek_account_info,
+        channel_info.key,
+    )?;
+    check_epoch(channel.epoch, epoch)?;
+    if !sender_cek_account.has_epoch(channel.epoch) {
+        msg!("Error: sender has no CEK for the current channel epoch");
+        return Err(SolariumError::StaleEpoch.into());
+    }
+
+    let message_info = Message::new(*sender_did_info.key, message).with_epoch(epoch);
+    channel.post(message_info);
+
+    channel
+        .serialize(&mut &mut channel_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn leave_private_channel(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::LeavePrivateChannel");
+    let account_info_iter = &mut accounts.iter();
+    let member_did_info = next_account_info(account_info_iter)?;
+    let member_authority_info = next_account_info(account_info_iter)?;
+    let member_membership_key_info = next_account_info(account_info_iter)?;
+    let member_cek_account_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+    let recipient_info = next_account_info(account_info_iter)?;
+
+    load_private_channel(program_id, channel_info)?;
+
+    // Check that the signer is an authority on the DID
+    // and that the DID is a member of the channel
+    check_authority_of_did(member_authority_info, member_did_info)?;
+    check_private_membership(
+        program_id,
+        member_did_info.key,
+        member_membership_key_info,
+        member_cek_account_info,
+        channel_info.key,
+    )?;
+
+    close_account(member_cek_account_info, recipient_info)
+}
+
+fn add_private_cek(program_id: &Pubkey, accounts: &[AccountInfo], cek: CEKData) -> ProgramResult {
+    msg!("SolariumInstruction::AddPrivateCEK");
+    let account_info_iter = &mut accounts.iter();
+    let member_did_info = next_account_info(account_info_iter)?;
+    let member_authority_info = next_account_info(account_info_iter)?;
+    let member_membership_key_info = next_account_info(account_info_iter)?;
+    let member_cek_account_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+
+    let channel = load_private_channel(program_id, channel_info)?;
+
+    // Check that the signer is an authority on the DID
+    // and that the DID is a member of the channel
+    check_authority_of_did(member_authority_info, member_did_info)?;
+    let mut member_cek_account = check_private_membership(
+        program_id,
+        member_did_info.key,
+        member_membership_key_info,
+        member_cek_account_info,
+        channel_info.key,
+    )?;
+    check_epoch(channel.epoch, cek.epoch)?;
+
+    // Only CEKs for new keys on the member DID can be added
+    let kid = key_id_fragment(&cek.kid);
+    if !get_did_key_ids(member_did_info)?
+        .iter()
+        .any(|key_id| key_id == kid)
+    {
+        msg!("Error: key {} is not on the member DID", kid);
+        return Err(SolariumError::KeyNotOnDID.into());
+    }
+    if member_cek_account
+        .ceks
+        .iter()
+        .any(|existing| key_id_fragment(&existing.kid) == kid)
+    {
+        msg!("Error: the member already has a CEK for key {}", kid);
+        return Err(SolariumError::CEKAlreadyExists.into());
+    }
+
+    member_cek_account.add(cek)?;
+
+    member_cek_account
+        .serialize(&mut &mut member_cek_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn rotate_private_channel_key(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    epoch: u32,
+    member_ceks: Vec<Vec<CEKData>>,
+) -> ProgramResult {
+    msg!("SolariumInstruction::RotatePrivateChannelKey");
+    let account_info_iter = &mut accounts.iter();
+    let channel_info = next_account_info(account_info_iter)?;
+    let rotator_did_info = next_account_info(account_info_iter)?;
+    let rotator_authority_info = next_account_info(account_info_iter)?;
+    let rotator_membership_key_info = next_account_info(account_info_iter)?;
+    let rotator_cek_account_info = next_account_info(account_info_iter)?;
+
+    let mut channel = load_private_channel(program_id, channel_info)?;
+
+    // Check that the rotator is a member of the channel holding a CEK for the current epoch
+    check_authority_of_did(rotator_authority_info, rotator_did_info)?;
+    let rotator_cek_account = check_private_membership(
+        program_id,
+        rotator_did_info.key,
+        rotator_membership_key_info,
+        rotator_cek_account_info,
+        channel_info.key,
+    )?;
+    if !rotator_cek_account.has_epoch(channel.epoch) {
+        msg!("Error: rotator has no CEK for the current channel epoch");
+        return Err(SolariumError::StaleEpoch.into());
+    }
+
+    if channel.epoch.checked_add(1) == Some(epoch) {
+        // Start a new rotation
+        channel.epoch = epoch;
+        channel.serialize(&mut &mut channel_info.data.borrow_mut()[..])?;
+    } else {
+        // Continue a rotation
+        check_epoch(channel.epoch, epoch)?;
+    }
+
+    for ceks in member_ceks {
+        let member_cek_account_info = next_account_info(account_info_iter)?;
+
+        if ceks.is_empty() {
+            msg!("Error: each member must be given at least one CEK");
+            return Err(SolariumError::NoCEKs.into());
+        }
+        if ceks.iter().any(|cek| cek.epoch != epoch) {
+            msg!("Error: CEKs must belong to the new epoch");
+            return Err(SolariumError::InvalidEpoch.into());
+        }
+
+        // Members that already hold a CEK for the epoch were rotated by an earlier transaction,
+        // and their CEKs are not replaced, so that a continued rotation cannot overwrite them
+        let mut member_cek_account =
+            load_private_cek_account(program_id, member_cek_account_info, channel_info.key)?;
+        if member_cek_account.has_epoch(epoch) {
+            msg!("Error: the member already holds a CEK for the epoch");
+            return Err(SolariumError::CEKAlreadyExists.into());
+        }
+        member_cek_account.replace_all(ceks)?;
+        member_cek_account.serialize(&mut &mut member_cek_account_info.data.borrow_mut()[..])?;
+    }
+
+    Ok(())
+}
+
+fn load_private_channel(
+    program_id: &Pubkey,
+    channel_info: &AccountInfo,
+) -> Result<ChannelData, ProgramError> {
+    if channel_info.owner != program_id {
+        msg!("Error: channel is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    if !channel.is_initialized() {
+        msg!("Channel account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+    if !channel.private {
+        msg!("Error: channel does not have private membership");
+        return Err(SolariumError::ChannelNotPrivate.into());
+    }
+
+    Ok(channel)
+}
+
+/// Checks that the membership key of a member of a private channel signed the transaction
+fn check_membership_key(membership_key_info: &AccountInfo) -> ProgramResult {
+    if !membership_key_info.is_signer {
+        msg!("Error: membership key signature missing");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    Ok(())
+}
+
+/// Checks that the private CEK account belongs to the DID in the channel,
+/// by recomputing the blinded membership seed from the membership key, which must sign
+fn check_private_membership(
+    program_id: &Pubkey,
+    did: &Pubkey,
+    membership_key_info: &AccountInfo,
+    cek_account_info: &AccountInfo,
+    channel: &Pubkey,
+) -> Result<PrivateCEKAccountData, ProgramError> {
+    check_membership_key(membership_key_info)?;
+
+    let membership_seed = get_membership_seed(did, channel, membership_key_info.key);
+    let cek_account = load_private_cek_account(program_id, cek_account_info, channel)?;
+    if cek_account.membership_seed != membership_seed {
+        msg!("Error: private cek account is not for this member of the channel");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    Ok(cek_account)
+}
+
+/// Loads a private CEK account, checking that it is owned by this program
+/// and that its address is derived from the channel and its membership seed
+fn load_private_cek_account(
+    program_id: &Pubkey,
+    cek_account_info: &AccountInfo,
+    channel: &Pubkey,
+) -> Result<PrivateCEKAccountData, ProgramError> {
+    if cek_account_info.owner != program_id {
+        msg!("Error: private cek account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let cek_account = program_borsh::try_from_slice_incomplete::<PrivateCEKAccountData>(
+        *cek_account_info.data.borrow(),
+    )?;
+    if !cek_account.is_initialized() {
+        msg!("Private cek account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    let (cek_account_address, _) = get_private_cek_account_address_with_seed(
+        program_id,
+        channel,
+        &cek_account.membership_seed,
+    );
+    if cek_account_address != *cek_account_info.key {
+        msg!("Error: private cek account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    Ok(cek_account)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_private_cek_account<'a>(
+    program_id: &Pubkey,
+    channel: &Pubkey,
+    membership_seed: [u8; 32],
+    ceks: Vec<CEKData>,
+    funder_info: AccountInfo<'a>,
+    cek_account_info: AccountInfo<'a>,
+    system_program_info: AccountInfo<'a>,
+    rent: &Rent,
+) -> ProgramResult {
+    let (cek_account_address, cek_account_bump_seed) =
+        get_private_cek_account_address_with_seed(program_id, channel, &membership_seed);
+
+    // Check that we are not overwriting an existing cek account
+    let data_len = cek_account_info.data.borrow().len();
+    if data_len > 0 {
+        msg!("CEK account already initialized");
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    // Check that the new cek account address has been derived from the channel and membership seed
+    if cek_account_address != *cek_account_info.key {
+        msg!("Error: private cek account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    let mut cek_account = PrivateCEKAccountData::new(membership_seed);
+    cek_account.add_all(ceks)?;
+
+    let size = PrivateCEKAccountData::size_bytes()?;
+    let cek_account_signer_seeds: &[&[_]] = &[
+        &channel.to_bytes(),
+        &membership_seed,
+        PRIVATE_CEK_ACCOUNT_ADDRESS_SEED,
+        &[cek_account_bump_seed],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            cek_account_info.key,
+            1.max(rent.minimum_balance(size as usize)),
+            size,
+            program_id,
+        ),
+        &[
+            funder_info.clone(),
+            cek_account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[cek_account_signer_seeds],
+    )?;
+
+    cek_account
+        .serialize(&mut &mut cek_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn update_user_details(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    alias: Option<String>,
+    address_book: Option<String>,
+    status: Option<String>,
+    avatar_uri: Option<String>,
+) -> ProgramResult {
+    msg!("SolariumInstruction::UpdateUserDetails");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let user_details_account_info = next_account_info(account_info_iter)?;
+    let mut user_details = program_borsh::try_from_slice_incomplete::<UserDetails>(
+        *user_details_account_info.data.borrow(),
+    )?;
+
+    if !user_details.is_initialized() {
+        msg!("UserDetails account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    // Check that the signer is an authority on the DID
+    // and that the user details account belongs to the DID
+    check_authority_of_user_details(
+        program_id,
+        authority_info,
+        did_info,
+        user_details_account_info,
+    )?;
+
+    // mutate the UserDetails object, leaving fields that were not supplied unchanged
+    if let Some(alias) = alias {
+        let alias = nfkc_alias(&alias);
+        validate_alias(&alias)?;
+        user_details.alias = alias;
+    }
+    if let Some(address_book) = address_book {
+        user_details.address_book = address_book.into();
+    }
+    if let Some(status) = status {
+        if status.len() > UserDetails::MAX_STATUS_SIZE as usize {
+            msg!(
+                "Error: Status exceeds {} bytes",
+                UserDetails::MAX_STATUS_SIZE
+            );
+            return Err(SolariumError::StatusTooLong.into());
+        }
+        user_details.status = status;
+    }
+    if let Some(avatar_uri) = avatar_uri {
+        if avatar_uri.len() > UserDetails::MAX_AVATAR_URI_SIZE as usize {
+            msg!(
+                "Error: Avatar URI exceeds {} bytes",
+                UserDetails::MAX_AVATAR_URI_SIZE
+            );
+            return Err(SolariumError::AvatarUriTooLong.into());
+        }
+        user_details.avatar_uri = avatar_uri;
+    }
+
+    user_details
+        .serialize(&mut &mut user_details_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn resize_user_details(program_id: &Pubkey, accounts: &[AccountInfo], size: u32) -> ProgramResult {
+    msg!("SolariumInstruction::ResizeUserDetails");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let user_details_account_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the signer is an authority on the DID
+    // and that the user details account belongs to the DID
+    check_authority_of_user_details(
+        program_id,
+        authority_info,
+        did_info,
+        user_details_account_info,
+    )?;
+
+    if user_details_account_info.owner != program_id {
+        msg!("Error: UserDetails account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // Check that the existing user details fit into the new size
+    let user_details = program_borsh::try_from_slice_incomplete::<UserDetails>(
+        *user_details_account_info.data.borrow(),
+    )?;
+    if program_borsh::get_instance_packed_len(&user_details)? > size as usize {
+        msg!("Error: UserDetails do not fit into an account of the requested size");
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+
+    resize_account(
+        user_details_account_info,
+        size as usize,
+        funder_info,
+        system_program_info,
+        rent,
+    )
+}
+
+fn close_user_details(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::CloseUserDetails");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let user_details_account_info = next_account_info(account_info_iter)?;
+
+    // Check that the signer is an authority on the DID
+    // and that the user details account belongs to the DID
+    check_authority_of_user_details(
+        program_id,
+        authority_info,
+        did_info,
+        user_details_account_info,
+    )?;
+
+    if user_details_account_info.owner != program_id {
+        msg!("Error: UserDetails account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // Refund the rent to the owner
+    close_account(user_details_account_info, authority_info)
+}
+
+/// Checks that the authority_info account is an authority for the DID,
+/// And that the user details account is derived from that DID
+fn check_authority_of_user_details(
+    program_id: &Pubkey,
+    authority_info: &AccountInfo,
+    did: &AccountInfo,
+    user_details_account_info: &AccountInfo,
+) -> ProgramResult {
+    check_authority_of_did(authority_info, did)?;
+
+    let (user_details_address, _) = get_userdetails_account_address_with_seed(program_id, did.key);
+    if user_details_address != *user_details_account_info.key {
+        msg!("Error: Attempt to update a userdetails account with an address not derived from the DID");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    Ok(())
+}
+
+fn add_cek(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    cek: CEKData,
+    prune_stale_ceks: bool,
+) -> ProgramResult {
+    if prune_stale_ceks {
+        msg!("SolariumInstruction::AddCEKAndPrune");
+    } else {
+        msg!("SolariumInstruction::AddCEK");
+    }
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let cek_account_info = next_account_info(account_info_iter)?;
+
+    msg!("checking authority");
+    // Check that the authority is valid for the DID
+    // and that the DID owns the CEK account
+    check_authority_of_cek(program_id, authority_info, did_info, cek_account_info)?;
+
+    msg!("checking account");
+    let mut cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+        *cek_account_info.data.borrow(),
+    )?;
+
+    if prune_stale_ceks {
+        let pruned = cek_account.prune(&get_did_key_ids(did_info)?);
+        msg!("pruned {} stale CEKs", pruned);
+    }
+
+    msg!("adding");
+    cek_account.add(cek)?;
+
+    msg!("serializing");
+    cek_account
+        .serialize(&mut &mut cek_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn replace_cek(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    kid: String,
+    cek: CEKData,
+) -> ProgramResult {
+    msg!("SolariumInstruction::ReplaceCEK");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let cek_account_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID
+    // and that the DID owns the CEK account
+    check_authority_of_cek(program_id, authority_info, did_info, cek_account_info)?;
+
+    let mut cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+        *cek_account_info.data.borrow(),
+    )?;
+
+    cek_account.replace(&kid, cek)?;
+
+    cek_account
+        .serialize(&mut &mut cek_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn add_ceks(program_id: &Pubkey, accounts: &[AccountInfo], ceks: Vec<CEKData>) -> ProgramResult {
+    msg!("SolariumInstruction::AddCEKs");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID once for all CEK accounts
+    check_authority_of_did(authority_info, did_info)?;
+
+    for cek in ceks {
+        let cek_account_info = next_account_info(account_info_iter)?;
+
+        if cek_account_info.owner != program_id {
+            msg!("Error: cek account is not a Solarium program account");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let mut cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+            *cek_account_info.data.borrow(),
+        )?;
+        if cek_account.owner_did != *did_info.key {
+            msg!("Error: cek account is not owned by the DID");
+            return Err(SolariumError::IncorrectAuthority.into());
+        }
+
+        cek_account.add(cek)?;
+
+        cek_account.serialize(&mut &mut cek_account_info.data.borrow_mut()[..])?;
+    }
+
+    Ok(())
+}
+
+fn provision_member_cek(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    cek: CEKData,
+) -> ProgramResult {
+    msg!("SolariumInstruction::ProvisionMemberCEK");
+    let account_info_iter = &mut accounts.iter();
+    let provider_did_info = next_account_info(account_info_iter)?;
+    let provider_authority_info = next_account_info(account_info_iter)?;
+    let provider_cek_account_info = next_account_info(account_info_iter)?;
+    let member_did_info = next_account_info(account_info_iter)?;
+    let member_cek_account_info = next_account_info(account_info_iter)?;
+    let channel_info = next_account_info(account_info_iter)?;
+
+    if channel_info.owner != program_id {
+        msg!("Error: channel is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let channel =
+        program_borsh::try_from_slice_incomplete::<ChannelData>(*channel_info.data.borrow())?;
+    if !channel.is_initialized() {
+        msg!("Channel account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    // Check that both the provider and the member are members of the channel
+    check_authority_of_did(provider_authority_info, provider_did_info)?;
+    check_membership(
+        program_id,
+        provider_did_info.key,
+        provider_cek_account_info,
+        channel_info.key,
+    )?;
+    check_membership(
+        program_id,
+        member_did_info.key,
+        member_cek_account_info,
+        channel_info.key,
+    )?;
+
+    // Check that the provider holds the current CEK, and that the CEK is for the current epoch
+    check_current_epoch_cek(provider_cek_account_info, &channel)?;
+    check_epoch(channel.epoch, cek.epoch)?;
+
+    let mut member_cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+        *member_cek_account_info.data.borrow(),
+    )?;
+
+    // Only CEKs for new keys on the member DID can be provisioned,
+    // so that a provider cannot replace the member's existing CEKs
+    let kid = key_id_fragment(&cek.kid);
+    if !get_did_key_ids(member_did_info)?
+        .iter()
+        .any(|key_id| key_id == kid)
+    {
+        msg!("Error: key {} is not on the member DID", kid);
+        return Err(SolariumError::KeyNotOnDID.into());
+    }
+    if member_cek_account
+        .ceks
+        .iter()
+        .any(|existing| key_id_fragment(&existing.kid) == kid)
+    {
+        msg!("Error: the member already has a CEK for key {}", kid);
+        return Err(SolariumError::CEKAlreadyExists.into());
+    }
+
+    member_cek_account.add(cek)?;
+
+    member_cek_account
+        .serialize(&mut &mut member_cek_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn prune_stale_ceks(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::PruneStaleCEKs");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let cek_account_info = next_account_info(account_info_iter)?;
+
+    if cek_account_info.owner != program_id {
+        msg!("Error: cek account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+        *cek_account_info.data.borrow(),
+    )?;
+    if cek_account.owner_did != *did_info.key {
+        msg!("Error: cek account is not owned by the DID");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    // Check that the account is the CEK account of the DID for its channel,
+    // rather than another account of this program that reads as a CEK account
+    let (cek_account_address, _) =
+        get_cek_account_address_with_seed(program_id, did_info.key, &cek_account.channel);
+    if cek_account_address != *cek_account_info.key {
+        msg!("Error: cek account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    let pruned = cek_account.prune(&get_did_key_ids(did_info)?);
+    msg!("pruned {} stale CEKs", pruned);
+
+    cek_account
+        .serialize(&mut &mut cek_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+/// Get the authority of a DID, i.e. the key that the DID address is derived from
+fn get_did_authority(did_info: &AccountInfo) -> Result<Pubkey, ProgramError> {
+    if *did_info.owner != sol_did::id() {
+        msg!("Error: DID account is not a sol-did program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let did = program_borsh::try_from_slice_incomplete::<SolData>(*did_info.data.borrow())?;
+    Ok(did.authority)
+}
+
+/// Get the fragments of the IDs of the verification methods on a DID,
+/// including the default key inferred from the DID authority
+fn get_did_key_ids(did_info: &AccountInfo) -> Result<Vec<String>, ProgramError> {
+    if *did_info.owner != sol_did::id() {
+        msg!("Error: DID account is not a sol-did program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let did = program_borsh::try_from_slice_incomplete::<SolData>(*did_info.data.borrow())?;
+
+    Ok(did
+        .inferred_verification_methods()
+        .iter()
+        .map(|verification_method| key_id_fragment(&verification_method.id).to_string())
+        .collect())
+}
+
+fn remove_cek(program_id: &Pubkey, accounts: &[AccountInfo], kid: String) -> ProgramResult {
+    msg!("SolariumInstruction::RemoveCEK");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let cek_account_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID
+    // and that the DID owns the CEK account
+    check_authority_of_cek(program_id, authority_info, did_info, cek_account_info)?;
+
+    let mut cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
+        *cek_account_info.data.borrow(),
+    )?;
+
+    cek_account.remove(kid)?;
+
+    cek_account
+        .serialize(&mut &mut cek_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn create_user_details(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    alias: String,
+    address_book: String,
+    size: u32,
+) -> ProgramResult {
+    msg!("SolariumInstruction::CreateUserDetails");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let user_details_account_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    let data_len = user_details_account_info.data.borrow().len();
+    if data_len > 0 {
+        msg!(
+            "Error: Attempt to create a userdetails account for an address that is already in use"
+        );
+        return Err(SolariumError::AlreadyInUse.into());
+    }
+
+    // Check that the authority is valid for the DID
+    check_authority_of_did(authority_info, did_info)?;
+
+    let (user_details_address, user_details_bump_seed) =
+        get_userdetails_account_address_with_seed(program_id, did_info.key);
+    if user_details_address != *user_details_account_info.key {
+        msg!("Error: Attempt to create a userdetails account with an address not derived from the DID");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    let alias = nfkc_alias(&alias);
+    validate_alias(&alias)?;
+
+    // Create the new userdetails account for the invitee
+    let new_user_details = UserDetails {
+        alias,
+        address_book: address_book.into(),
+        ..UserDetails::default()
+    };
+
+    let user_details_account_signer_seeds: &[&[_]] = &[
+        &did_info.key.to_bytes(),
+        USERDETAILS_ACCOUNT_ADDRESS_SEED,
+        &[user_details_bump_seed],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            user_details_account_info.key,
+            1.max(rent.minimum_balance(size as usize)),
+            size as u64,
+            program_id,
+        ),
+        &[
+            funder_info.clone(),
+            user_details_account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[user_details_account_signer_seeds],
+    )?;
+
+    new_user_details
+        .serialize(&mut &mut user_details_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn create_notifications(program_id: &Pubkey, accounts: &[AccountInfo], size: u16) -> ProgramResult {
+    msg!("SolariumInstruction::CreateNotifications");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let notifications_account_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    let data_len = notifications_account_info.data.borrow().len();
+    if data_len > 0 {
+        msg!("Error: Attempt to create a notifications account for an address that is already in use");
+        return Err(SolariumError::AlreadyInUse.into());
+    }
+
+    // Check that the authority is valid for the DID
+    check_authority_of_did(authority_info, did_info)?;
+
+    let (notifications_address, notifications_bump_seed) =
+        get_notifications_account_address_with_seed(program_id, did_info.key);
+    if notifications_address != *notifications_account_info.key {
+        msg!("Error: Attempt to create a notifications account with an address not derived from the DID");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    // Create the new notifications account
+    let new_notifications = Notifications::new(size)?;
+    new_notifications.check_allocation(0)?;
+    let notifications_size = new_notifications.size_bytes()?;
+
+    let notifications_account_signer_seeds: &[&[_]] = &[
+        &did_info.key.to_bytes(),
+        NOTIFICATIONS_ACCOUNT_ADDRESS_SEED,
+        &[notifications_bump_seed],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            notifications_account_info.key,
+            1.max(rent.minimum_balance(notifications_size as usize)),
+            notifications_size,
+            program_id,
+        ),
+        &[
+            funder_info.clone(),
+            notifications_account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[notifications_account_signer_seeds],
+    )?;
+
+    new_notifications
+        .serialize(&mut &mut notifications_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn add_notification(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    notification_type: NotificationType,
+    pubkey: &Pubkey,
+) -> ProgramResult {
+    msg!("SolariumInstruction::AddNotification");
+    let account_info_iter = &mut accounts.iter();
+    let notifications_info = next_account_info(account_info_iter)?;
+    let sender_did_info = next_account_info(account_info_iter)?;
+    let sender_authority_info = next_account_info(account_info_iter)?;
+    let owner_did_info = next_account_info(account_info_iter)?;
+    let owner_blocklist_info = next_account_info(account_info_iter)?;
+    let sender_cek_account_info = next_account_info(account_info_iter).ok();
+
+    // Check that the sender of the message is valid
+    // the sender signer is an authority on the DID.
+    validate_owner(sender_did_info, &[sender_authority_info])?;
+
+    // Check that the notifications account belongs to the owner DID
+    let (notifications_address, _) =
+        get_notifications_account_address_with_seed(program_id, owner_did_info.key);
+    if notifications_address != *notifications_info.key {
+        msg!("Error: notifications account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    // Check that the owner has not blocked the sender
+    check_not_blocked(
+        program_id,
+        owner_did_info.key,
+        owner_blocklist_info,
+        sender_did_info.key,
+    )?;
+
+    let mut notifications = load_notifications(program_id, notifications_info)?;
+
+    let notification = Notification::new(notification_type, *pubkey, *sender_did_info.key);
+
+    // Notifications about the sender (e.g. a direct channel) must refer to the sender DID
+    if notification.notification_type.refers_to_sender()
+        && notification.pubkey != *sender_did_info.key
+    {
+        msg!("Error: notification does not refer to the sender");
+        return Err(SolariumError::NotificationNotAuthorized.into());
+    }
+
+    // Check that the sender is allowed to send this notification to the owner.
+    // Contact requests can come from anyone the owner has not blocked.
+    if !notifications.is_allowed(sender_did_info.key)
+        && !notification.notification_type.is_unsolicited()
+    {
+        check_notification_sender_membership(
+            program_id,
+            &notification,
+            owner_did_info.key,
+            sender_did_info.key,
+            sender_cek_account_info,
+        )
+        .map_err(|_| {
+            msg!("Error: sender is not authorised to send this notification");
+            SolariumError::NotificationNotAuthorized
+        })?;
+    }
+
+    notifications.add(notification);
+
+    notifications
+        .serialize(&mut &mut notifications_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+/// Checks that the sender of a notification is a member of the channel it refers to
+fn check_notification_sender_membership(
+    program_id: &Pubkey,
+    notification: &Notification,
+    owner_did: &Pubkey,
+    sender_did: &Pubkey,
+    sender_cek_account_info: Option<&AccountInfo>,
+) -> ProgramResult {
+    match (
+        notification.channel(program_id, owner_did),
+        sender_cek_account_info,
+    ) {
+        (Some(channel), Some(cek_account_info)) => {
+            check_membership(program_id, sender_did, cek_account_info, &channel)
+        }
+        _ => Err(SolariumError::NotificationNotAuthorized.into()),
+    }
+}
+
+fn set_notifications_policy(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    open: bool,
+    allowlist: Vec<Pubkey>,
+) -> ProgramResult {
+    msg!("SolariumInstruction::SetNotificationsPolicy");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let notifications_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID
+    // and that the DID owns the notifications account
+    check_authority_of_notifications(program_id, authority_info, did_info, notifications_info)?;
+
+    if allowlist.len() > Notifications::MAX_ALLOWLIST_SIZE as usize {
+        msg!("Error: Too many DIDs in the notifications allowlist");
+        return Err(SolariumError::AllowlistFull.into());
+    }
+
+    let mut notifications = load_notifications(program_id, notifications_info)?;
+    notifications.open = open;
+    notifications.allowlist = allowlist;
+
+    notifications
+        .serialize(&mut &mut notifications_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn block(program_id: &Pubkey, accounts: &[AccountInfo], did: Pubkey) -> ProgramResult {
+    msg!("SolariumInstruction::Block");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let owner_did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let blocklist_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the authority is valid for the DID
+    check_authority_of_did(authority_info, owner_did_info)?;
+
+    let (blocklist_address, blocklist_bump_seed) =
+        get_blocklist_account_address_with_seed(program_id, owner_did_info.key);
+    if blocklist_address != *blocklist_info.key {
+        msg!(
+            "Error: Attempt to update a blocklist account with an address not derived from the DID"
+        );
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    // Create the blocklist account the first time the owner blocks someone
+    if blocklist_info.data_is_empty() {
+        let size = Blocklist::size_bytes();
+        let blocklist_account_signer_seeds: &[&[_]] = &[
+            &owner_did_info.key.to_bytes(),
+            BLOCKLIST_ACCOUNT_ADDRESS_SEED,
+            &[blocklist_bump_seed],
+        ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                funder_info.key,
+                blocklist_info.key,
+                1.max(rent.minimum_balance(size as usize)),
+                size,
+                program_id,
+            ),
+            &[
+                funder_info.clone(),
+                blocklist_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[blocklist_account_signer_seeds],
+        )?;
+    } else if blocklist_info.owner != program_id {
+        msg!("Error: Blocklist account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut blocklist =
+        program_borsh::try_from_slice_incomplete::<Blocklist>(*blocklist_info.data.borrow())?;
+    blocklist.block(did)?;
+
+    blocklist
+        .serialize(&mut &mut blocklist_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn unblock(program_id: &Pubkey, accounts: &[AccountInfo], did: Pubkey) -> ProgramResult {
+    msg!("SolariumInstruction::Unblock");
+    let account_info_iter = &mut accounts.iter();
+    let owner_did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let blocklist_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID
+    check_authority_of_did(authority_info, owner_did_info)?;
+
+    let (blocklist_address, _) =
+        get_blocklist_account_address_with_seed(program_id, owner_did_info.key);
+    if blocklist_address != *blocklist_info.key {
+        msg!(
+            "Error: Attempt to update a blocklist account with an address not derived from the DID"
+        );
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    if blocklist_info.data_is_empty() {
+        msg!("Blocklist account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    if blocklist_info.owner != program_id {
+        msg!("Error: Blocklist account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let mut blocklist =
+        program_borsh::try_from_slice_incomplete::<Blocklist>(*blocklist_info.data.borrow())?;
+    blocklist.unblock(&did);
+
+    blocklist
+        .serialize(&mut &mut blocklist_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+/// Checks that the owner DID has not blocked the sender DID.
+fn check_not_blocked(
+    program_id: &Pubkey,
+    owner_did: &Pubkey,
+    blocklist_info: &AccountInfo,
+    sender_did: &Pubkey,
+) -> ProgramResult {
+    if is_blocked(program_id, owner_did, blocklist_info, sender_did)? {
+        msg!("Error: sender has been blocked by the recipient");
+        return Err(SolariumError::Blocked.into());
+    }
+
+    Ok(())
+}
+
+/// Checks if the owner DID has blocked the sender DID.
+/// An unallocated blocklist account means the owner has not blocked anyone.
+fn is_blocked(
+    program_id: &Pubkey,
+    owner_did: &Pubkey,
+    blocklist_info: &AccountInfo,
+    sender_did: &Pubkey,
+) -> Result<bool, ProgramError> {
+    let (blocklist_address, _) = get_blocklist_account_address_with_seed(program_id, owner_did);
+    if blocklist_address != *blocklist_info.key {
+        msg!("Error: blocklist account address derivation mismatch");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    if blocklist_info.data_is_empty() {
+        return Ok(false);
+    }
+
+    if blocklist_info.owner != program_id {
+        msg!("Error: Blocklist account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let blocklist =
+        program_borsh::try_from_slice_incomplete::<Blocklist>(*blocklist_info.data.borrow())?;
+    Ok(blocklist.is_blocked(sender_did))
+}
+
+fn ack_notifications(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    ack: NotificationsAck,
+) -> ProgramResult {
+    msg!("SolariumInstruction::AckNotifications");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let notifications_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID
+    // and that the DID owns the notifications account
+    check_authority_of_notifications(program_id, authority_info, did_info, notifications_info)?;
+
+    let mut notifications = load_notifications(program_id, notifications_info)?;
+    match ack {
+        NotificationsAck::UpToIndex(index) => notifications.remove_up_to(index),
+        NotificationsAck::Pubkey(pubkey) => notifications.remove_by_pubkey(&pubkey),
+    }
+
+    notifications
+        .serialize(&mut &mut notifications_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn clear_notifications(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::ClearNotifications");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let notifications_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID
+    // and that the DID owns the notifications account
+    check_authority_of_notifications(program_id, authority_info, did_info, notifications_info)?;
+
+    let mut notifications = load_notifications(program_id, notifications_info)?;
+    notifications.clear();
+
+    notifications
+        .serialize(&mut &mut notifications_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn resize_notifications(program_id: &Pubkey, accounts: &[AccountInfo], size: u16) -> ProgramResult {
+    msg!("SolariumInstruction::ResizeNotifications");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let notifications_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the authority is valid for the DID
+    // and that the DID owns the notifications account
+    check_authority_of_notifications(program_id, authority_info, did_info, notifications_info)?;
+
+    // Accounts in the legacy layout are migrated by resizing them
+    let mut notifications = read_notifications(program_id, notifications_info)?;
+    notifications.resize(size)?;
+    notifications.check_allocation(notifications_info.data_len())?;
+
+    let notifications_size = notifications.size_bytes()?;
+    resize_account(
+        notifications_info,
+        notifications_size as usize,
+        funder_info,
+        system_program_info,
+        rent,
+    )?;
+
+    notifications
+        .serialize(&mut &mut notifications_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn close_notifications(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::CloseNotifications");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let notifications_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID
+    // and that the DID owns the notifications account
+    check_authority_of_notifications(program_id, authority_info, did_info, notifications_info)?;
+
+    if notifications_info.owner != program_id {
+        msg!("Error: Notifications account is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    // Refund the rent to the owner
+    close_account(notifications_info, authority_info)
+}
+
+fn claim_alias(program_id: &Pubkey, accounts: &[AccountInfo], alias: String) -> ProgramResult {
+    msg!("SolariumInstruction::ClaimAlias");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let alias_account_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the authority is valid for the DID
+    check_authority_of_did(authority_info, did_info)?;
+
+    let (alias_address, alias_bump_seed) = get_alias_account_address_with_seed(program_id, &alias);
+    if alias_address != *alias_account_info.key {
+        msg!("Error: Attempt to claim an alias with an address not derived from the alias");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    if !alias_account_info.data_is_empty() {
+        msg!("Error: Attempt to claim an alias that is already in use");
+        return Err(SolariumError::AlreadyInUse.into());
+    }
+
+    let alias_record = AliasRecord::new(*did_info.key, &alias);
+    validate_alias(&alias_record.alias)?;
+
+    let size = program_borsh::get_instance_packed_len(&alias_record)?;
+    let alias_hash = hash(alias_record.alias.as_bytes());
+    let alias_account_signer_seeds: &[&[_]] = &[
+        alias_hash.as_ref(),
+        ALIAS_ACCOUNT_ADDRESS_SEED,
+        &[alias_bump_seed],
+    ];
+
+    invoke_signed(
+        &system_instruction::create_account(
+            funder_info.key,
+            alias_account_info.key,
+            1.max(rent.minimum_balance(size)),
+            size as u64,
+            program_id,
+        ),
+        &[
+            funder_info.clone(),
+            alias_account_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[alias_account_signer_seeds],
+    )?;
+
+    alias_record
+        .serialize(&mut &mut alias_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn release_alias(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::ReleaseAlias");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
     let authority_info = next_account_info(account_info_iter)?;
-    let user_details_account_info = next_account_info(account_info_iter)?;
-    let mut user_details = program_borsh::try_from_slice_incomplete::<UserDetails>(
-        *user_details_account_info.data.borrow(),
-    )?;
+    let alias_account_info = next_account_info(account_info_iter)?;
+    let recipient_info = next_account_info(account_info_iter)?;
 
-    if !user_details.is_initialized() {
-        msg!("UserDetails account not initialized");
-        return Err(ProgramError::UninitializedAccount);
-    }
+    // Check that the authority is valid for the DID
+    // and that the DID owns the alias
+    check_authority_of_alias(program_id, authority_info, did_info, alias_account_info)?;
 
-    // Check that the signer is an authority on the DID.
-    validate_owner(did_info, &[authority_info]).unwrap();
+    close_account(alias_account_info, recipient_info)
+}
 
-    // check that the user details account belongs to the DID
-    let (user_details_address, _) =
-        get_userdetails_account_address_with_seed(program_id, did_info.key);
-    if user_details_address != *user_details_account_info.key {
-        msg!("Error: Attempt to update a userdetails account with an address not derived from the DID");
+fn transfer_alias(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_owner_did: Pubkey,
+) -> ProgramResult {
+    msg!("SolariumInstruction::TransferAlias");
+    let account_info_iter = &mut accounts.iter();
+    let did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let alias_account_info = next_account_info(account_info_iter)?;
+
+    // Check that the authority is valid for the DID
+    // and that the DID owns the alias
+    let mut alias_record =
+        check_authority_of_alias(program_id, authority_info, did_info, alias_account_info)?;
+
+    alias_record.owner_did = new_owner_did;
+
+    alias_record
+        .serialize(&mut &mut alias_account_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+fn add_contact_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    page: u16,
+    entry: ContactEntry,
+) -> ProgramResult {
+    msg!("SolariumInstruction::AddContactEntry");
+    let account_info_iter = &mut accounts.iter();
+    let funder_info = next_account_info(account_info_iter)?;
+    let owner_did_info = next_account_info(account_info_iter)?;
+    let authority_info = next_account_info(account_info_iter)?;
+    let address_book_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let rent = &Rent::from_account_info(rent_info)?;
+
+    // Check that the authority is valid for the DID
+    check_authority_of_did(authority_info, owner_did_info)?;
+
+    let (address_book_address, address_book_bump_seed) =
+        get_address_book_account_address_with_seed(program_id, owner_did_info.key, page);
+    if address_book_address != *address_book_info.key {
+        msg!("Error: Attempt to update an address book page with an address not derived from the DID and page");
         return Err(SolariumError::AddressDerivationMismatch.into());
     }
 
-    // mutate the UserDetails object
-    user_details.alias = alias;
-    user_details.address_book = address_book;
+    // Create the page the first time an entry is added to it
+    let mut address_book_page = if address_book_info.data_is_empty() {
+        let size = AddressBookPage::size_bytes();
+        let address_book_account_signer_seeds: &[&[_]] = &[
+            &owner_did_info.key.to_bytes(),
+            &page.to_le_bytes(),
+            ADDRESS_BOOK_ACCOUNT_ADDRESS_SEED,
+            &[address_book_bump_seed],
+        ];
+
+        invoke_signed(
+            &system_instruction::create_account(
+                funder_info.key,
+                address_book_info.key,
+                1.max(rent.minimum_balance(size as usize)),
+                size,
+                program_id,
+            ),
+            &[
+                funder_info.clone(),
+                address_book_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[address_book_account_signer_seeds],
+        )?;
+
+        AddressBookPage::new(*owner_did_info.key, page)
+    } else {
+        load_address_book_page(program_id, owner_did_info, address_book_info)?
+    };
+
+    address_book_page.add(entry)?;
 
-    user_details
-        .serialize(&mut *user_details_account_info.data.borrow_mut())
+    address_book_page
+        .serialize(&mut &mut address_book_info.data.borrow_mut()[..])
         .map_err(|e| e.into())
 }
 
-fn add_cek(program_id: &Pubkey, accounts: &[AccountInfo], cek: CEKData) -> ProgramResult {
-    msg!("SolariumInstruction::AddCEK");
+fn update_contact_entry(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    entry: ContactEntry,
+) -> ProgramResult {
+    msg!("SolariumInstruction::UpdateContactEntry");
     let account_info_iter = &mut accounts.iter();
-    let did_info = next_account_info(account_info_iter)?;
+    let owner_did_info = next_account_info(account_info_iter)?;
     let authority_info = next_account_info(account_info_iter)?;
-    let cek_account_info = next_account_info(account_info_iter)?;
+    let address_book_info = next_account_info(account_info_iter)?;
 
-    msg!("checking authority");
     // Check that the authority is valid for the DID
-    // and that the DID owns the CEK account
-    check_authority_of_cek(program_id, authority_info, did_info, cek_account_info).unwrap();
-
-    msg!("checking account");
-    let mut cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
-        *cek_account_info.data.borrow(),
-    )?;
+    check_authority_of_did(authority_info, owner_did_info)?;
 
-    msg!("adding");
-    cek_account.add(cek);
+    let mut address_book_page =
+        load_address_book_page(program_id, owner_did_info, address_book_info)?;
+    address_book_page.update(entry)?;
 
-    msg!("serializing");
-    cek_account
-        .serialize(&mut *cek_account_info.data.borrow_mut())
+    address_book_page
+        .serialize(&mut &mut address_book_info.data.borrow_mut()[..])
         .map_err(|e| e.into())
 }
 
-fn remove_cek(program_id: &Pubkey, accounts: &[AccountInfo], kid: String) -> ProgramResult {
-    msg!("SolariumInstruction::RemoveCEK");
+fn remove_contact_entry(program_id: &Pubkey, accounts: &[AccountInfo], id: u32) -> ProgramResult {
+    msg!("SolariumInstruction::RemoveContactEntry");
     let account_info_iter = &mut accounts.iter();
-    let did_info = next_account_info(account_info_iter)?;
+    let owner_did_info = next_account_info(account_info_iter)?;
     let authority_info = next_account_info(account_info_iter)?;
-    let cek_account_info = next_account_info(account_info_iter)?;
+    let address_book_info = next_account_info(account_info_iter)?;
 
     // Check that the authority is valid for the DID
-    // and that the DID owns the CEK account
-    check_authority_of_cek(program_id, authority_info, did_info, cek_account_info).unwrap();
+    check_authority_of_did(authority_info, owner_did_info)?;
 
-    let mut cek_account = program_borsh::try_from_slice_incomplete::<CEKAccountData>(
-        *cek_account_info.data.borrow(),
+    let mut address_book_page =
+        load_address_book_page(program_id, owner_did_info, address_book_info)?;
+    address_book_page.remove(id)?;
+
+    address_book_page
+        .serialize(&mut &mut address_book_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+/// Load an address book page, checking that it is owned by the DID
+/// and that its address is derived from the DID and page index
+fn load_address_book_page(
+    program_id: &Pubkey,
+    owner_did_info: &AccountInfo,
+    address_book_info: &AccountInfo,
+) -> Result<AddressBookPage, ProgramError> {
+    if address_book_info.owner != program_id {
+        msg!("Error: Address book page is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let address_book_page = program_borsh::try_from_slice_incomplete::<AddressBookPage>(
+        *address_book_info.data.borrow(),
     )?;
+    if !address_book_page.is_initialized() {
+        msg!("Address book page not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
 
-    cek_account.remove(kid).unwrap();
+    if address_book_page.owner_did != *owner_did_info.key {
+        msg!("Error: Address book page is not owned by the DID");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
 
-    cek_account
-        .serialize(&mut *cek_account_info.data.borrow_mut())
-        .map_err(|e| e.into())
+    let (address_book_address, _) = get_address_book_account_address_with_seed(
+        program_id,
+        owner_did_info.key,
+        address_book_page.page,
+    );
+    if address_book_address != *address_book_info.key {
+        msg!("Error: Address book page address is not derived from the DID and page");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    Ok(address_book_page)
 }
 
-fn create_user_details(
+fn create_prekey_bundle(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    alias: String,
-    address_book: String,
-    size: u32,
+    identity_key: [u8; 32],
+    signed_prekey: SignedPreKey,
+    one_time_prekeys: Vec<OneTimePreKey>,
 ) -> ProgramResult {
-    msg!("SolariumInstruction::CreateUserDetails");
+    msg!("SolariumInstruction::CreatePreKeyBundle");
     let account_info_iter = &mut accounts.iter();
     let funder_info = next_account_info(account_info_iter)?;
     let did_info = next_account_info(account_info_iter)?;
     let authority_info = next_account_info(account_info_iter)?;
-    let user_details_account_info = next_account_info(account_info_iter)?;
+    let prekey_bundle_info = next_account_info(account_info_iter)?;
     let rent_info = next_account_info(account_info_iter)?;
     let system_program_info = next_account_info(account_info_iter)?;
 
     let rent = &Rent::from_account_info(rent_info)?;
 
-    let data_len = user_details_account_info.data.borrow().len();
-    if data_len > 0 {
-        msg!(
-            "Error: Attempt to create a userdetails account for an address that is already in use"
-        );
-        return Err(SolariumError::AlreadyInUse.into());
-    }
-
     // Check that the authority is valid for the DID
-    check_authority_of_did(authority_info, did_info).unwrap();
+    check_authority_of_did(authority_info, did_info)?;
 
-    let (user_details_address, user_details_bump_seed) =
-        get_userdetails_account_address_with_seed(program_id, did_info.key);
-    if user_details_address != *user_details_account_info.key {
-        msg!("Error: Attempt to create a userdetails account with an address not derived from the DID");
+    let (prekey_bundle_address, prekey_bundle_bump_seed) =
+        get_prekey_bundle_account_address_with_seed(program_id, did_info.key);
+    if prekey_bundle_address != *prekey_bundle_info.key {
+        msg!("Error: Attempt to create a prekey bundle account with an address not derived from the DID");
         return Err(SolariumError::AddressDerivationMismatch.into());
     }
 
-    // Create the new userdetails account for the invitee
-    let new_user_details = UserDetails {
-        alias,
-        address_book,
+    if !prekey_bundle_info.data_is_empty() {
+        msg!("Error: Attempt to create a prekey bundle account for an address that is already in use");
+        return Err(SolariumError::AlreadyInUse.into());
+    }
+
+    let mut prekey_bundle = PreKeyBundle {
+        owner_did: *did_info.key,
+        identity_key,
+        signed_prekey,
+        one_time_prekeys: vec![],
     };
+    prekey_bundle.refill(one_time_prekeys)?;
 
-    let user_details_account_signer_seeds: &[&[_]] = &[
+    let size = PreKeyBundle::size_bytes();
+    let prekey_bundle_account_signer_seeds: &[&[_]] = &[
         &did_info.key.to_bytes(),
-        USERDETAILS_ACCOUNT_ADDRESS_SEED,
-        &[user_details_bump_seed],
+        PREKEY_BUNDLE_ACCOUNT_ADDRESS_SEED,
+        &[prekey_bundle_bump_seed],
     ];
 
     invoke_signed(
         &system_instruction::create_account(
             funder_info.key,
-            user_details_account_info.key,
+            prekey_bundle_info.key,
             1.max(rent.minimum_balance(size as usize)),
-            size as u64,
+            size,
             program_id,
         ),
         &[
             funder_info.clone(),
-            user_details_account_info.clone(),
+            prekey_bundle_info.clone(),
             system_program_info.clone(),
         ],
-        &[&user_details_account_signer_seeds],
+        &[prekey_bundle_account_signer_seeds],
     )?;
 
-    new_user_details
-        .serialize(&mut *user_details_account_info.data.borrow_mut())
+    prekey_bundle
+        .serialize(&mut &mut prekey_bundle_info.data.borrow_mut()[..])
         .map_err(|e| e.into())
 }
 
-fn create_notifications(program_id: &Pubkey, accounts: &[AccountInfo], size: u8) -> ProgramResult {
-    msg!("SolariumInstruction::CreateNotifications");
+fn consume_one_time_prekey(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    msg!("SolariumInstruction::ConsumeOneTimePreKey");
+    let account_info_iter = &mut accounts.iter();
+    let prekey_bundle_info = next_account_info(account_info_iter)?;
+    let requester_did_info = next_account_info(account_info_iter)?;
+    let requester_authority_info = next_account_info(account_info_iter)?;
+
+    // Only DIDs can request prekeys, so that draining a pool is attributable
+    check_authority_of_did(requester_authority_info, requester_did_info)?;
+
+    let mut prekey_bundle = load_prekey_bundle(program_id, prekey_bundle_info)?;
+    let one_time_prekey = prekey_bundle.consume_one_time_prekey()?;
+
+    prekey_bundle.serialize(&mut &mut prekey_bundle_info.data.borrow_mut()[..])?;
+
+    set_return_data(&one_time_prekey.try_to_vec()?);
+    Ok(())
+}
+
+fn refill_prekeys(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    signed_prekey: Option<SignedPreKey>,
+    one_time_prekeys: Vec<OneTimePreKey>,
+) -> ProgramResult {
+    msg!("SolariumInstruction::RefillPreKeys");
     let account_info_iter = &mut accounts.iter();
-    let funder_info = next_account_info(account_info_iter)?;
     let did_info = next_account_info(account_info_iter)?;
     let authority_info = next_account_info(account_info_iter)?;
-    let notifications_account_info = next_account_info(account_info_iter)?;
-    let rent_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
+    let prekey_bundle_info = next_account_info(account_info_iter)?;
 
-    let rent = &Rent::from_account_info(rent_info)?;
+    // Check that the authority is valid for the DID
+    check_authority_of_did(authority_info, did_info)?;
 
-    let data_len = notifications_account_info.data.borrow().len();
-    if data_len > 0 {
-        msg!("Error: Attempt to create a notifications account for an address that is already in use");
-        return Err(SolariumError::AlreadyInUse.into());
+    let mut prekey_bundle = load_prekey_bundle(program_id, prekey_bundle_info)?;
+    if prekey_bundle.owner_did != *did_info.key {
+        msg!("Error: Prekey bundle is not owned by the DID");
+        return Err(SolariumError::IncorrectAuthority.into());
     }
 
-    // Check that the authority is valid for the DID
-    check_authority_of_did(authority_info, did_info).unwrap();
+    if let Some(signed_prekey) = signed_prekey {
+        prekey_bundle.signed_prekey = signed_prekey;
+    }
+    prekey_bundle.refill(one_time_prekeys)?;
 
-    let (notifications_address, notifications_bump_seed) =
-        get_notifications_account_address_with_seed(program_id, did_info.key);
-    if notifications_address != *notifications_account_info.key {
-        msg!("Error: Attempt to create a notifications account with an address not derived from the DID");
+    prekey_bundle
+        .serialize(&mut &mut prekey_bundle_info.data.borrow_mut()[..])
+        .map_err(|e| e.into())
+}
+
+/// Load a prekey bundle, checking that it is an initialized Solarium account
+/// at the address derived from its owner DID
+fn load_prekey_bundle(
+    program_id: &Pubkey,
+    prekey_bundle_info: &AccountInfo,
+) -> Result<PreKeyBundle, ProgramError> {
+    if prekey_bundle_info.owner != program_id {
+        msg!("Error: Prekey bundle is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let prekey_bundle = program_borsh::try_from_slice_incomplete::<PreKeyBundle>(
+        *prekey_bundle_info.data.borrow(),
+    )?;
+    if !prekey_bundle.is_initialized() {
+        msg!("Prekey bundle not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    let (prekey_bundle_address, _) =
+        get_prekey_bundle_account_address_with_seed(program_id, &prekey_bundle.owner_did);
+    if prekey_bundle_address != *prekey_bundle_info.key {
+        msg!("Error: Prekey bundle address is not derived from its owner DID");
         return Err(SolariumError::AddressDerivationMismatch.into());
     }
 
-    // Create the new notifications account
-    let new_notifications = Notifications::new(size);
-    let notifications_size = new_notifications.size_bytes();
+    Ok(prekey_bundle)
+}
 
-    let notifications_account_signer_seeds: &[&[_]] = &[
-        &did_info.key.to_bytes(),
-        NOTIFICATIONS_ACCOUNT_ADDRESS_SEED,
-        &[notifications_bump_seed],
-    ];
+/// Checks that the authority_info account is an authority for the DID,
+/// And that the alias record is owned by that DID.
+/// Returns the alias record.
+fn check_authority_of_alias(
+    program_id: &Pubkey,
+    authority_info: &AccountInfo,
+    did: &AccountInfo,
+    alias_account_info: &AccountInfo,
+) -> Result<AliasRecord, ProgramError> {
+    check_authority_of_did(authority_info, did)?;
 
-    invoke_signed(
-        &system_instruction::create_account(
-            funder_info.key,
-            notifications_account_info.key,
-            1.max(rent.minimum_balance(notifications_size as usize)),
-            notifications_size as u64,
-            program_id,
-        ),
-        &[
-            funder_info.clone(),
-            notifications_account_info.clone(),
-            system_program_info.clone(),
-        ],
-        &[&notifications_account_signer_seeds],
-    )?;
+    if alias_account_info.owner != program_id {
+        msg!("Error: alias record is not a Solarium program account");
+        return Err(ProgramError::IncorrectProgramId);
+    }
 
-    new_notifications
-        .serialize(&mut *notifications_account_info.data.borrow_mut())
-        .map_err(|e| e.into())
+    let alias_record =
+        program_borsh::try_from_slice_incomplete::<AliasRecord>(*alias_account_info.data.borrow())?;
+    if !alias_record.is_initialized() {
+        msg!("Alias record not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+
+    if alias_record.owner_did != *did.key {
+        msg!("Error: alias is not owned by the DID");
+        return Err(SolariumError::IncorrectAuthority.into());
+    }
+
+    Ok(alias_record)
 }
 
-fn add_notification(
+/// Reallocates a program account to the new size,
+/// topping up its rent from the funder, or refunding the excess rent to the funder
+fn resize_account<'a>(
+    account_info: &AccountInfo<'a>,
+    size: usize,
+    funder_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    rent: &Rent,
+) -> ProgramResult {
+    let required_lamports = 1.max(rent.minimum_balance(size));
+    let current_lamports = account_info.lamports();
+
+    if required_lamports > current_lamports {
+        invoke(
+            &system_instruction::transfer(
+                funder_info.key,
+                account_info.key,
+                required_lamports - current_lamports,
+            ),
+            &[
+                funder_info.clone(),
+                account_info.clone(),
+                system_program_info.clone(),
+            ],
+        )?;
+    } else {
+        let excess_lamports = current_lamports - required_lamports;
+        let funder_lamports = funder_info
+            .lamports()
+            .checked_add(excess_lamports)
+            .ok_or(SolariumError::Overflow)?;
+        **account_info.try_borrow_mut_lamports()? = required_lamports;
+        **funder_info.try_borrow_mut_lamports()? = funder_lamports;
+    }
+
+    account_info.realloc(size, false)
+}
+
+/// Closes a program account, transferring all of its lamports to the recipient
+fn close_account(account_info: &AccountInfo, recipient_info: &AccountInfo) -> ProgramResult {
+    let recipient_lamports = recipient_info
+        .lamports()
+        .checked_add(account_info.lamports())
+        .ok_or(SolariumError::Overflow)?;
+    **recipient_info.try_borrow_mut_lamports()? = recipient_lamports;
+    **account_info.try_borrow_mut_lamports()? = 0;
+
+    account_info.realloc(0, false)
+}
+
+/// Checks that the authority_info account is an authority for the DID,
+/// And that the notifications account is derived from that DID
+fn check_authority_of_notifications(
     program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    notification_type: NotificationType,
-    pubkey: &Pubkey,
+    authority_info: &AccountInfo,
+    did: &AccountInfo,
+    notifications_info: &AccountInfo,
 ) -> ProgramResult {
-    msg!("SolariumInstruction::AddNotification");
-    let account_info_iter = &mut accounts.iter();
-    let notifications_info = next_account_info(account_info_iter)?;
-    let sender_did_info = next_account_info(account_info_iter)?;
-    let sender_authority_info = next_account_info(account_info_iter)?;
+    check_authority_of_did(authority_info, did)?;
+
+    let (notifications_address, _) =
+        get_notifications_account_address_with_seed(program_id, did.key);
+    if notifications_address != *notifications_info.key {
+        msg!("Error: Attempt to update a notifications account with an address not derived from the DID");
+        return Err(SolariumError::AddressDerivationMismatch.into());
+    }
+
+    Ok(())
+}
+
+/// Loads an existing notifications account, checking that it is initialized and owned by this program,
+/// and that it is large enough to hold its full buffer
+fn load_notifications(
+    program_id: &Pubkey,
+    notifications_info: &AccountInfo,
+) -> Result<Notifications, ProgramError> {
+    let notifications = read_notifications(program_id, notifications_info)?;
+    if notifications_info.data_len() < notifications.size_bytes()? as usize {
+        msg!("Error: Notifications account must be resized to the current layout");
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+
+    Ok(notifications)
+}
 
-    let mut notifications = program_borsh::try_from_slice_incomplete::<Notifications>(
+/// Reads an existing notifications account, checking that it is initialized and owned by this program,
+/// including accounts that are still in the legacy layout
+fn read_notifications(
+    program_id: &Pubkey,
+    notifications_info: &AccountInfo,
+) -> Result<Notifications, ProgramError> {
+    let notifications = program_borsh::try_from_slice_incomplete::<Notifications>(
         *notifications_info.data.borrow(),
     )?;
     if !notifications.is_initialized() {
@@ -632,18 +3386,21 @@ fn add_notification(
         return Err(ProgramError::IncorrectProgramId);
     }
 
-    // Check that the sender of the message is valid
-    // the sender signer is an authority on the DID.
-    validate_owner(sender_did_info, &[sender_authority_info]).unwrap();
+    Ok(notifications)
+}
+
+/// Adds a notification to an existing notifications account
+fn push_notification(
+    program_id: &Pubkey,
+    notifications_info: &AccountInfo,
+    notification: Notification,
+) -> ProgramResult {
+    let mut notifications = load_notifications(program_id, notifications_info)?;
 
-    let notification = Notification {
-        notification_type,
-        pubkey: *pubkey,
-    };
     notifications.add(notification);
 
     notifications
-        .serialize(&mut *notifications_info.data.borrow_mut())
+        .serialize(&mut &mut notifications_info.data.borrow_mut()[..])
         .map_err(|e| e.into())
 }
 
@@ -663,9 +3420,11 @@ pub fn process_instruction(
             creator_ceks,
             invitee_ceks,
         } => initialize_direct_channel(program_id, accounts, creator_ceks, invitee_ceks),
-        SolariumInstruction::Post { message } => post(program_id, accounts, message),
-        SolariumInstruction::AddToChannel { ceks } => add_to_channel(program_id, accounts, ceks),
-        SolariumInstruction::AddCEK { cek } => add_cek(program_id, accounts, cek),
+        SolariumInstruction::Post { message, epoch } => post(program_id, accounts, message, epoch),
+        SolariumInstruction::AddToChannel { ceks, members_page } => {
+            add_to_channel(program_id, accounts, ceks, members_page)
+        }
+        SolariumInstruction::AddCEK { cek } => add_cek(program_id, accounts, cek, false),
         SolariumInstruction::RemoveCEK { kid } => remove_cek(program_id, accounts, kid),
         SolariumInstruction::CreateUserDetails {
             alias,
@@ -675,7 +3434,16 @@ pub fn process_instruction(
         SolariumInstruction::UpdateUserDetails {
             alias,
             address_book,
-        } => update_user_details(program_id, accounts, alias, address_book),
+            status,
+            avatar_uri,
+        } => update_user_details(
+            program_id,
+            accounts,
+            alias,
+            address_book,
+            status,
+            avatar_uri,
+        ),
         SolariumInstruction::CreateNotifications { size } => {
             create_notifications(program_id, accounts, size)
         }
@@ -683,5 +3451,105 @@ pub fn process_instruction(
             notification_type,
             pubkey,
         } => add_notification(program_id, accounts, notification_type, &pubkey),
+        SolariumInstruction::PostWithMentions {
+            message,
+            mentions,
+            epoch,
+        } => post_with_mentions(program_id, accounts, message, mentions, epoch),
+        SolariumInstruction::SetNotificationsPolicy { open, allowlist } => {
+            set_notifications_policy(program_id, accounts, open, allowlist)
+        }
+        SolariumInstruction::Block { did } => block(program_id, accounts, did),
+        SolariumInstruction::Unblock { did } => unblock(program_id, accounts, did),
+        SolariumInstruction::AckNotifications { ack } => {
+            ack_notifications(program_id, accounts, ack)
+        }
+        SolariumInstruction::ClearNotifications => clear_notifications(program_id, accounts),
+        SolariumInstruction::ResizeNotifications { size } => {
+            resize_notifications(program_id, accounts, size)
+        }
+        SolariumInstruction::CloseNotifications => close_notifications(program_id, accounts),
+        SolariumInstruction::ResizeUserDetails { size } => {
+            resize_user_details(program_id, accounts, size)
+        }
+        SolariumInstruction::CloseUserDetails => close_user_details(program_id, accounts),
+        SolariumInstruction::ClaimAlias { alias } => claim_alias(program_id, accounts, alias),
+        SolariumInstruction::ReleaseAlias => release_alias(program_id, accounts),
+        SolariumInstruction::TransferAlias { new_owner_did } => {
+            transfer_alias(program_id, accounts, new_owner_did)
+        }
+        SolariumInstruction::AddContactEntry { page, entry } => {
+            add_contact_entry(program_id, accounts, page, entry)
+        }
+        SolariumInstruction::UpdateContactEntry { entry } => {
+            update_contact_entry(program_id, accounts, entry)
+        }
+        SolariumInstruction::RemoveContactEntry { id } => {
+            remove_contact_entry(program_id, accounts, id)
+        }
+        SolariumInstruction::CreatePreKeyBundle {
+            identity_key,
+            signed_prekey,
+            one_time_prekeys,
+        } => create_prekey_bundle(
+            program_id,
+            accounts,
+            identity_key,
+            signed_prekey,
+            one_time_prekeys,
+        ),
+        SolariumInstruction::ConsumeOneTimePreKey => consume_one_time_prekey(program_id, accounts),
+        SolariumInstruction::RefillPreKeys {
+            signed_prekey,
+            one_time_prekeys,
+        } => refill_prekeys(program_id, accounts, signed_prekey, one_time_prekeys),
+        SolariumInstruction::RotateChannelKey { epoch, member_ceks } => {
+            rotate_channel_key(program_id, accounts, epoch, member_ceks)
+        }
+        SolariumInstruction::PruneStaleCEKs => prune_stale_ceks(program_id, accounts),
+        SolariumInstruction::ReplaceCEK { kid, cek } => replace_cek(program_id, accounts, kid, cek),
+        SolariumInstruction::AddCEKs { ceks } => add_ceks(program_id, accounts, ceks),
+        SolariumInstruction::ProvisionMemberCEK { cek } => {
+            provision_member_cek(program_id, accounts, cek)
+        }
+        SolariumInstruction::InitializePrivateChannel { name, ceks } => {
+            initialize_private_channel(program_id, accounts, name, ceks)
+        }
+        SolariumInstruction::AddToPrivateChannel {
+            invitee_membership_seed,
+            ceks,
+        } => add_to_private_channel(program_id, accounts, invitee_membership_seed, ceks),
+        SolariumInstruction::PostPrivate { message, epoch } => {
+            post_private(program_id, accounts, message, epoch)
+        }
+        SolariumInstruction::LeaveChannel { members_page } => {
+            leave_channel(program_id, accounts, members_page)
+        }
+        SolariumInstruction::InviteToChannel { ceks } => {
+            invite_to_channel(program_id, accounts, ceks)
+        }
+        SolariumInstruction::AcceptInvite { members_page } => {
+            accept_invite(program_id, accounts, members_page)
+        }
+        SolariumInstruction::DeclineInvite => decline_invite(program_id, accounts),
+        SolariumInstruction::RevokeInvite => revoke_invite(program_id, accounts),
+        SolariumInstruction::CreateInviteCode {
+            invite_pubkey,
+            expiry,
+            max_uses,
+        } => create_invite_code(program_id, accounts, invite_pubkey, expiry, max_uses),
+        SolariumInstruction::RedeemInvite { members_page } => {
+            redeem_invite(program_id, accounts, members_page)
+        }
+        SolariumInstruction::CloseInviteCode => close_invite_code(program_id, accounts),
+        SolariumInstruction::AddCEKAndPrune { cek } => add_cek(program_id, accounts, cek, true),
+        SolariumInstruction::RemoveFromChannel { members_page } => {
+            remove_from_channel(program_id, accounts, members_page)
+        }
+        SolariumInstruction::LeavePrivateChannel => leave_private_channel(program_id, accounts),
+        SolariumInstruction::AddPrivateCEK { cek } => add_private_cek(program_id, accounts, cek),
+        SolariumInstruction::RotatePrivateChannelKey { epoch, member_ceks } => {
+            rotate_private_channel_key(program_id, accounts, epoch, member_ceks)
+        }
     }
 }
@@ -1,14 +1,21 @@
 //! Program state
+use crate::borsh::{get_instance_packed_len, LegacyLayout, VersionedString, VersionedVec};
 use crate::error::SolariumError;
-use crate::state::NotificationType::GroupChannel;
 use {
-    borsh::{BorshDeserialize, BorshSchema, BorshSerialize},
+    borsh::{
+        schema::{Declaration, Definition},
+        BorshDeserialize, BorshSchema, BorshSerialize,
+    },
     solana_program::{
+        entrypoint::MAX_PERMITTED_DATA_INCREASE,
+        hash::{hash, hashv},
+        program_error::ProgramError,
         program_pack::IsInitialized,
         pubkey::Pubkey,
         sysvar::{clock::Clock, Sysvar},
     },
-    std::collections::VecDeque,
+    std::collections::{HashMap, VecDeque},
+    unicode_normalization::UnicodeNormalization,
 };
 
 fn push_into_deque<T>(vec: Vec<T>, entry: T, size: usize) -> Vec<T> {
@@ -22,13 +29,27 @@ fn push_into_deque<T>(vec: Vec<T>, entry: T, size: usize) -> Vec<T> {
     deque.into()
 }
 
-/// Structure of a channel
-#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+/// Structure of a channel.
+/// Fields appended to the channel must also be read in its BorshDeserialize impl.
+#[derive(Clone, Debug, Default, BorshSerialize, BorshSchema, PartialEq)]
 pub struct ChannelData {
     /// The channel nae
     pub name: String,
     /// All of the messages in the channel
-    pub messages: Vec<Message>,
+    pub messages: VersionedVec<Message>,
+    /// The current CEK epoch, incremented each time the channel key is rotated.
+    /// Messages and CEKs from earlier epochs are stale.
+    pub epoch: u32,
+    /// Whether membership of the channel is private. The CEK accounts of a private channel
+    /// are derived from blinded membership seeds rather than from the member DIDs.
+    pub private: bool,
+    /// The number of members of the channel. Group channels with public membership
+    /// list their members in ChannelMembersPage accounts.
+    pub member_count: u32,
+    /// The number of ChannelMembersPage accounts of the channel
+    pub members_page_count: u16,
+    /// The number of members on the last ChannelMembersPage of the channel
+    pub last_members_page_len: u8,
 }
 impl ChannelData {
     /// Default message count
@@ -41,7 +62,20 @@ impl ChannelData {
     pub fn new(name: String) -> Self {
         Self {
             name,
-            messages: Vec::with_capacity(usize::from(ChannelData::DEFAULT_SIZE)),
+            messages: VersionedVec::with_capacity(usize::from(ChannelData::DEFAULT_SIZE)),
+            epoch: 0,
+            private: false,
+            member_count: 0,
+            members_page_count: 0,
+            last_members_page_len: 0,
+        }
+    }
+
+    /// Create a new channel with private membership
+    pub fn new_private(name: String) -> Self {
+        Self {
+            private: true,
+            ..Self::new(name)
         }
     }
 
@@ -50,10 +84,58 @@ impl ChannelData {
         let clock = Clock::get().unwrap();
         message.timestamp = clock.unix_timestamp;
         self.messages = push_into_deque(
-            self.messages.clone(),
+            self.messages.to_vec(),
             message,
             ChannelData::DEFAULT_SIZE as usize,
-        );
+        )
+        .into();
+    }
+
+    /// The page of the channel members list that the next member must be added to:
+    /// the last page, or a new page following it once the last page is full.
+    /// Only appending to the end of the list keeps the pages contiguous.
+    pub fn next_members_page(&self) -> u16 {
+        match self.members_page_count.checked_sub(1) {
+            Some(last_page) if self.last_members_page_len < ChannelMembersPage::MAX_MEMBERS => {
+                last_page
+            }
+            _ => self.members_page_count,
+        }
+    }
+
+    /// Count a member added to the given page of the channel members list
+    pub fn add_member(&mut self, page: u16) -> Result<(), SolariumError> {
+        if page != self.next_members_page() {
+            return Err(SolariumError::InvalidChannelMembersPage);
+        }
+        if page == self.members_page_count {
+            self.members_page_count = self
+                .members_page_count
+                .checked_add(1)
+                .ok_or(SolariumError::Overflow)?;
+            self.last_members_page_len = 0;
+        }
+        self.last_members_page_len += 1;
+        self.member_count = self
+            .member_count
+            .checked_add(1)
+            .ok_or(SolariumError::Overflow)?;
+        Ok(())
+    }
+
+    /// Count a member removed from the given page of the channel members list
+    pub fn remove_member(&mut self, page: u16) -> Result<(), SolariumError> {
+        if self.members_page_count.checked_sub(1) == Some(page) {
+            self.last_members_page_len = self
+                .last_members_page_len
+                .checked_sub(1)
+                .ok_or(SolariumError::Overflow)?;
+        }
+        self.member_count = self
+            .member_count
+            .checked_sub(1)
+            .ok_or(SolariumError::Overflow)?;
+        Ok(())
     }
 
     /// The maximum size of a channel in bytes
@@ -62,6 +144,32 @@ impl ChannelData {
         // TODO max title size
     }
 }
+impl BorshDeserialize for ChannelData {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let name = String::deserialize(buf)?;
+        let messages = VersionedVec::<Message>::deserialize(buf)?;
+
+        // A channel whose messages are in the legacy layout was last written before the
+        // fields following the messages were added, and may be followed by stale bytes
+        if messages.is_legacy() {
+            return Ok(Self {
+                name,
+                messages,
+                ..Self::default()
+            });
+        }
+
+        Ok(Self {
+            name,
+            messages,
+            epoch: u32::deserialize(buf)?,
+            private: bool::deserialize(buf)?,
+            member_count: u32::deserialize(buf)?,
+            members_page_count: u16::deserialize(buf)?,
+            last_members_page_len: u8::deserialize(buf)?,
+        })
+    }
+}
 impl IsInitialized for ChannelData {
     /// Checks if a channel has been initialized
     fn is_initialized(&self) -> bool {
@@ -69,16 +177,166 @@ impl IsInitialized for ChannelData {
     }
 }
 
-/// A Content Encryption Key for a channel encrypted with a key on the DID of the owner
+/// The algorithm used to wrap a CEK
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub enum JweAlgorithm {
+    /// ECDH-ES key agreement with XChaCha20-Poly1305 key wrapping
+    EcdhEsXc20pkw,
+}
+
+/// The JWE header of a wrapped CEK
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct JweHeader {
+    /// The key wrapping algorithm
+    pub alg: JweAlgorithm,
+    /// The ephemeral X25519 public key used for the key agreement
+    pub epk: [u8; 32],
+    /// The XChaCha20 nonce used to wrap the CEK
+    pub nonce: [u8; 24],
+    /// The Poly1305 authentication tag of the wrapped CEK
+    pub tag: [u8; 16],
+}
+
+/// The header of a CEK.
+///
+/// Headers are stored as length-prefixed bytes, the same layout as a Borsh string,
+/// so that CEKs written with free-form string headers remain readable.
+/// A typed header is marked by a leading byte that cannot start a UTF-8 string.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CEKHeader {
+    /// A free-form string header, as written before headers were typed
+    Legacy(String),
+    /// A typed JWE header
+    Jwe(JweHeader),
+}
+impl CEKHeader {
+    /// The leading byte of an encoded typed header
+    pub const JWE_MARKER: u8 = 0xff;
+
+    /// The length of an encoded typed header in bytes
+    pub const JWE_LEN: usize = 1 + 1 + 32 + 24 + 16;
+
+    /// Encode the header as bytes
+    pub fn to_bytes(&self) -> std::io::Result<Vec<u8>> {
+        match self {
+            CEKHeader::Legacy(header) => Ok(header.as_bytes().to_vec()),
+            CEKHeader::Jwe(header) => {
+                let mut bytes = vec![CEKHeader::JWE_MARKER];
+                header.serialize(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Decode a header from bytes
+    pub fn from_bytes(bytes: Vec<u8>) -> std::io::Result<Self> {
+        match bytes.split_first() {
+            Some((&CEKHeader::JWE_MARKER, header)) => {
+                JweHeader::try_from_slice(header).map(CEKHeader::Jwe)
+            }
+            _ => String::from_utf8(bytes)
+                .map(CEKHeader::Legacy)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    /// The length of the encoded header in bytes
+    pub fn len(&self) -> usize {
+        match self {
+            CEKHeader::Legacy(header) => header.len(),
+            CEKHeader::Jwe(_) => CEKHeader::JWE_LEN,
+        }
+    }
+
+    /// Checks if the header is an empty legacy header
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks that the header is a typed header with an ephemeral public key
+    pub fn validate(&self) -> Result<(), SolariumError> {
+        match self {
+            CEKHeader::Jwe(header) if header.epk != [0; 32] => Ok(()),
+            _ => Err(SolariumError::InvalidCEKHeader),
+        }
+    }
+}
+impl Default for CEKHeader {
+    fn default() -> Self {
+        CEKHeader::Legacy(String::new())
+    }
+}
+impl BorshSerialize for CEKHeader {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self.to_bytes()?.serialize(writer)
+    }
+}
+impl BorshDeserialize for CEKHeader {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        CEKHeader::from_bytes(Vec::<u8>::deserialize(buf)?)
+    }
+}
+impl BorshSchema for CEKHeader {
+    fn add_definitions_recursively(definitions: &mut HashMap<Declaration, Definition>) {
+        Vec::<u8>::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> Declaration {
+        Vec::<u8>::declaration()
+    }
+}
+
+/// A Content Encryption Key for a channel encrypted with a key on the DID of the owner.
+/// CEKs written before channel epochs were introduced are read as belonging to epoch 0.
 #[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
 pub struct CEKData {
-    // TODO agree with Martin
     /// The header information for the CEK
-    pub header: String,
+    pub header: CEKHeader,
     /// The identifier on the owner DID of the key that this CEK is encrypted with
     pub kid: String,
     /// The CEK itself, encrypted by the DID key
     pub encrypted_key: String,
+    /// The channel epoch that this CEK belongs to
+    pub epoch: u32,
+}
+impl CEKData {
+    /// The maximum size of the header in bytes.
+    /// Only typed headers can be written, so this is the length of a typed header.
+    pub const MAX_HEADER_SIZE: u16 = CEKHeader::JWE_LEN as u16;
+
+    /// The maximum size of the key ID in bytes
+    pub const MAX_KID_SIZE: u16 = 128;
+
+    /// The maximum size of the encrypted key in bytes
+    pub const MAX_ENCRYPTED_KEY_SIZE: u16 = 128;
+
+    /// Checks that the CEK has a valid typed header and that each field is within its maximum size.
+    /// CEKs with legacy headers can be read but not written.
+    pub fn validate(&self) -> Result<(), SolariumError> {
+        self.header.validate()?;
+        self.check_size()
+    }
+
+    /// Checks that each field of the CEK is within its maximum size
+    pub fn check_size(&self) -> Result<(), SolariumError> {
+        if self.header.len() > CEKData::MAX_HEADER_SIZE as usize
+            || self.kid.len() > CEKData::MAX_KID_SIZE as usize
+            || self.encrypted_key.len() > CEKData::MAX_ENCRYPTED_KEY_SIZE as usize
+        {
+            return Err(SolariumError::CEKTooLarge);
+        }
+        Ok(())
+    }
+}
+impl LegacyLayout for CEKData {
+    fn deserialize_legacy(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self {
+            header: CEKHeader::deserialize(buf)?,
+            kid: String::deserialize(buf)?,
+            encrypted_key: String::deserialize(buf)?,
+            epoch: 0,
+        })
+    }
 }
 
 /// Defines a CEK account structure.
@@ -91,7 +349,7 @@ pub struct CEKAccountData {
     /// The channel that these CEKs decrypt
     pub channel: Pubkey,
     /// The CEKs for the channel, one per key in the owner DID
-    pub ceks: Vec<CEKData>,
+    pub ceks: VersionedVec<CEKData>,
 }
 impl CEKAccountData {
     /// The maximum number of CEKs that can be added to an individual CEK account
@@ -102,18 +360,58 @@ impl CEKAccountData {
         Self {
             owner_did,
             channel,
-            ceks: Vec::with_capacity(usize::from(CEKAccountData::MAX_CEKS)),
+            ceks: VersionedVec::with_capacity(usize::from(CEKAccountData::MAX_CEKS)),
         }
     }
 
     /// Add a number of CEKs to the account at the same time
-    pub fn add_all(&mut self, ceks: Vec<CEKData>) {
-        ceks.iter().for_each(|cek| self.ceks.push(cek.clone()))
+    pub fn add_all(&mut self, ceks: Vec<CEKData>) -> Result<(), SolariumError> {
+        ceks.into_iter().try_for_each(|cek| self.add(cek))
     }
 
     /// add a new CEK to the account
-    pub fn add(&mut self, cek: CEKData) {
+    pub fn add(&mut self, cek: CEKData) -> Result<(), SolariumError> {
+        cek.validate()?;
+        if self.ceks.len() >= CEKAccountData::MAX_CEKS as usize {
+            return Err(SolariumError::TooManyCEKs);
+        }
         self.ceks.push(cek);
+        Ok(())
+    }
+
+    /// Remove the CEKs whose key ID fragment is not in `key_ids`,
+    /// returning the number of CEKs removed
+    pub fn prune(&mut self, key_ids: &[String]) -> usize {
+        let count = self.ceks.len();
+        self.ceks.retain(|cek| {
+            key_ids
+                .iter()
+                .any(|key_id| key_id == key_id_fragment(&cek.kid))
+        });
+        count - self.ceks.len()
+    }
+
+    /// Replace the CEK with the given key ID
+    pub fn replace(&mut self, kid: &str, cek: CEKData) -> Result<(), SolariumError> {
+        cek.validate()?;
+        let index = self
+            .ceks
+            .iter()
+            .position(|existing| existing.kid == kid)
+            .ok_or(SolariumError::CEKNotFound)?;
+        self.ceks[index] = cek;
+        Ok(())
+    }
+
+    /// Replace all CEKs in the account
+    pub fn replace_all(&mut self, ceks: Vec<CEKData>) -> Result<(), SolariumError> {
+        self.ceks.clear();
+        self.add_all(ceks)
+    }
+
+    /// Checks if the account holds a CEK for the given channel epoch
+    pub fn has_epoch(&self, epoch: u32) -> bool {
+        self.ceks.iter().any(|cek| cek.epoch == epoch)
     }
 
     /// remove a CEK from the account by key ID
@@ -129,6 +427,35 @@ impl CEKAccountData {
         }
     }
 }
+impl CEKAccountData {
+    /// Get the allocated size of a CEK account in bytes,
+    /// i.e. the size of the account when it holds MAX_CEKS CEKs of the maximum size
+    pub fn size_bytes() -> Result<u64, ProgramError> {
+        let empty_size = get_instance_packed_len(&CEKAccountData::default())? as u64;
+        CEKAccountData::max_ceks_size_bytes()?
+            .checked_add(empty_size)
+            .ok_or_else(|| SolariumError::Overflow.into())
+    }
+
+    /// Get the size in bytes of MAX_CEKS CEKs of the maximum size that can be written
+    pub fn max_ceks_size_bytes() -> Result<u64, ProgramError> {
+        let cek_size = get_instance_packed_len(&CEKData {
+            header: CEKHeader::Jwe(JweHeader {
+                alg: JweAlgorithm::EcdhEsXc20pkw,
+                epk: [0; 32],
+                nonce: [0; 24],
+                tag: [0; 16],
+            }),
+            kid: "x".repeat(CEKData::MAX_KID_SIZE as usize),
+            encrypted_key: "x".repeat(CEKData::MAX_ENCRYPTED_KEY_SIZE as usize),
+            epoch: 0,
+        })? as u64;
+
+        cek_size
+            .checked_mul(CEKAccountData::MAX_CEKS as u64)
+            .ok_or_else(|| SolariumError::Overflow.into())
+    }
+}
 impl IsInitialized for CEKAccountData {
     /// Checks if a CEK account has been initialized
     fn is_initialized(&self) -> bool {
@@ -136,17 +463,243 @@ impl IsInitialized for CEKAccountData {
     }
 }
 
-/// Defines a UserDetails account structure
+/// Defines a PendingInvite account structure.
+/// A pending invite holds the CEKs for an invitee to a group channel until the invitee
+/// accepts the invite, creating their CEK account, or the invite is declined or revoked.
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct PendingInvite {
+    /// The channel that the invitee is invited to
+    pub channel: Pubkey,
+    /// The DID of the member who sent the invite
+    pub inviter_did: Pubkey,
+    /// The DID of the invitee
+    pub invitee_did: Pubkey,
+    /// The account that funded the invite, refunded when the invite is closed
+    pub funder: Pubkey,
+    /// The CEKs for the channel, encrypted for the keys in the invitee DID
+    pub ceks: Vec<CEKData>,
+}
+impl PendingInvite {
+    /// Create a new pending invite
+    pub fn new(
+        channel: Pubkey,
+        inviter_did: Pubkey,
+        invitee_did: Pubkey,
+        funder: Pubkey,
+        ceks: Vec<CEKData>,
+    ) -> Result<Self, SolariumError> {
+        if ceks.len() > CEKAccountData::MAX_CEKS as usize {
+            return Err(SolariumError::TooManyCEKs);
+        }
+        ceks.iter().try_for_each(|cek| cek.validate())?;
+
+        Ok(Self {
+            channel,
+            inviter_did,
+            invitee_did,
+            funder,
+            ceks,
+        })
+    }
+
+    /// Get the allocated size of a PendingInvite account in bytes
+    pub fn size_bytes() -> Result<u64, ProgramError> {
+        let empty_size = get_instance_packed_len(&PendingInvite::default())? as u64;
+        CEKAccountData::max_ceks_size_bytes()?
+            .checked_add(empty_size)
+            .ok_or_else(|| SolariumError::Overflow.into())
+    }
+}
+impl IsInitialized for PendingInvite {
+    /// Checks if a PendingInvite account has been initialized
+    fn is_initialized(&self) -> bool {
+        self.invitee_did != Pubkey::default()
+    }
+}
+
+/// Defines an InviteCode account structure.
+/// An invite code lets anyone who holds its invite keypair join a group channel until it expires
+/// or has been redeemed `max_uses` times. Redeeming creates a CEK account without CEKs,
+/// which a member then fills using `ProvisionMemberCEK`.
+/// Redemptions are signed by the invite keypair, so its secret key is never revealed on-chain.
+/// The account does not store a hash of a secret instead, as a preimage presented to redeem
+/// the code would be visible in the transaction to anyone who could then redeem it themselves.
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct InviteCode {
+    /// The channel that the invite code is for
+    pub channel: Pubkey,
+    /// The DID of the member who created the invite code
+    pub creator_did: Pubkey,
+    /// The account that funded the invite code, refunded when the invite code is closed
+    pub funder: Pubkey,
+    /// The public key of the invite keypair, which must sign each redemption
+    pub invite_pubkey: Pubkey,
+    /// The unix timestamp after which the invite code can no longer be redeemed
+    pub expiry: i64,
+    /// The maximum number of times the invite code can be redeemed
+    pub max_uses: u16,
+    /// The number of times the invite code has been redeemed
+    pub uses: u16,
+}
+impl InviteCode {
+    /// Create a new invite code
+    pub fn new(
+        channel: Pubkey,
+        creator_did: Pubkey,
+        funder: Pubkey,
+        invite_pubkey: Pubkey,
+        expiry: i64,
+        max_uses: u16,
+    ) -> Self {
+        Self {
+            channel,
+            creator_did,
+            funder,
+            invite_pubkey,
+            expiry,
+            max_uses,
+            uses: 0,
+        }
+    }
+
+    /// Redeem the invite code, signed by the given invite key, at the given unix timestamp
+    pub fn redeem(&mut self, invite_pubkey: &Pubkey, now: i64) -> Result<(), SolariumError> {
+        if *invite_pubkey != self.invite_pubkey {
+            return Err(SolariumError::InvalidInviteKey);
+        }
+        if now > self.expiry {
+            return Err(SolariumError::InviteCodeExpired);
+        }
+        if self.uses >= self.max_uses {
+            return Err(SolariumError::InviteCodeExhausted);
+        }
+        self.uses += 1;
+        Ok(())
+    }
+
+    /// Get the allocated size of an InviteCode account in bytes
+    pub fn size_bytes() -> u64 {
+        // channel, creator DID, funder, invite pubkey, expiry, max uses, uses
+        32 + 32 + 32 + 32 + 8 + 2 + 2
+    }
+}
+impl IsInitialized for InviteCode {
+    /// Checks if an InviteCode account has been initialized
+    fn is_initialized(&self) -> bool {
+        self.channel != Pubkey::default()
+    }
+}
+
+/// Defines a private CEK account structure.
+/// A private CEK account stores the encrypted CEKs of a member of a private channel.
+/// Unlike a CEKAccountData, it records neither the member DID nor the channel,
+/// only the blinded membership seed, from which its address is derived together with the channel.
 #[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct PrivateCEKAccountData {
+    /// The blinded membership seed, see `get_membership_seed`
+    pub membership_seed: [u8; 32],
+    /// The CEKs for the channel, one per key in the member DID
+    pub ceks: Vec<CEKData>,
+}
+impl PrivateCEKAccountData {
+    /// Create a new private CEK account
+    pub fn new(membership_seed: [u8; 32]) -> Self {
+        Self {
+            membership_seed,
+            ceks: Vec::with_capacity(usize::from(CEKAccountData::MAX_CEKS)),
+        }
+    }
+
+    /// Add a number of CEKs to the account at the same time
+    pub fn add_all(&mut self, ceks: Vec<CEKData>) -> Result<(), SolariumError> {
+        ceks.into_iter().try_for_each(|cek| self.add(cek))
+    }
+
+    /// add a new CEK to the account
+    pub fn add(&mut self, cek: CEKData) -> Result<(), SolariumError> {
+        cek.validate()?;
+        if self.ceks.len() >= CEKAccountData::MAX_CEKS as usize {
+            return Err(SolariumError::TooManyCEKs);
+        }
+        self.ceks.push(cek);
+        Ok(())
+    }
+
+    /// Replace all CEKs in the account
+    pub fn replace_all(&mut self, ceks: Vec<CEKData>) -> Result<(), SolariumError> {
+        self.ceks.clear();
+        self.add_all(ceks)
+    }
+
+    /// Checks if the account holds a CEK for the given channel epoch
+    pub fn has_epoch(&self, epoch: u32) -> bool {
+        self.ceks.iter().any(|cek| cek.epoch == epoch)
+    }
+
+    /// Get the allocated size of a private CEK account in bytes.
+    /// This is never larger than a CEK account, so the same size is used for both.
+    pub fn size_bytes() -> Result<u64, ProgramError> {
+        CEKAccountData::size_bytes()
+    }
+}
+impl IsInitialized for PrivateCEKAccountData {
+    /// Checks if a private CEK account has been initialized
+    fn is_initialized(&self) -> bool {
+        self.membership_seed != [0; 32]
+    }
+}
+
+/// Get the fragment of a key ID, e.g. "key1" for both "did:sol:abc#key1" and "key1"
+pub fn key_id_fragment(kid: &str) -> &str {
+    kid.rsplit('#').next().unwrap_or(kid)
+}
+
+/// Defines a UserDetails account structure.
+/// Fields appended to the user details account must also be read in its BorshDeserialize impl.
+#[derive(Clone, Debug, Default, BorshSerialize, BorshSchema, PartialEq)]
 pub struct UserDetails {
     /// The user's public alias
     pub alias: String,
-    /// The user's encrypted address book
-    pub address_book: String,
+    /// The user's encrypted address book.
+    /// Address books that outgrow the account can be stored in AddressBookPage accounts instead.
+    pub address_book: VersionedString,
+    /// The user's public status text
+    pub status: String,
+    /// A URI for the user's public avatar image
+    pub avatar_uri: String,
 }
 impl UserDetails {
     /// The recommended default size of a userDetails account
     pub const DEFAULT_SIZE_BYTES: u32 = 1536; //1.5kb
+
+    /// The maximum size of the status text in bytes
+    pub const MAX_STATUS_SIZE: u16 = 256;
+
+    /// The maximum size of the avatar URI in bytes
+    pub const MAX_AVATAR_URI_SIZE: u16 = 256;
+}
+impl BorshDeserialize for UserDetails {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let alias = String::deserialize(buf)?;
+        let address_book = VersionedString::deserialize(buf)?;
+
+        // An account whose address book is in the legacy layout was last written before the
+        // fields following the address book were added, and may be followed by stale bytes
+        if address_book.is_legacy() {
+            return Ok(Self {
+                alias,
+                address_book,
+                ..Self::default()
+            });
+        }
+
+        Ok(Self {
+            alias,
+            address_book,
+            status: String::deserialize(buf)?,
+            avatar_uri: String::deserialize(buf)?,
+        })
+    }
 }
 impl IsInitialized for UserDetails {
     /// Checks if a UserDetails account has been initialized
@@ -156,17 +709,45 @@ impl IsInitialized for UserDetails {
 }
 
 /// Struct for the NotificationType object
-#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
 pub enum NotificationType {
     /// The user has been added to a group channel. The pubkey is the channel address.
+    #[default]
     GroupChannel,
     /// The user has been added to a direct channel.
     /// The pubkey is the address of the DID of the other user in the direct channel.
     DirectChannel,
+    /// The user has been mentioned in a message. The pubkey is the channel address.
+    Mention,
+    /// The user has been removed from a group channel. The pubkey is the channel address.
+    RemovedFromChannel,
+    /// The CEK of a channel has been rotated, and the user should rotate theirs.
+    /// The pubkey is the channel address.
+    KeyRotated,
+    /// Another user would like to be added as a contact.
+    /// The pubkey is the address of the DID of the other user.
+    ContactRequest,
+    /// An app-specific event, identified by the given code.
+    /// The pubkey should be interpreted by the app.
+    // the BorshSchema derive emits a helper struct per variant whose fields are never read
+    Custom(#[allow(dead_code)] u16),
+    /// The user has been invited to a group channel and can accept or decline the invite.
+    /// The pubkey is the channel address.
+    ChannelInvite,
 }
-impl Default for NotificationType {
-    fn default() -> NotificationType {
-        GroupChannel
+impl NotificationType {
+    /// Checks if notifications of this type refer to the DID of the sender
+    pub fn refers_to_sender(&self) -> bool {
+        matches!(
+            self,
+            NotificationType::DirectChannel | NotificationType::ContactRequest
+        )
+    }
+
+    /// Checks if notifications of this type may be sent by any sender that the owner
+    /// has not blocked, without channel membership or allowlisting
+    pub fn is_unsolicited(&self) -> bool {
+        matches!(self, NotificationType::ContactRequest)
     }
 }
 
@@ -178,37 +759,192 @@ pub struct Notification {
     /// The public key relating to the notification.
     /// The key should be interpreted in relation to the notification type.
     pub pubkey: Pubkey,
+    /// The DID that sent the notification
+    pub sender: Pubkey,
+    /// The unix timestamp at which the notification was received
+    pub timestamp: i64,
 }
+impl Notification {
+    /// Create a new notification without a timestamp, for transport to the chain
+    pub fn new(notification_type: NotificationType, pubkey: Pubkey, sender: Pubkey) -> Self {
+        Self {
+            notification_type,
+            pubkey,
+            sender,
+            timestamp: 0,
+        }
+    }
 
-/// Defines a Notifications account structure
-#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+    /// Get the address of the channel that this notification refers to,
+    /// for a notification sent to the owner DID
+    pub fn channel(&self, program_id: &Pubkey, owner_did: &Pubkey) -> Option<Pubkey> {
+        match self.notification_type {
+            NotificationType::GroupChannel
+            | NotificationType::Mention
+            | NotificationType::RemovedFromChannel
+            | NotificationType::KeyRotated
+            | NotificationType::ChannelInvite => Some(self.pubkey),
+            NotificationType::DirectChannel => {
+                let (channel, _) =
+                    get_channel_address_with_seed(program_id, owner_did, &self.pubkey);
+                Some(channel)
+            }
+            NotificationType::ContactRequest | NotificationType::Custom(_) => None,
+        }
+    }
+}
+impl LegacyLayout for Notification {
+    fn deserialize_legacy(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self {
+            notification_type: NotificationType::deserialize(buf)?,
+            pubkey: Pubkey::deserialize(buf)?,
+            sender: Pubkey::default(),
+            timestamp: 0,
+        })
+    }
+}
+
+/// Defines a Notifications account structure.
+/// Fields appended to the notifications account must also be read in its BorshDeserialize impl.
+#[derive(Clone, Debug, Default, BorshSerialize, BorshSchema, PartialEq)]
 pub struct Notifications {
     /// The circular buffer of notifications for the user
-    pub notifications: Vec<Notification>,
+    pub notifications: VersionedVec<Notification>,
     /// The amount of notifications this user can hold simultaneously
-    pub size: u8,
+    pub size: u16,
+    /// If set, any DID can send notifications to this user
+    pub open: bool,
+    /// DIDs that can send notifications to this user without being members of the referenced channel
+    pub allowlist: Vec<Pubkey>,
 }
 impl Notifications {
     /// Default size of the notifications buffer
-    pub const DEFAULT_SIZE: u8 = 8;
+    pub const DEFAULT_SIZE: u16 = 8;
+
+    /// The maximum number of DIDs that can be added to the allowlist
+    pub const MAX_ALLOWLIST_SIZE: u8 = 8;
+
+    /// The maximum size of the notifications buffer.
+    /// An instruction can grow an account by at most MAX_PERMITTED_DATA_INCREASE bytes,
+    /// about 130 notifications, so larger buffers are reached by resizing the account
+    /// over several instructions. A full buffer of this size still fits in the program heap
+    /// when it is loaded.
+    pub const MAX_SIZE: u16 = 256;
 
     /// Create a new notifications account
-    pub fn new(size: u8) -> Self {
-        Self {
-            notifications: Vec::with_capacity(usize::from(size)),
+    pub fn new(size: u16) -> Result<Self, ProgramError> {
+        Notifications::check_size(size)?;
+        Ok(Self {
+            notifications: VersionedVec::with_capacity(usize::from(size)),
             size,
+            open: false,
+            allowlist: vec![],
+        })
+    }
+
+    /// Checks that a notifications buffer size is between 1 and MAX_SIZE
+    fn check_size(size: u16) -> Result<(), ProgramError> {
+        if size == 0 || size > Notifications::MAX_SIZE {
+            return Err(SolariumError::NotificationsSizeOutOfBounds.into());
         }
+        Ok(())
+    }
+
+    /// Checks if the sender DID may send notifications to this user
+    /// without being a member of the referenced channel
+    pub fn is_allowed(&self, sender_did: &Pubkey) -> bool {
+        self.open || self.allowlist.contains(sender_did)
+    }
+
+    /// Add a notification to the account - pushing out an old one if necessary.
+    /// The buffer is updated in place, as a copy of a full buffer may not fit in the program heap.
+    pub fn add(&mut self, mut notification: Notification) {
+        let clock = Clock::get().unwrap();
+        notification.timestamp = clock.unix_timestamp;
+        let len = self.notifications.len();
+        let excess = (len + 1).saturating_sub(self.size as usize).min(len);
+        self.notifications.drain(..excess);
+        self.notifications.push(notification);
+    }
+
+    /// Remove all notifications up to and including the given index
+    pub fn remove_up_to(&mut self, index: u16) {
+        let count = (index as usize + 1).min(self.notifications.len());
+        self.notifications.drain(..count);
+    }
+
+    /// Remove all notifications relating to the given public key
+    pub fn remove_by_pubkey(&mut self, pubkey: &Pubkey) {
+        self.notifications
+            .retain(|notification| notification.pubkey != *pubkey);
     }
 
-    /// Add a notification to the account - pushing out an old one if necessary
-    pub fn add(&mut self, notification: Notification) {
-        self.notifications =
-            push_into_deque(self.notifications.clone(), notification, self.size as usize);
+    /// Remove all notifications
+    pub fn clear(&mut self) {
+        self.notifications.clear();
     }
 
-    /// Get the allocated size of the Notifications account in bytes
-    pub fn size_bytes(&self) -> u64 {
-        (((8 + 1) * self.size) + 1) as u64
+    /// Change the amount of notifications this user can hold,
+    /// pushing out the oldest notifications if necessary
+    pub fn resize(&mut self, size: u16) -> Result<(), ProgramError> {
+        Notifications::check_size(size)?;
+        let excess = self.notifications.len().saturating_sub(size as usize);
+        self.notifications.drain(..excess);
+        self.size = size;
+        Ok(())
+    }
+
+    /// Get the allocated size of the Notifications account in bytes,
+    /// i.e. the size of the account when the notifications buffer and allowlist are full
+    pub fn size_bytes(&self) -> Result<u64, ProgramError> {
+        // Custom is the largest notification type
+        let notification_size = get_instance_packed_len(&Notification {
+            notification_type: NotificationType::Custom(0),
+            ..Notification::default()
+        })? as u64;
+        let empty_size = get_instance_packed_len(&Notifications {
+            notifications: VersionedVec::default(),
+            size: self.size,
+            open: self.open,
+            allowlist: vec![Pubkey::default(); Notifications::MAX_ALLOWLIST_SIZE as usize],
+        })? as u64;
+
+        notification_size
+            .checked_mul(self.size as u64)
+            .and_then(|notifications_size| notifications_size.checked_add(empty_size))
+            .ok_or_else(|| SolariumError::Overflow.into())
+    }
+
+    /// Checks that an account of the given size can be grown to hold the full buffer
+    /// by a single instruction
+    pub fn check_allocation(&self, current_size: usize) -> Result<(), ProgramError> {
+        let increase = (self.size_bytes()? as usize).saturating_sub(current_size);
+        if increase > MAX_PERMITTED_DATA_INCREASE {
+            return Err(SolariumError::NotificationsSizeOutOfBounds.into());
+        }
+        Ok(())
+    }
+}
+impl BorshDeserialize for Notifications {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let notifications = VersionedVec::<Notification>::deserialize(buf)?;
+
+        // Notifications in the legacy layout were written when the size was a u8,
+        // before the notification policy was added
+        if notifications.is_legacy() {
+            return Ok(Self {
+                notifications,
+                size: u8::deserialize(buf)?.into(),
+                ..Self::default()
+            });
+        }
+
+        Ok(Self {
+            notifications,
+            size: u16::deserialize(buf)?,
+            open: bool::deserialize(buf)?,
+            allowlist: Vec::<Pubkey>::deserialize(buf)?,
+        })
     }
 }
 impl IsInitialized for Notifications {
@@ -218,6 +954,333 @@ impl IsInitialized for Notifications {
     }
 }
 
+/// Defines a Blocklist account structure
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct Blocklist {
+    /// The DIDs that the owner has blocked
+    pub blocked: Vec<Pubkey>,
+}
+impl Blocklist {
+    /// The maximum number of DIDs a user can block
+    pub const MAX_SIZE: u8 = 32;
+
+    /// Block a DID
+    pub fn block(&mut self, did: Pubkey) -> Result<(), SolariumError> {
+        if self.is_blocked(&did) {
+            return Ok(());
+        }
+        if self.blocked.len() >= Blocklist::MAX_SIZE as usize {
+            return Err(SolariumError::BlocklistFull);
+        }
+        self.blocked.push(did);
+        Ok(())
+    }
+
+    /// Unblock a DID
+    pub fn unblock(&mut self, did: &Pubkey) {
+        self.blocked.retain(|blocked| blocked != did);
+    }
+
+    /// Checks if the DID is blocked
+    pub fn is_blocked(&self, did: &Pubkey) -> bool {
+        self.blocked.contains(did)
+    }
+
+    /// Get the allocated size of a Blocklist account in bytes
+    pub fn size_bytes() -> u64 {
+        4 + 32 * Blocklist::MAX_SIZE as u64
+    }
+}
+
+/// An encrypted entry in a user's address book
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct ContactEntry {
+    /// Identifies the entry within the address book, chosen by the client
+    pub id: u32,
+    /// The encrypted contact details
+    pub data: String,
+}
+
+/// Defines an AddressBookPage account structure.
+/// A user's address book is split over a sequence of pages, each holding a bounded number of entries.
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct AddressBookPage {
+    /// The DID that owns the address book
+    pub owner_did: Pubkey,
+    /// The index of this page in the address book
+    pub page: u16,
+    /// The encrypted entries on this page
+    pub entries: Vec<ContactEntry>,
+}
+impl AddressBookPage {
+    /// The maximum number of entries on a page
+    pub const MAX_ENTRIES: u8 = 16;
+
+    /// The maximum size of the encrypted data of an entry in bytes
+    pub const MAX_ENTRY_DATA_SIZE: u16 = 256;
+
+    /// Create a new empty address book page
+    pub fn new(owner_did: Pubkey, page: u16) -> Self {
+        Self {
+            owner_did,
+            page,
+            entries: vec![],
+        }
+    }
+
+    /// Add an entry to the page
+    pub fn add(&mut self, entry: ContactEntry) -> Result<(), SolariumError> {
+        AddressBookPage::check_entry_size(&entry)?;
+        if self.find(entry.id).is_some() {
+            return Err(SolariumError::ContactEntryExists);
+        }
+        if self.entries.len() >= AddressBookPage::MAX_ENTRIES as usize {
+            return Err(SolariumError::AddressBookPageFull);
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Replace the data of an existing entry
+    pub fn update(&mut self, entry: ContactEntry) -> Result<(), SolariumError> {
+        AddressBookPage::check_entry_size(&entry)?;
+        let index = self
+            .find(entry.id)
+            .ok_or(SolariumError::ContactEntryNotFound)?;
+        self.entries[index] = entry;
+        Ok(())
+    }
+
+    /// Remove an entry from the page
+    pub fn remove(&mut self, id: u32) -> Result<(), SolariumError> {
+        let index = self.find(id).ok_or(SolariumError::ContactEntryNotFound)?;
+        self.entries.remove(index);
+        Ok(())
+    }
+
+    fn find(&self, id: u32) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.id == id)
+    }
+
+    fn check_entry_size(entry: &ContactEntry) -> Result<(), SolariumError> {
+        if entry.data.len() > AddressBookPage::MAX_ENTRY_DATA_SIZE as usize {
+            return Err(SolariumError::ContactEntryTooLarge);
+        }
+        Ok(())
+    }
+
+    /// Get the allocated size of an AddressBookPage account in bytes
+    pub fn size_bytes() -> u64 {
+        // owner DID, page index, entries length prefix,
+        // then each entry's id, data length prefix and data
+        let entry_size = 4 + 4 + AddressBookPage::MAX_ENTRY_DATA_SIZE as u64;
+        32 + 2 + 4 + entry_size * AddressBookPage::MAX_ENTRIES as u64
+    }
+}
+impl IsInitialized for AddressBookPage {
+    /// Checks if an AddressBookPage account has been initialized
+    fn is_initialized(&self) -> bool {
+        self.owner_did != Pubkey::default()
+    }
+}
+
+/// Defines a ChannelMembersPage account structure.
+/// The members of a group channel are listed over a sequence of pages, each holding a bounded number of DIDs.
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct ChannelMembersPage {
+    /// The channel that the members belong to
+    pub channel: Pubkey,
+    /// The index of this page in the member list
+    pub page: u16,
+    /// The DIDs of the members on this page
+    pub members: Vec<Pubkey>,
+}
+impl ChannelMembersPage {
+    /// The maximum number of members on a page
+    pub const MAX_MEMBERS: u8 = 32;
+
+    /// Create a new empty channel members page
+    pub fn new(channel: Pubkey, page: u16) -> Self {
+        Self {
+            channel,
+            page,
+            members: vec![],
+        }
+    }
+
+    /// Add a member to the page
+    pub fn add(&mut self, did: Pubkey) -> Result<(), SolariumError> {
+        if self.members.len() >= ChannelMembersPage::MAX_MEMBERS as usize {
+            return Err(SolariumError::ChannelMembersPageFull);
+        }
+        self.members.push(did);
+        Ok(())
+    }
+
+    /// Remove a member from the page
+    pub fn remove(&mut self, did: &Pubkey) -> Result<(), SolariumError> {
+        let index = self
+            .members
+            .iter()
+            .position(|member| member == did)
+            .ok_or(SolariumError::ChannelMemberNotFound)?;
+        self.members.remove(index);
+        Ok(())
+    }
+
+    /// Get the allocated size of a ChannelMembersPage account in bytes
+    pub fn size_bytes() -> u64 {
+        // channel, page index, members length prefix, then each member DID
+        32 + 2 + 4 + 32 * ChannelMembersPage::MAX_MEMBERS as u64
+    }
+}
+impl IsInitialized for ChannelMembersPage {
+    /// Checks if a ChannelMembersPage account has been initialized
+    fn is_initialized(&self) -> bool {
+        self.channel != Pubkey::default()
+    }
+}
+
+/// A one-time prekey, used by at most one X3DH key agreement
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct OneTimePreKey {
+    /// Identifies the prekey, chosen by the client
+    pub id: u32,
+    /// The X25519 public key
+    pub key: [u8; 32],
+}
+
+/// A medium-term prekey, signed by the owner's identity key
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct SignedPreKey {
+    /// Identifies the prekey, chosen by the client
+    pub id: u32,
+    /// The X25519 public key
+    pub key: [u8; 32],
+    /// The signature of the key by the identity key
+    pub signature: [u8; 64],
+}
+
+/// Defines a PreKeyBundle account structure.
+/// A prekey bundle publishes the keys that other users need to
+/// run an X3DH key agreement with the owner while they are offline.
+#[derive(Clone, Debug, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct PreKeyBundle {
+    /// The DID that owns the prekey bundle
+    pub owner_did: Pubkey,
+    /// The owner's long-term identity public key
+    pub identity_key: [u8; 32],
+    /// The owner's current signed prekey
+    pub signed_prekey: SignedPreKey,
+    /// The pool of one-time prekeys, handed out oldest first
+    pub one_time_prekeys: Vec<OneTimePreKey>,
+}
+impl PreKeyBundle {
+    /// The maximum number of one-time prekeys in the pool
+    pub const MAX_ONE_TIME_PREKEYS: u8 = 32;
+
+    /// Add one-time prekeys to the pool
+    pub fn refill(&mut self, one_time_prekeys: Vec<OneTimePreKey>) -> Result<(), SolariumError> {
+        if self.one_time_prekeys.len() + one_time_prekeys.len()
+            > PreKeyBundle::MAX_ONE_TIME_PREKEYS as usize
+        {
+            return Err(SolariumError::PreKeyPoolFull);
+        }
+        self.one_time_prekeys.extend(one_time_prekeys);
+        Ok(())
+    }
+
+    /// Remove the oldest one-time prekey from the pool
+    pub fn consume_one_time_prekey(&mut self) -> Result<OneTimePreKey, SolariumError> {
+        if self.one_time_prekeys.is_empty() {
+            return Err(SolariumError::PreKeysExhausted);
+        }
+        Ok(self.one_time_prekeys.remove(0))
+    }
+
+    /// Get the allocated size of a PreKeyBundle account in bytes
+    pub fn size_bytes() -> u64 {
+        // owner DID, identity key, signed prekey (id, key, signature),
+        // then the one-time prekey pool length prefix and prekeys (id, key)
+        32 + 32 + (4 + 32 + 64) + 4 + (4 + 32) * PreKeyBundle::MAX_ONE_TIME_PREKEYS as u64
+    }
+}
+impl IsInitialized for PreKeyBundle {
+    /// Checks if a PreKeyBundle account has been initialized
+    fn is_initialized(&self) -> bool {
+        self.owner_did != Pubkey::default()
+    }
+}
+
+/// Defines an AliasRecord account structure.
+/// An alias record is a unique claim on a normalised alias by a DID.
+#[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
+pub struct AliasRecord {
+    /// The DID that owns the alias
+    pub owner_did: Pubkey,
+    /// The normalised alias
+    pub alias: String,
+}
+impl AliasRecord {
+    /// Create a new alias record, normalising the alias
+    pub fn new(owner_did: Pubkey, alias: &str) -> Self {
+        Self {
+            owner_did,
+            alias: normalize_alias(alias),
+        }
+    }
+}
+impl IsInitialized for AliasRecord {
+    /// Checks if an alias record has been initialized
+    fn is_initialized(&self) -> bool {
+        !self.alias.is_empty()
+    }
+}
+
+/// The minimum length of an alias, in characters
+pub const MIN_ALIAS_LENGTH: usize = 1;
+
+/// The maximum length of an alias, in characters
+pub const MAX_ALIAS_LENGTH: usize = 32;
+
+/// Normalise an alias so that equivalent aliases map to the same alias record,
+/// e.g. "@Alice", "alice" and "ａｌｉｃｅ"
+pub fn normalize_alias(alias: &str) -> String {
+    alias
+        .nfkc()
+        .collect::<String>()
+        .trim()
+        .trim_start_matches('@')
+        .to_lowercase()
+}
+
+/// Convert an alias to Unicode normalization form KC, preserving case,
+/// so that e.g. "Ａｌｉｃｅ" is stored as "Alice"
+pub fn nfkc_alias(alias: &str) -> String {
+    alias.nfkc().collect()
+}
+
+/// Check that an alias follows the rules for aliases:
+/// - it must be between MIN_ALIAS_LENGTH and MAX_ALIAS_LENGTH characters long
+/// - it may only contain ASCII letters, digits and '_'
+///
+/// Restricting aliases to ASCII prevents lookalike aliases mixing scripts, e.g. "аlice"
+/// with a Cyrillic "а". Aliases should be normalised with nfkc_alias or normalize_alias
+/// before validation.
+pub fn validate_alias(alias: &str) -> Result<(), SolariumError> {
+    let length = alias.chars().count();
+    if !(MIN_ALIAS_LENGTH..=MAX_ALIAS_LENGTH).contains(&length) {
+        return Err(SolariumError::AliasInvalidLength);
+    }
+
+    let is_allowed = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    if !alias.chars().all(is_allowed) {
+        return Err(SolariumError::AliasInvalidCharacter);
+    }
+
+    Ok(())
+}
+
 /// The seed string used to derive a program address for a Solarium channel (for direct channels)
 pub const CHANNEL_ADDRESS_SEED: &[u8; 16] = br"solarium_channel";
 
@@ -227,6 +1290,30 @@ pub const CEK_ACCOUNT_ADDRESS_SEED: &[u8; 20] = br"solarium_cek_account";
 /// The seed string used to derive a program address for a Solarium cek account
 pub const USERDETAILS_ACCOUNT_ADDRESS_SEED: &[u8; 28] = br"solarium_userdetails_account";
 
+/// The seed string used to derive a program address for a Solarium blocklist account
+pub const BLOCKLIST_ACCOUNT_ADDRESS_SEED: &[u8; 26] = br"solarium_blocklist_account";
+
+/// The seed string used to derive a program address for a Solarium alias record
+pub const ALIAS_ACCOUNT_ADDRESS_SEED: &[u8; 22] = br"solarium_alias_account";
+
+/// The seed string used to derive a program address for a Solarium address book page account
+pub const ADDRESS_BOOK_ACCOUNT_ADDRESS_SEED: &[u8; 29] = br"solarium_address_book_account";
+
+/// The seed string used to derive a program address for a Solarium channel members page account
+pub const CHANNEL_MEMBERS_ACCOUNT_ADDRESS_SEED: &[u8; 32] = br"solarium_channel_members_account";
+
+/// The seed string used to derive a program address for a Solarium pending invite account
+pub const PENDING_INVITE_ACCOUNT_ADDRESS_SEED: &[u8; 31] = br"solarium_pending_invite_account";
+
+/// The seed string used to derive a program address for a Solarium invite code account
+pub const INVITE_CODE_ACCOUNT_ADDRESS_SEED: &[u8; 28] = br"solarium_invite_code_account";
+
+/// The seed string used to derive a program address for a Solarium private CEK account
+pub const PRIVATE_CEK_ACCOUNT_ADDRESS_SEED: &[u8; 28] = br"solarium_private_cek_account";
+
+/// The seed string used to derive a program address for a Solarium prekey bundle account
+pub const PREKEY_BUNDLE_ACCOUNT_ADDRESS_SEED: &[u8; 30] = br"solarium_prekey_bundle_account";
+
 /// The seed string used to derive a program address for a Solarium notifications account
 pub const NOTIFICATIONS_ACCOUNT_ADDRESS_SEED: &[u8; 30] = br"solarium_notifications_account";
 
@@ -246,6 +1333,38 @@ pub fn get_cek_account_address_with_seed(
     )
 }
 
+/// Get the blinded membership seed of a DID in a private channel.
+/// The membership key is the public key of a keypair generated for the member when they are
+/// added to the channel and shared only with the member, so the seed cannot be recomputed
+/// from the DID and channel alone. Members prove their membership by signing with the keypair,
+/// so its secret key is never revealed on-chain.
+pub fn get_membership_seed(did: &Pubkey, channel: &Pubkey, membership_key: &Pubkey) -> [u8; 32] {
+    hashv(&[
+        &did.to_bytes(),
+        &channel.to_bytes(),
+        &membership_key.to_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Get program-derived private CEK account address for a blinded membership seed in a channel.
+/// The address is derived from the channel as well, so that an account for a channel
+/// can only be created by a member of that channel.
+pub fn get_private_cek_account_address_with_seed(
+    program_id: &Pubkey,
+    channel: &Pubkey,
+    membership_seed: &[u8; 32],
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            &channel.to_bytes(),
+            membership_seed,
+            PRIVATE_CEK_ACCOUNT_ADDRESS_SEED,
+        ],
+        program_id,
+    )
+}
+
 /// Get program-derived user details account address for the did
 pub fn get_userdetails_account_address_with_seed(
     program_id: &Pubkey,
@@ -257,6 +1376,99 @@ pub fn get_userdetails_account_address_with_seed(
     )
 }
 
+/// Get program-derived blocklist account address for the did
+pub fn get_blocklist_account_address_with_seed(program_id: &Pubkey, did: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&did.to_bytes(), BLOCKLIST_ACCOUNT_ADDRESS_SEED],
+        program_id,
+    )
+}
+
+/// Get program-derived alias record address for the alias.
+/// The alias is normalised and hashed, as aliases may be longer than the maximum seed length.
+pub fn get_alias_account_address_with_seed(program_id: &Pubkey, alias: &str) -> (Pubkey, u8) {
+    let alias_hash = hash(normalize_alias(alias).as_bytes());
+    Pubkey::find_program_address(
+        &[alias_hash.as_ref(), ALIAS_ACCOUNT_ADDRESS_SEED],
+        program_id,
+    )
+}
+
+/// Get program-derived address book page account address for the did and page index
+pub fn get_address_book_account_address_with_seed(
+    program_id: &Pubkey,
+    did: &Pubkey,
+    page: u16,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            &did.to_bytes(),
+            &page.to_le_bytes(),
+            ADDRESS_BOOK_ACCOUNT_ADDRESS_SEED,
+        ],
+        program_id,
+    )
+}
+
+/// Get program-derived channel members account address for the channel and page
+pub fn get_channel_members_account_address_with_seed(
+    program_id: &Pubkey,
+    channel: &Pubkey,
+    page: u16,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            &channel.to_bytes(),
+            &page.to_le_bytes(),
+            CHANNEL_MEMBERS_ACCOUNT_ADDRESS_SEED,
+        ],
+        program_id,
+    )
+}
+
+/// Get program-derived pending invite account address for the invitee did and channel
+pub fn get_pending_invite_account_address_with_seed(
+    program_id: &Pubkey,
+    invitee_did: &Pubkey,
+    channel: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            &invitee_did.to_bytes(),
+            &channel.to_bytes(),
+            PENDING_INVITE_ACCOUNT_ADDRESS_SEED,
+        ],
+        program_id,
+    )
+}
+
+/// Get program-derived invite code account address for the channel and the invite pubkey
+pub fn get_invite_code_account_address_with_seed(
+    program_id: &Pubkey,
+    channel: &Pubkey,
+    invite_pubkey: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            &channel.to_bytes(),
+            &invite_pubkey.to_bytes(),
+            INVITE_CODE_ACCOUNT_ADDRESS_SEED,
+        ],
+        program_id,
+    )
+}
+
+/// Get program-derived prekey bundle account address for the did
+pub fn get_prekey_bundle_account_address_with_seed(
+    program_id: &Pubkey,
+    did: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[&did.to_bytes(), PREKEY_BUNDLE_ACCOUNT_ADDRESS_SEED],
+        program_id,
+    )
+}
+
 /// Get program-derived notifications account address for the did
 pub fn get_notifications_account_address_with_seed(
     program_id: &Pubkey,
@@ -290,7 +1502,8 @@ pub fn get_channel_address_with_seed(
     )
 }
 
-/// Struct for the Message object
+/// Struct for the Message object.
+/// Messages written before channel epochs were introduced are read as belonging to epoch 0.
 #[derive(Clone, Debug, Default, BorshSerialize, BorshDeserialize, BorshSchema, PartialEq)]
 pub struct Message {
     /// The unix timestamp at which the message was received
@@ -299,6 +1512,8 @@ pub struct Message {
     pub sender: Pubkey,
     /// The (typically encrypted) message content
     pub content: String,
+    /// The channel epoch of the CEK that the message is encrypted with
+    pub epoch: u32,
 }
 
 impl Message {
@@ -308,9 +1523,16 @@ impl Message {
             timestamp: 0,
             sender,
             content,
+            epoch: 0,
         }
     }
 
+    /// Set the channel epoch of the CEK that the message is encrypted with
+    pub fn with_epoch(mut self, epoch: u32) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
     /// Create a new message and set its timestamp
     pub fn new_with_timestamp(sender: Pubkey, content: String) -> Self {
         let clock = Clock::get().unwrap();
@@ -318,9 +1540,294 @@ impl Message {
             timestamp: clock.unix_timestamp,
             sender,
             content,
+            epoch: 0,
         }
     }
 }
+impl LegacyLayout for Message {
+    fn deserialize_legacy(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Ok(Self {
+            timestamp: i64::deserialize(buf)?,
+            sender: Pubkey::deserialize(buf)?,
+            content: String::deserialize(buf)?,
+            epoch: 0,
+        })
+    }
+}
 
 #[cfg(test)]
-pub mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_legacy_cek_header() {
+        // a CEK written when the header was a string
+        let legacy_bytes = (
+            "legacy header".to_string(),
+            "key1".to_string(),
+            "key".to_string(),
+            0u32,
+        )
+            .try_to_vec()
+            .unwrap();
+
+        let cek = CEKData::try_from_slice(&legacy_bytes).unwrap();
+
+        assert_eq!(cek.header, CEKHeader::Legacy("legacy header".to_string()));
+        assert_eq!(cek.kid, "key1");
+        assert_eq!(cek.validate(), Err(SolariumError::InvalidCEKHeader));
+    }
+
+    #[test]
+    fn read_legacy_cek_account() {
+        // a CEK account written before CEKs recorded their epoch
+        let owner_did = Pubkey::new_unique();
+        let channel = Pubkey::new_unique();
+        let legacy_bytes = (
+            owner_did,
+            channel,
+            vec![("header".to_string(), "key1".to_string(), "key".to_string())],
+        )
+            .try_to_vec()
+            .unwrap();
+
+        let mut cek_account = CEKAccountData::try_from_slice(&legacy_bytes).unwrap();
+
+        assert!(cek_account.ceks.is_legacy());
+        assert_eq!(cek_account.ceks[0].kid, "key1");
+        assert!(cek_account.has_epoch(0));
+
+        // the account is rewritten in the current layout
+        cek_account.ceks[0].epoch = 1;
+        let bytes = cek_account.try_to_vec().unwrap();
+        let cek_account = CEKAccountData::try_from_slice(&bytes).unwrap();
+
+        assert!(!cek_account.ceks.is_legacy());
+        assert!(cek_account.has_epoch(1));
+    }
+
+    #[test]
+    fn read_legacy_channel() {
+        // a channel written before messages recorded their epoch,
+        // followed by stale bytes from a longer message that has since been dropped
+        let sender = Pubkey::new_unique();
+        let mut legacy_bytes = (
+            "channel".to_string(),
+            vec![(1i64, sender, "hello".to_string())],
+        )
+            .try_to_vec()
+            .unwrap();
+        legacy_bytes.extend_from_slice(&[0xff; 16]);
+
+        let channel =
+            crate::borsh::try_from_slice_incomplete::<ChannelData>(&legacy_bytes).unwrap();
+
+        assert_eq!(channel.name, "channel");
+        assert_eq!(channel.messages[0].sender, sender);
+        assert_eq!(channel.messages[0].content, "hello");
+        assert_eq!(channel.messages[0].epoch, 0);
+        assert_eq!(channel.epoch, 0);
+        assert!(!channel.private);
+        assert_eq!(channel.member_count, 0);
+        assert_eq!(channel.members_page_count, 0);
+    }
+
+    #[test]
+    fn read_legacy_user_details() {
+        // an address book that has shrunk leaves stale bytes behind it
+        let mut data = ("alice".to_string(), "book".to_string())
+            .try_to_vec()
+            .unwrap();
+        data.extend_from_slice(b"ook that was longer");
+
+        let user_details = UserDetails::deserialize(&mut data.as_slice()).unwrap();
+        assert_eq!(user_details.alias, "alice");
+        assert_eq!(user_details.address_book, "book");
+        assert!(user_details.status.is_empty());
+        assert!(user_details.avatar_uri.is_empty());
+
+        // the current layout is read back in full
+        let user_details = UserDetails {
+            status: "away".to_string(),
+            ..user_details
+        };
+        let data = user_details.try_to_vec().unwrap();
+        let read = UserDetails::deserialize(&mut data.as_slice()).unwrap();
+        assert_eq!(read, user_details);
+        assert!(!read.address_book.is_legacy());
+    }
+
+    #[test]
+    fn channel_members_pages_are_contiguous() {
+        let mut channel = ChannelData::new("channel".to_string());
+        assert_eq!(channel.next_members_page(), 0);
+        assert_eq!(
+            channel.add_member(1),
+            Err(SolariumError::InvalidChannelMembersPage)
+        );
+
+        // fill the first page
+        for _ in 0..ChannelMembersPage::MAX_MEMBERS {
+            assert_eq!(channel.add_member(0), Ok(()));
+        }
+        assert_eq!(channel.members_page_count, 1);
+        assert_eq!(channel.next_members_page(), 1);
+        assert_eq!(
+            channel.add_member(0),
+            Err(SolariumError::InvalidChannelMembersPage)
+        );
+        assert_eq!(
+            channel.add_member(2),
+            Err(SolariumError::InvalidChannelMembersPage)
+        );
+
+        // a full last page is followed by a new page
+        assert_eq!(channel.add_member(1), Ok(()));
+        assert_eq!(channel.members_page_count, 2);
+        assert_eq!(channel.last_members_page_len, 1);
+        assert_eq!(
+            channel.member_count,
+            ChannelMembersPage::MAX_MEMBERS as u32 + 1
+        );
+
+        // removing from an earlier page does not reopen it
+        assert_eq!(channel.remove_member(0), Ok(()));
+        assert_eq!(channel.next_members_page(), 1);
+        assert_eq!(channel.member_count, ChannelMembersPage::MAX_MEMBERS as u32);
+
+        // an emptied last page is reused
+        assert_eq!(channel.remove_member(1), Ok(()));
+        assert_eq!(channel.last_members_page_len, 0);
+        assert_eq!(channel.next_members_page(), 1);
+        assert_eq!(channel.remove_member(1), Err(SolariumError::Overflow));
+    }
+
+    #[test]
+    fn round_trip_jwe_cek_header() {
+        let header = CEKHeader::Jwe(JweHeader {
+            alg: JweAlgorithm::EcdhEsXc20pkw,
+            epk: [1; 32],
+            nonce: [2; 24],
+            tag: [3; 16],
+        });
+        let cek = CEKData {
+            header: header.clone(),
+            kid: "key1".to_string(),
+            encrypted_key: "key".to_string(),
+            epoch: 0,
+        };
+
+        let bytes = cek.try_to_vec().unwrap();
+
+        assert_eq!(CEKData::try_from_slice(&bytes).unwrap(), cek);
+        assert_eq!(header.len(), header.to_bytes().unwrap().len());
+        assert_eq!(cek.validate(), Ok(()));
+    }
+
+    #[test]
+    fn cek_account_fits_largest_ceks() {
+        let cek = CEKData {
+            header: CEKHeader::Jwe(JweHeader {
+                alg: JweAlgorithm::EcdhEsXc20pkw,
+                epk: [1; 32],
+                nonce: [2; 24],
+                tag: [3; 16],
+            }),
+            kid: "x".repeat(CEKData::MAX_KID_SIZE as usize),
+            encrypted_key: "x".repeat(CEKData::MAX_ENCRYPTED_KEY_SIZE as usize),
+            epoch: u32::MAX,
+        };
+        let mut cek_account = CEKAccountData::new(Pubkey::new_unique(), Pubkey::new_unique());
+        for _ in 0..CEKAccountData::MAX_CEKS {
+            assert_eq!(cek_account.add(cek.clone()), Ok(()));
+        }
+
+        assert_eq!(
+            cek_account.try_to_vec().unwrap().len() as u64,
+            CEKAccountData::size_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn notifications_size_bounds() {
+        assert_eq!(
+            Notifications::new(0),
+            Err(SolariumError::NotificationsSizeOutOfBounds.into())
+        );
+        assert_eq!(
+            Notifications::new(Notifications::MAX_SIZE + 1),
+            Err(SolariumError::NotificationsSizeOutOfBounds.into())
+        );
+
+        // a full account of the maximum size must be grown over several instructions
+        let mut notifications = Notifications::new(Notifications::MAX_SIZE).unwrap();
+        assert_eq!(
+            notifications.check_allocation(0),
+            Err(SolariumError::NotificationsSizeOutOfBounds.into())
+        );
+        let half = Notifications::new(Notifications::MAX_SIZE / 2).unwrap();
+        assert_eq!(half.check_allocation(0), Ok(()));
+        assert_eq!(
+            notifications.check_allocation(half.size_bytes().unwrap() as usize),
+            Ok(())
+        );
+
+        assert_eq!(
+            notifications.resize(0),
+            Err(SolariumError::NotificationsSizeOutOfBounds.into())
+        );
+        assert_eq!(notifications.size, Notifications::MAX_SIZE);
+    }
+
+    #[test]
+    fn read_legacy_notifications() {
+        let pubkey = Pubkey::new_unique();
+        let mut data = vec![(NotificationType::DirectChannel, pubkey)]
+            .try_to_vec()
+            .unwrap();
+        data.push(16);
+        // stale bytes left behind by a shorter buffer
+        data.extend_from_slice(&[0xff; 32]);
+
+        let notifications = Notifications::deserialize(&mut data.as_slice()).unwrap();
+        assert_eq!(
+            notifications.notifications.as_slice(),
+            &[Notification::new(
+                NotificationType::DirectChannel,
+                pubkey,
+                Pubkey::default()
+            )]
+        );
+        assert_eq!(notifications.size, 16);
+        assert!(!notifications.open);
+        assert!(notifications.allowlist.is_empty());
+    }
+
+    #[test]
+    fn redeem_invite_code() {
+        let invite_pubkey = Pubkey::new_unique();
+        let mut invite_code = InviteCode::new(
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            invite_pubkey,
+            100,
+            1,
+        );
+
+        assert_eq!(
+            invite_code.redeem(&Pubkey::new_unique(), 0),
+            Err(SolariumError::InvalidInviteKey)
+        );
+        assert_eq!(
+            invite_code.redeem(&invite_pubkey, 101),
+            Err(SolariumError::InviteCodeExpired)
+        );
+        assert_eq!(invite_code.redeem(&invite_pubkey, 100), Ok(()));
+        assert_eq!(
+            invite_code.redeem(&invite_pubkey, 100),
+            Err(SolariumError::InviteCodeExhausted)
+        );
+        assert_eq!(invite_code.uses, 1);
+    }
+}
@@ -1,11 +1,17 @@
-// Mark this test as BPF-only due to current `ProgramTest` limitations when CPIing into the system program
-#![cfg(feature = "test-bpf")]
-
 use crate::solarium_context::SolariumContext;
 use solana_program_test::tokio;
 use solana_sdk::signature::{Keypair, Signer};
-use solarium::state::NotificationType::GroupChannel;
-use solarium::state::{ChannelData, Notification};
+use solarium::id;
+use solarium::instruction::NotificationsAck;
+use solarium::state::NotificationType::{
+    ContactRequest, Custom, DirectChannel, GroupChannel, KeyRotated, Mention, RemovedFromChannel,
+};
+use solarium::state::{
+    get_cek_account_address_with_seed, get_membership_seed,
+    get_pending_invite_account_address_with_seed, get_private_cek_account_address_with_seed,
+    AddressBookPage, CEKAccountData, CEKData, CEKHeader, ChannelData, ContactEntry, Notifications,
+    OneTimePreKey, SignedPreKey, UserDetails, MAX_ALIAS_LENGTH,
+};
 
 mod solarium_context;
 
@@ -32,6 +38,206 @@ async fn add_cek() {
     assert!(found);
 }
 
+#[tokio::test]
+async fn add_too_many_ceks() {
+    let mut context = SolariumContext::new().await;
+
+    // the channel is created with one CEK for alice
+    context.create_channel().await;
+    for index in 2..=CEKAccountData::MAX_CEKS {
+        context
+            .try_add_cek(SolariumContext::make_dummy_cekdata(&format!(
+                "key{}",
+                index
+            )))
+            .await
+            .unwrap();
+    }
+
+    let result = context
+        .try_add_cek(SolariumContext::make_dummy_cekdata("one too many"))
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn add_cek_with_legacy_header() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+
+    let mut cek = SolariumContext::make_dummy_cekdata("key2");
+    cek.header = CEKHeader::Legacy("{\"alg\":\"ECDH-ES+XC20PKW\"}".to_string());
+    let result = context.try_add_cek(cek).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn add_cek_too_large() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+
+    let mut cek = SolariumContext::make_dummy_cekdata("key2");
+    cek.encrypted_key = "x".repeat(CEKData::MAX_ENCRYPTED_KEY_SIZE as usize + 1);
+    let result = context.try_add_cek(cek).await;
+    assert!(result.is_err());
+
+    // a CEK with the largest key ID and encrypted key fits in the account
+    let cek = CEKData {
+        header: SolariumContext::make_dummy_header(2),
+        kid: "x".repeat(CEKData::MAX_KID_SIZE as usize),
+        encrypted_key: "x".repeat(CEKData::MAX_ENCRYPTED_KEY_SIZE as usize),
+        epoch: 0,
+    };
+    context.try_add_cek(cek).await.unwrap();
+}
+
+#[tokio::test]
+async fn prune_stale_ceks() {
+    let mut context = SolariumContext::new().await;
+
+    // alice's DID only has the default key, so the CEK for key1 is stale
+    context.create_channel().await;
+    let default_kid = format!("did:sol:{}#default", context.alice_did);
+    context
+        .add_cek(SolariumContext::make_dummy_cekdata(&default_kid))
+        .await;
+
+    context.prune_stale_ceks().await;
+
+    let cek_account = context.get_cek_account(context.alice_cek.unwrap()).await;
+    let kids: Vec<&str> = cek_account
+        .ceks
+        .iter()
+        .map(|cek| cek.kid.as_str())
+        .collect();
+    assert_eq!(kids, vec![default_kid.as_str()]);
+}
+
+#[tokio::test]
+async fn prune_stale_ceks_requires_cek_account_address() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+
+    // a copy of the CEK account at another address is not the CEK account of the DID
+    let copy = context.copy_account(context.alice_cek.unwrap()).await;
+    let result = context.try_prune_stale_ceks_at(copy).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn add_cek_and_prune() {
+    let mut context = SolariumContext::new().await;
+
+    // alice's DID only has the default key, so the CEK for key1 is stale
+    context.create_channel().await;
+    let default_kid = format!("did:sol:{}#default", context.alice_did);
+    context
+        .add_cek_and_prune(SolariumContext::make_dummy_cekdata(&default_kid))
+        .await;
+
+    let cek_account = context.get_cek_account(context.alice_cek.unwrap()).await;
+    assert_eq!(cek_account.ceks.len(), 1);
+    assert_eq!(cek_account.ceks[0].kid, default_kid);
+}
+
+#[tokio::test]
+async fn replace_cek() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+
+    let mut cek = SolariumContext::make_dummy_cekdata("key1");
+    cek.header = SolariumContext::make_dummy_header(2);
+    context.try_replace_cek("key1", cek.clone()).await.unwrap();
+
+    let cek_account = context.get_cek_account(context.alice_cek.unwrap()).await;
+    assert_eq!(cek_account.ceks, vec![cek]);
+
+    let result = context
+        .try_replace_cek("missing", SolariumContext::make_dummy_cekdata("key2"))
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn add_ceks_across_channels() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+    let first_channel = context.channel.unwrap();
+    let first_cek_account = context.alice_cek.unwrap();
+    context.create_channel().await;
+    let second_channel = context.channel.unwrap();
+    let second_cek_account = context.alice_cek.unwrap();
+
+    context
+        .add_ceks(vec![
+            (first_channel, SolariumContext::make_dummy_cekdata("key2")),
+            (second_channel, SolariumContext::make_dummy_cekdata("key2")),
+        ])
+        .await;
+
+    for cek_account in [first_cek_account, second_cek_account] {
+        let cek_account = context.get_cek_account(cek_account).await;
+        assert!(cek_account.ceks.iter().any(|cek| cek.kid == "key2"));
+    }
+}
+
+#[tokio::test]
+async fn provision_cek_for_member() {
+    let mut context = SolariumContext::new().await;
+    let bob_did = context.bob_did;
+
+    context.create_channel().await;
+    context.add_to_channel().await;
+
+    // bob's DID only has the default key
+    let result = context
+        .try_provision_cek_for_bob(SolariumContext::make_dummy_cekdata("key9"))
+        .await;
+    assert!(result.is_err());
+
+    let mut cek = SolariumContext::make_dummy_cekdata("default");
+    context
+        .try_provision_cek_for_bob(cek.clone())
+        .await
+        .unwrap();
+
+    let (bob_cek_account, _) =
+        get_cek_account_address_with_seed(&id(), &bob_did, &context.channel.unwrap());
+    let cek_account = context.get_cek_account(bob_cek_account).await;
+    assert!(cek_account.ceks.contains(&cek));
+
+    // an existing CEK cannot be overwritten by another member
+    cek.header = SolariumContext::make_dummy_header(2);
+    let result = context.try_provision_cek_for_bob(cek).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn provision_cek_for_member_requires_current_epoch() {
+    let mut context = SolariumContext::new().await;
+    let bob_did = context.bob_did;
+
+    context.create_channel().await;
+    context.add_to_channel().await;
+
+    // the CEK must belong to the current epoch
+    let mut cek = SolariumContext::make_dummy_cekdata("default");
+    cek.epoch = 1;
+    let result = context.try_provision_cek_for_bob(cek.clone()).await;
+    assert!(result.is_err());
+
+    // rotating for bob only leaves alice without a CEK for the current epoch
+    context.rotate_channel_key(1, vec![bob_did]).await;
+    context.refresh_blockhash().await;
+    let result = context.try_provision_cek_for_bob(cek).await;
+    assert!(result.is_err());
+}
+
 #[tokio::test]
 async fn remove_cek() {
     let kid = "key1";
@@ -146,6 +352,61 @@ async fn update_user_details() {
     assert_eq!(user_details.address_book, new_address_book);
 }
 
+#[tokio::test]
+async fn update_user_details_partially() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_user_details().await;
+    context.update_user_details("Alice", "encrypted data").await;
+
+    // set the profile fields without touching the alias or address book
+    context
+        .update_user_details_fields(
+            None,
+            None,
+            Some("Out to lunch".to_string()),
+            Some("https://example.com/alice.png".to_string()),
+        )
+        .await;
+
+    let user_details = context.get_user_details().await;
+
+    assert_eq!(user_details.alias, "Alice");
+    assert_eq!(user_details.address_book, "encrypted data");
+    assert_eq!(user_details.status, "Out to lunch");
+    assert_eq!(user_details.avatar_uri, "https://example.com/alice.png");
+
+    // change only the alias, which is normalised from fullwidth characters
+    context
+        .update_user_details_fields(Some("Ａｌｉｃｉａ".to_string()), None, None, None)
+        .await;
+
+    let user_details = context.get_user_details().await;
+
+    assert_eq!(user_details.alias, "Alicia");
+    assert_eq!(user_details.address_book, "encrypted data");
+    assert_eq!(user_details.status, "Out to lunch");
+}
+
+#[tokio::test]
+async fn update_user_details_rejects_long_fields() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_user_details().await;
+
+    let too_long = "x".repeat(UserDetails::MAX_STATUS_SIZE as usize + 1);
+    assert!(context
+        .try_update_user_details_fields(None, None, Some(too_long), None)
+        .await
+        .is_err());
+
+    let too_long = "x".repeat(UserDetails::MAX_AVATAR_URI_SIZE as usize + 1);
+    assert!(context
+        .try_update_user_details_fields(None, None, None, Some(too_long))
+        .await
+        .is_err());
+}
+
 #[tokio::test]
 async fn create_notifications() {
     let mut context = SolariumContext::new().await;
@@ -161,7 +422,77 @@ async fn create_notifications() {
 async fn add_notification() {
     let mut context = SolariumContext::new().await;
 
+    context.create_channel().await;
+    context.add_to_channel().await;
+    context.create_notifications().await;
+
+    let group_channel_pubkey = context.channel.unwrap();
+
+    context
+        .add_notification(GroupChannel, &group_channel_pubkey)
+        .await;
+
+    let notifications = context.get_notifications().await;
+    let notification = &notifications.notifications[0];
+
+    assert_eq!(notification.notification_type, GroupChannel);
+    assert_eq!(notification.pubkey, group_channel_pubkey);
+    assert_eq!(notification.sender, context.bob_did);
+    assert!(notification.timestamp > 0);
+}
+
+#[tokio::test]
+async fn post_with_mentions() {
+    let message = "hello @alice";
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+    context.add_to_channel().await;
+    context.create_notifications().await;
+
+    context
+        .post_as_bob_with_mentions(message, vec![context.alice_did])
+        .await;
+
+    let channel = context.get_channel().await;
+    assert_eq!(channel.messages.len(), 1);
+    assert_eq!(channel.messages[0].content, message);
+    assert_eq!(channel.messages[0].sender, context.bob_did);
+
+    let notifications = context.get_notifications().await;
+    let notification = &notifications.notifications[0];
+
+    assert_eq!(notification.notification_type, Mention);
+    assert_eq!(notification.pubkey, context.channel.unwrap());
+    assert_eq!(notification.sender, context.bob_did);
+}
+
+#[tokio::test]
+async fn add_notification_from_non_member_fails() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_notifications().await;
+
+    let group_channel_pubkey = Keypair::new().pubkey();
+
+    let result = context
+        .try_add_notification(GroupChannel, &group_channel_pubkey)
+        .await;
+
+    assert!(result.is_err());
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.notifications, vec![]);
+}
+
+#[tokio::test]
+async fn add_notification_from_allowlisted_sender() {
+    let mut context = SolariumContext::new().await;
+
     context.create_notifications().await;
+    context
+        .set_notifications_policy(false, vec![context.bob_did])
+        .await;
 
     let group_channel_pubkey = Keypair::new().pubkey();
 
@@ -171,11 +502,1006 @@ async fn add_notification() {
 
     let notifications = context.get_notifications().await;
 
-    assert_eq!(
-        notifications.notifications[0],
-        Notification {
-            notification_type: GroupChannel,
-            pubkey: group_channel_pubkey
-        }
-    );
+    assert_eq!(notifications.notifications[0].pubkey, group_channel_pubkey);
+}
+
+#[tokio::test]
+async fn add_notification_in_open_mode() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_notifications().await;
+    context.set_notifications_policy(true, vec![]).await;
+
+    let group_channel_pubkey = Keypair::new().pubkey();
+
+    context
+        .add_notification(GroupChannel, &group_channel_pubkey)
+        .await;
+
+    let notifications = context.get_notifications().await;
+
+    assert_eq!(notifications.notifications[0].pubkey, group_channel_pubkey);
+}
+
+#[tokio::test]
+async fn blocked_sender_cannot_add_notification() {
+    let mut context = SolariumContext::new().await;
+
+    let bob_did = context.bob_did;
+
+    context.create_notifications().await;
+    context.set_notifications_policy(true, vec![]).await;
+    context.block(&bob_did).await;
+
+    let group_channel_pubkey = Keypair::new().pubkey();
+
+    let result = context
+        .try_add_notification(GroupChannel, &group_channel_pubkey)
+        .await;
+    assert!(result.is_err());
+
+    context.unblock(&bob_did).await;
+
+    // use a different pubkey, so that the transaction does not match the rejected one
+    let group_channel_pubkey = Keypair::new().pubkey();
+    context
+        .add_notification(GroupChannel, &group_channel_pubkey)
+        .await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.notifications[0].pubkey, group_channel_pubkey);
+}
+
+#[tokio::test]
+async fn blocked_sender_cannot_mention() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+    context.add_to_channel().await;
+    context.create_notifications().await;
+
+    let bob_did = context.bob_did;
+    context.block(&bob_did).await;
+
+    let result = context
+        .try_post_as_bob_with_mentions("hello @alice", vec![context.alice_did])
+        .await;
+    assert!(result.is_err());
+
+    let notifications = context.get_notifications().await;
+    assert!(notifications.notifications.is_empty());
+}
+
+#[tokio::test]
+async fn blocked_creator_cannot_create_direct_channel() {
+    let mut context = SolariumContext::new().await;
+
+    let alice_did = context.alice_did;
+
+    context.block_as_bob(&alice_did).await;
+
+    let result = context.try_create_direct_channel().await;
+    assert!(result.is_err());
+
+    // the invitee blocklist cannot be left out to skip the check
+    let result = context
+        .try_create_direct_channel_with_accounts(Some(9))
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn ack_notifications() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_notifications().await;
+    context.set_notifications_policy(true, vec![]).await;
+
+    let first = Keypair::new().pubkey();
+    let second = Keypair::new().pubkey();
+    let third = Keypair::new().pubkey();
+    context.add_notification(GroupChannel, &first).await;
+    context.add_notification(GroupChannel, &second).await;
+    context.add_notification(GroupChannel, &third).await;
+
+    context
+        .ack_notifications(NotificationsAck::UpToIndex(0))
+        .await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.notifications.len(), 2);
+    assert_eq!(notifications.notifications[0].pubkey, second);
+
+    context
+        .ack_notifications(NotificationsAck::Pubkey(third))
+        .await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.notifications.len(), 1);
+    assert_eq!(notifications.notifications[0].pubkey, second);
+}
+
+#[tokio::test]
+async fn clear_notifications() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_notifications().await;
+    context.set_notifications_policy(true, vec![]).await;
+
+    context
+        .add_notification(GroupChannel, &Keypair::new().pubkey())
+        .await;
+    context.clear_notifications().await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.notifications, vec![]);
+}
+
+#[tokio::test]
+async fn fill_large_notifications_account() {
+    let size: u16 = 32;
+    let mut context = SolariumContext::new().await;
+
+    context.create_notifications_with_size(size).await;
+    context.set_notifications_policy(true, vec![]).await;
+
+    // send more notifications than the account can hold
+    let pubkeys: Vec<_> = (0..(size + 2)).map(|_| Keypair::new().pubkey()).collect();
+    for pubkey in pubkeys.iter() {
+        context.add_notification(GroupChannel, pubkey).await;
+    }
+
+    let notifications = context.get_notifications().await;
+
+    assert_eq!(notifications.size, size);
+    assert_eq!(notifications.notifications.len(), size as usize);
+    assert_eq!(notifications.notifications[0].pubkey, pubkeys[2]);
+}
+
+#[tokio::test]
+async fn add_custom_notification() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_notifications().await;
+    context.set_notifications_policy(true, vec![]).await;
+
+    let app_pubkey = Keypair::new().pubkey();
+
+    context.add_notification(Custom(42), &app_pubkey).await;
+
+    let notifications = context.get_notifications().await;
+
+    assert_eq!(notifications.notifications[0].notification_type, Custom(42));
+    assert_eq!(notifications.notifications[0].pubkey, app_pubkey);
+}
+
+#[tokio::test]
+async fn contact_request_must_refer_to_sender() {
+    let mut context = SolariumContext::new().await;
+    let bob_did = context.bob_did;
+
+    context.create_notifications().await;
+    context.set_notifications_policy(false, vec![bob_did]).await;
+
+    let result = context
+        .try_add_notification(ContactRequest, &Keypair::new().pubkey())
+        .await;
+    assert!(result.is_err());
+
+    context.add_notification(ContactRequest, &bob_did).await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.notifications.len(), 1);
+    assert_eq!(notifications.notifications[0].pubkey, bob_did);
+}
+
+#[tokio::test]
+async fn contact_request_from_unknown_sender() {
+    let mut context = SolariumContext::new().await;
+    let bob_did = context.bob_did;
+
+    // bob is neither a channel member nor allowlisted, but can still request to be a contact
+    context.create_notifications().await;
+    context.add_notification(ContactRequest, &bob_did).await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.notifications.len(), 1);
+    assert_eq!(
+        notifications.notifications[0].notification_type,
+        ContactRequest
+    );
+
+    // other notification types still need authorisation
+    let result = context
+        .try_add_notification(GroupChannel, &Keypair::new().pubkey())
+        .await;
+    assert!(result.is_err());
+
+    // once blocked, bob can no longer send contact requests
+    context.block(&bob_did).await;
+    context.refresh_blockhash().await;
+    let result = context.try_add_notification(ContactRequest, &bob_did).await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn resize_notifications() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_notifications().await;
+    context.set_notifications_policy(true, vec![]).await;
+
+    let first = Keypair::new().pubkey();
+    let second = Keypair::new().pubkey();
+    context.add_notification(GroupChannel, &first).await;
+    context.add_notification(GroupChannel, &second).await;
+
+    context.resize_notifications(64).await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.size, 64);
+    assert_eq!(notifications.notifications.len(), 2);
+    assert_eq!(
+        context
+            .get_account_size(context.alice_notifications.unwrap())
+            .await,
+        Some(notifications.size_bytes().unwrap() as usize)
+    );
+
+    // shrinking the account keeps the most recent notifications
+    context.resize_notifications(1).await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.size, 1);
+    assert_eq!(notifications.notifications.len(), 1);
+    assert_eq!(notifications.notifications[0].pubkey, second);
+}
+
+#[tokio::test]
+async fn grow_notifications_to_max_size() {
+    let mut context = SolariumContext::new().await;
+
+    // a full buffer of the maximum size is too large to allocate in one instruction
+    let result = context
+        .try_create_notifications_with_size(Notifications::MAX_SIZE)
+        .await;
+    assert!(result.is_err());
+
+    context
+        .create_notifications_with_size(Notifications::MAX_SIZE / 2)
+        .await;
+    context.resize_notifications(Notifications::MAX_SIZE).await;
+
+    let notifications = context.get_notifications().await;
+    assert_eq!(notifications.size, Notifications::MAX_SIZE);
+    assert_eq!(
+        context
+            .get_account_size(context.alice_notifications.unwrap())
+            .await,
+        Some(notifications.size_bytes().unwrap() as usize)
+    );
+}
+
+#[tokio::test]
+async fn close_notifications() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_notifications().await;
+    let alice = context.alice.pubkey();
+    let rent = context
+        .get_balance(context.alice_notifications.unwrap())
+        .await;
+    let balance = context.get_balance(alice).await;
+
+    context.close_notifications().await;
+
+    assert_eq!(
+        context
+            .get_account_size(context.alice_notifications.unwrap())
+            .await,
+        None
+    );
+    // the rent is refunded to the owner
+    assert_eq!(context.get_balance(alice).await, balance + rent);
+}
+
+#[tokio::test]
+async fn resize_user_details() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_user_details().await;
+
+    // resizing to zero would leave the account unreadable
+    let result = context.try_resize_user_details(0).await;
+    assert!(result.is_err());
+
+    context.resize_user_details(4096).await;
+
+    assert_eq!(
+        context
+            .get_account_size(context.alice_user_details.unwrap())
+            .await,
+        Some(4096)
+    );
+
+    let user_details = context.get_user_details().await;
+    assert_eq!(user_details.alias, "Alice");
+}
+
+#[tokio::test]
+async fn close_user_details() {
+    let mut context = SolariumContext::new().await;
+
+    context.create_user_details().await;
+    let alice = context.alice.pubkey();
+    let rent = context
+        .get_balance(context.alice_user_details.unwrap())
+        .await;
+    let balance = context.get_balance(alice).await;
+
+    context.close_user_details().await;
+
+    assert_eq!(
+        context
+            .get_account_size(context.alice_user_details.unwrap())
+            .await,
+        None
+    );
+    // the rent is refunded to the owner
+    assert_eq!(context.get_balance(alice).await, balance + rent);
+}
+
+#[tokio::test]
+async fn add_to_channel_notifies_invitee() {
+    let mut context = SolariumContext::new().await;
+
+    let bob_notifications = context.create_notifications_as_bob().await;
+    context.create_channel().await;
+    context.add_to_channel().await;
+
+    let notifications = context.get_notifications_at(bob_notifications).await;
+    let notification = &notifications.notifications[0];
+
+    assert_eq!(notification.notification_type, GroupChannel);
+    assert_eq!(notification.pubkey, context.channel.unwrap());
+    assert_eq!(notification.sender, context.alice_did);
+}
+
+#[tokio::test]
+async fn create_direct_channel_notifies_invitee() {
+    let mut context = SolariumContext::new().await;
+
+    let bob_notifications = context.create_notifications_as_bob().await;
+    context.create_direct_channel().await;
+
+    let notifications = context.get_notifications_at(bob_notifications).await;
+    let notification = &notifications.notifications[0];
+
+    assert_eq!(notification.notification_type, DirectChannel);
+    assert_eq!(notification.pubkey, context.alice_did);
+    assert_eq!(notification.sender, context.alice_did);
+}
+
+#[tokio::test]
+async fn claim_alias() {
+    let mut context = SolariumContext::new().await;
+
+    context.claim_alias("Alice").await;
+
+    let alias_record = context.get_alias_record("@alice").await.unwrap();
+    assert_eq!(alias_record.owner_did, context.alice_did);
+    assert_eq!(alias_record.alias, "alice");
+
+    // the alias cannot be claimed by another DID
+    let result = context.try_claim_alias_as_bob("@ALICE").await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn transfer_alias() {
+    let mut context = SolariumContext::new().await;
+    let bob_did = context.bob_did;
+
+    context.claim_alias("alice").await;
+    context.transfer_alias("alice", &bob_did).await;
+
+    let alias_record = context.get_alias_record("alice").await.unwrap();
+    assert_eq!(alias_record.owner_did, bob_did);
+}
+
+#[tokio::test]
+async fn release_alias() {
+    let mut context = SolariumContext::new().await;
+
+    context.claim_alias("alice").await;
+    context.release_alias("alice").await;
+
+    assert_eq!(context.get_alias_record("alice").await, None);
+
+    // the alias can be claimed again once released
+    context.try_claim_alias_as_bob("alice").await.unwrap();
+}
+
+#[tokio::test]
+async fn claim_invalid_alias() {
+    let mut context = SolariumContext::new().await;
+
+    for alias in [
+        "@",
+        "bob smith",
+        "bob!",
+        "bob.smith",
+        // the first letter is a Cyrillic "а"
+        "аlice",
+        "b".repeat(MAX_ALIAS_LENGTH + 1).as_str(),
+    ] {
+        let result = context.try_claim_alias_as_bob(alias).await;
+        assert!(result.is_err(), "alias {:?} should be rejected", alias);
+    }
+
+    // fullwidth characters are normalised before validation
+    context.try_claim_alias_as_bob("ｂｏｂ").await.unwrap();
+    let alias_record = context.get_alias_record("bob").await.unwrap();
+    assert_eq!(alias_record.alias, "bob");
+}
+
+#[tokio::test]
+async fn address_book_entries() {
+    let mut context = SolariumContext::new().await;
+
+    let entry = |id: u32, data: &str| ContactEntry {
+        id,
+        data: data.to_string(),
+    };
+
+    context.add_contact_entry(0, entry(1, "bob")).await;
+    context.add_contact_entry(0, entry(2, "carol")).await;
+    context.add_contact_entry(1, entry(3, "dave")).await;
+
+    let page = context.get_address_book_page(0).await.unwrap();
+    assert_eq!(page.owner_did, context.alice_did);
+    assert_eq!(page.entries, vec![entry(1, "bob"), entry(2, "carol")]);
+    assert_eq!(
+        context.get_address_book_page(1).await.unwrap().entries,
+        vec![entry(3, "dave")]
+    );
+
+    // ids are unique within a page
+    let result = context.try_add_contact_entry(0, entry(1, "eve")).await;
+    assert!(result.is_err());
+
+    context.update_contact_entry(0, entry(1, "robert")).await;
+    context.remove_contact_entry(0, 2).await;
+
+    let page = context.get_address_book_page(0).await.unwrap();
+    assert_eq!(page.entries, vec![entry(1, "robert")]);
+}
+
+#[tokio::test]
+async fn address_book_page_full() {
+    let mut context = SolariumContext::new().await;
+
+    for id in 0..AddressBookPage::MAX_ENTRIES as u32 {
+        context
+            .add_contact_entry(
+                0,
+                ContactEntry {
+                    id,
+                    data: "x".repeat(AddressBookPage::MAX_ENTRY_DATA_SIZE as usize),
+                },
+            )
+            .await;
+    }
+
+    let result = context
+        .try_add_contact_entry(
+            0,
+            ContactEntry {
+                id: 100,
+                data: "x".to_string(),
+            },
+        )
+        .await;
+    assert!(result.is_err());
+
+    // the entry fits on the next page
+    context
+        .add_contact_entry(
+            1,
+            ContactEntry {
+                id: 100,
+                data: "x".to_string(),
+            },
+        )
+        .await;
+}
+
+#[tokio::test]
+async fn consume_and_refill_prekeys() {
+    let mut context = SolariumContext::new().await;
+
+    let prekey = |id: u32| OneTimePreKey {
+        id,
+        key: [id as u8; 32],
+    };
+
+    context
+        .create_prekey_bundle(vec![prekey(1), prekey(2)])
+        .await;
+
+    // prekeys are handed out oldest first
+    context.try_consume_one_time_prekey_as_bob().await.unwrap();
+    let prekey_bundle = context.get_prekey_bundle().await;
+    assert_eq!(prekey_bundle.owner_did, context.alice_did);
+    assert_eq!(prekey_bundle.one_time_prekeys, vec![prekey(2)]);
+
+    context.try_consume_one_time_prekey_as_bob().await.unwrap();
+    let result = context.try_consume_one_time_prekey_as_bob().await;
+    assert!(result.is_err());
+
+    let signed_prekey = SignedPreKey {
+        id: 2,
+        key: [4; 32],
+        signature: [5; 64],
+    };
+    context
+        .refill_prekeys(Some(signed_prekey.clone()), vec![prekey(3), prekey(4)])
+        .await;
+
+    let prekey_bundle = context.get_prekey_bundle().await;
+    assert_eq!(prekey_bundle.signed_prekey, signed_prekey);
+    assert_eq!(prekey_bundle.one_time_prekeys, vec![prekey(3), prekey(4)]);
+}
+
+#[tokio::test]
+async fn rotate_channel_key() {
+    let mut context = SolariumContext::new().await;
+    let alice_did = context.alice_did;
+    let bob_did = context.bob_did;
+
+    context.create_channel().await;
+    context.add_to_channel().await;
+    let bob_notifications = context.create_notifications_as_bob().await;
+
+    // rotate the key for alice only
+    context.rotate_channel_key(1, vec![alice_did]).await;
+    assert_eq!(context.get_channel().await.epoch, 1);
+
+    // messages must be encrypted with the current CEK
+    let result = context.try_post_with_epoch("stale", 0).await;
+    assert!(result.is_err());
+    context.try_post_with_epoch("fresh", 1).await.unwrap();
+
+    // bob has no CEK for the new epoch yet, so can neither post nor rotate
+    let result = context.try_post_as_bob_with_epoch("too early", 1).await;
+    assert!(result.is_err());
+    let result = context
+        .try_rotate_channel_key_as_bob(1, vec![bob_did])
+        .await;
+    assert!(result.is_err());
+    let result = context
+        .try_rotate_channel_key_as_bob(2, vec![bob_did])
+        .await;
+    assert!(result.is_err());
+
+    // continue the rotation for bob
+    context.rotate_channel_key(1, vec![bob_did]).await;
+    context
+        .try_post_as_bob_with_epoch("hello", 1)
+        .await
+        .unwrap();
+
+    let channel = context.get_channel().await;
+    assert_eq!(channel.epoch, 1);
+    assert_eq!(channel.messages.last().unwrap().epoch, 1);
+
+    let notifications = context.get_notifications_at(bob_notifications).await;
+    assert_eq!(notifications.notifications[0].notification_type, KeyRotated);
+    assert_eq!(notifications.notifications[0].sender, alice_did);
+}
+
+#[tokio::test]
+async fn continue_rotation_only_for_unrotated_members() {
+    let mut context = SolariumContext::new().await;
+    let alice_did = context.alice_did;
+    let bob_did = context.bob_did;
+
+    context.create_channel().await;
+    context.add_to_channel().await;
+    context.rotate_channel_key(1, vec![alice_did]).await;
+
+    let mut cek = SolariumContext::make_dummy_cekdata("default");
+    cek.epoch = 1;
+
+    // members already rotated for the epoch are not replaced
+    context.refresh_blockhash().await;
+    let result = context
+        .try_rotate_channel_key_with_ceks(1, vec![(alice_did, vec![cek.clone()])])
+        .await;
+    assert!(result.is_err());
+
+    // each member must be given CEKs, for keys on the member DID
+    let result = context
+        .try_rotate_channel_key_with_ceks(1, vec![(bob_did, vec![])])
+        .await;
+    assert!(result.is_err());
+    let mut unknown_key_cek = cek.clone();
+    unknown_key_cek.kid = "unknown".to_string();
+    let result = context
+        .try_rotate_channel_key_with_ceks(1, vec![(bob_did, vec![unknown_key_cek])])
+        .await;
+    assert!(result.is_err());
+
+    context
+        .try_rotate_channel_key_with_ceks(1, vec![(bob_did, vec![cek])])
+        .await
+        .unwrap();
+    context
+        .try_post_as_bob_with_epoch("hello", 1)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn private_channel_membership() {
+    let alice_membership_key = Keypair::new();
+    let bob_membership_key = Keypair::new();
+    let mut context = SolariumContext::new().await;
+    let alice_did = context.alice_did;
+    let bob_did = context.bob_did;
+
+    context.create_private_channel(&alice_membership_key).await;
+    let channel = context.channel.unwrap();
+    assert!(context.get_channel().await.private);
+
+    // no CEK account exists at the address derived from the DID and channel
+    let (alice_public_cek, _) = get_cek_account_address_with_seed(&id(), &alice_did, &channel);
+    assert_eq!(context.get_account_size(alice_public_cek).await, None);
+    let result = context.try_post_with_epoch("public", 0).await;
+    assert!(result.is_err());
+
+    context
+        .add_bob_to_private_channel(&alice_membership_key, &bob_membership_key.pubkey())
+        .await;
+    let (bob_public_cek, _) = get_cek_account_address_with_seed(&id(), &bob_did, &channel);
+    assert_eq!(context.get_account_size(bob_public_cek).await, None);
+
+    // bob can only prove membership with his own membership key
+    let result = context
+        .try_post_private_as_bob("wrong key", &alice_membership_key)
+        .await;
+    assert!(result.is_err());
+    context
+        .try_post_private_as_bob("hello", &bob_membership_key)
+        .await
+        .unwrap();
+
+    let channel = context.get_channel().await;
+    assert_eq!(channel.messages.len(), 1);
+    assert_eq!(channel.messages[0].sender, bob_did);
+}
+
+#[tokio::test]
+async fn private_membership_is_bound_to_the_channel() {
+    let alice_membership_key = Keypair::new();
+    let bob_membership_key = Keypair::new();
+    let mut context = SolariumContext::new().await;
+    let bob_did = context.bob_did;
+
+    context.create_private_channel(&alice_membership_key).await;
+    let first_channel = context.channel.unwrap();
+    context.create_private_channel(&alice_membership_key).await;
+
+    // a member of the second channel adds bob with his membership seed for the first channel
+    let bob_membership_seed =
+        get_membership_seed(&bob_did, &first_channel, &bob_membership_key.pubkey());
+    context
+        .try_add_to_private_channel(&alice_membership_key, bob_membership_seed)
+        .await
+        .unwrap();
+
+    // the account only proves membership of the second channel
+    context.channel = Some(first_channel);
+    let result = context
+        .try_post_private_as_bob("hello", &bob_membership_key)
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn private_channel_lifecycle() {
+    let alice_membership_key = Keypair::new();
+    let bob_membership_key = Keypair::new();
+    let mut context = SolariumContext::new().await;
+    let alice_did = context.alice_did;
+    let bob_did = context.bob_did;
+
+    context.create_private_channel(&alice_membership_key).await;
+    let channel = context.channel.unwrap();
+    context
+        .add_bob_to_private_channel(&alice_membership_key, &bob_membership_key.pubkey())
+        .await;
+    let alice_membership_seed =
+        get_membership_seed(&alice_did, &channel, &alice_membership_key.pubkey());
+    let bob_membership_seed = get_membership_seed(&bob_did, &channel, &bob_membership_key.pubkey());
+
+    // bob can add a CEK for each key on his DID
+    let cek = SolariumContext::make_dummy_cekdata("default");
+    context
+        .try_add_private_cek_as_bob(&bob_membership_key, cek.clone())
+        .await
+        .unwrap();
+    context.refresh_blockhash().await;
+    let result = context
+        .try_add_private_cek_as_bob(&bob_membership_key, cek)
+        .await;
+    assert!(result.is_err());
+    let result = context
+        .try_add_private_cek_as_bob(
+            &bob_membership_key,
+            SolariumContext::make_dummy_cekdata("unknown"),
+        )
+        .await;
+    assert!(result.is_err());
+
+    // rotate the key for bob only, leaving alice unable to add members
+    let mut rotated_cek = SolariumContext::make_dummy_cekdata("default");
+    rotated_cek.epoch = 1;
+    context
+        .try_rotate_private_channel_key(
+            &alice_membership_key,
+            1,
+            vec![(bob_membership_seed, vec![rotated_cek.clone()])],
+        )
+        .await
+        .unwrap();
+    assert_eq!(context.get_channel().await.epoch, 1);
+    let result = context
+        .try_add_to_private_channel(&alice_membership_key, [3; 32])
+        .await;
+    assert!(result.is_err());
+    context
+        .try_post_private_as_bob_with_epoch("rotated", &bob_membership_key, 1)
+        .await
+        .unwrap();
+
+    // alice holds no CEK for the new epoch, so cannot continue the rotation
+    let result = context
+        .try_rotate_private_channel_key(
+            &alice_membership_key,
+            1,
+            vec![(alice_membership_seed, vec![rotated_cek])],
+        )
+        .await;
+    assert!(result.is_err());
+
+    // bob leaves, closing his private CEK account
+    context
+        .leave_private_channel_as_bob(&bob_membership_key)
+        .await;
+    let (bob_cek_account, _) =
+        get_private_cek_account_address_with_seed(&id(), &channel, &bob_membership_seed);
+    assert_eq!(context.get_account_size(bob_cek_account).await, None);
+    let result = context
+        .try_post_private_as_bob_with_epoch("gone", &bob_membership_key, 1)
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn list_channel_members() {
+    let mut context = SolariumContext::new().await;
+    let alice_did = context.alice_did;
+    let bob_did = context.bob_did;
+
+    context.create_channel().await;
+    assert_eq!(context.get_channel().await.member_count, 1);
+
+    context.add_to_channel().await;
+    assert_eq!(context.get_channel().await.member_count, 2);
+    let members_page = context.get_channel_members_page(0).await;
+    assert_eq!(members_page.members, vec![alice_did, bob_did]);
+
+    context.leave_channel_as_bob(0).await;
+    assert_eq!(context.get_channel().await.member_count, 1);
+    let members_page = context.get_channel_members_page(0).await;
+    assert_eq!(members_page.members, vec![alice_did]);
+
+    let (bob_cek_account, _) =
+        get_cek_account_address_with_seed(&id(), &bob_did, &context.channel.unwrap());
+    assert_eq!(context.get_account_size(bob_cek_account).await, None);
+}
+
+#[tokio::test]
+async fn remove_from_channel() {
+    let mut context = SolariumContext::new().await;
+    let alice_did = context.alice_did;
+    let bob_did = context.bob_did;
+
+    let bob_notifications = context.create_notifications_as_bob().await;
+    context.create_channel().await;
+    context.add_to_channel().await;
+    assert_eq!(context.get_channel().await.members_page_count, 1);
+
+    // bob can only be removed from the page that lists him
+    let result = context.try_remove_bob_from_channel(1).await;
+    assert!(result.is_err());
+
+    // the rent of bob's CEK account can only be refunded to bob
+    let alice = context.alice.pubkey();
+    let result = context
+        .try_remove_bob_from_channel_refunding(0, alice)
+        .await;
+    assert!(result.is_err());
+
+    let (bob_cek_account, _) =
+        get_cek_account_address_with_seed(&id(), &bob_did, &context.channel.unwrap());
+    let bob = context.bob.pubkey();
+    let rent = context.get_balance(bob_cek_account).await;
+    let balance = context.get_balance(bob).await;
+
+    context.remove_bob_from_channel(0).await;
+    assert_eq!(context.get_balance(bob).await, balance + rent);
+    let channel = context.get_channel().await;
+    assert_eq!(channel.member_count, 1);
+    assert_eq!(channel.last_members_page_len, 1);
+    let members_page = context.get_channel_members_page(0).await;
+    assert_eq!(members_page.members, vec![alice_did]);
+    assert_eq!(context.get_account_size(bob_cek_account).await, None);
+
+    let notifications = context.get_notifications_at(bob_notifications).await;
+    let notification = notifications.notifications.last().unwrap();
+    assert_eq!(notification.notification_type, RemovedFromChannel);
+    assert_eq!(notification.pubkey, context.channel.unwrap());
+    assert_eq!(notification.sender, alice_did);
+}
+
+#[tokio::test]
+async fn accept_invite() {
+    let mut context = SolariumContext::new().await;
+    let alice_did = context.alice_did;
+    let bob_did = context.bob_did;
+
+    context.create_channel().await;
+    let channel = context.channel.unwrap();
+    let (bob_cek_account, _) = get_cek_account_address_with_seed(&id(), &bob_did, &channel);
+    let (pending_invite, _) =
+        get_pending_invite_account_address_with_seed(&id(), &bob_did, &channel);
+
+    // bob is not a member until he accepts the invite
+    context.invite_bob("key1").await;
+    assert!(context.get_account_size(pending_invite).await.is_some());
+    assert_eq!(context.get_account_size(bob_cek_account).await, None);
+
+    context.accept_invite_as_bob().await;
+    assert_eq!(context.get_account_size(pending_invite).await, None);
+    let cek_account = context.get_cek_account(bob_cek_account).await;
+    assert_eq!(cek_account.ceks[0].kid, "key1");
+
+    assert_eq!(context.get_channel().await.member_count, 2);
+    let members_page = context.get_channel_members_page(0).await;
+    assert_eq!(members_page.members, vec![alice_did, bob_did]);
+}
+
+#[tokio::test]
+async fn decline_and_revoke_invite() {
+    let mut context = SolariumContext::new().await;
+    let bob_did = context.bob_did;
+
+    context.create_channel().await;
+    let channel = context.channel.unwrap();
+    let (bob_cek_account, _) = get_cek_account_address_with_seed(&id(), &bob_did, &channel);
+    let (pending_invite, _) =
+        get_pending_invite_account_address_with_seed(&id(), &bob_did, &channel);
+
+    context.invite_bob("key1").await;
+    context.decline_invite_as_bob().await;
+    assert_eq!(context.get_account_size(pending_invite).await, None);
+
+    context.invite_bob("key2").await;
+    context.revoke_invite_to_bob().await;
+    assert_eq!(context.get_account_size(pending_invite).await, None);
+
+    assert_eq!(context.get_account_size(bob_cek_account).await, None);
+    assert_eq!(context.get_channel().await.member_count, 1);
+}
+
+#[tokio::test]
+async fn redeem_invite_code() {
+    let invite = Keypair::new();
+    let mut context = SolariumContext::new().await;
+    let bob_did = context.bob_did;
+
+    context.create_channel().await;
+    let now = context.get_unix_timestamp().await;
+    context
+        .create_invite_code(&invite.pubkey(), now + 3600, 2)
+        .await;
+
+    let result = context.try_redeem_invite_as_bob(&Keypair::new()).await;
+    assert!(result.is_err());
+
+    // redeeming creates a CEK account without CEKs
+    context.try_redeem_invite_as_bob(&invite).await.unwrap();
+    assert_eq!(context.get_invite_code(&invite.pubkey()).await.uses, 1);
+    assert_eq!(context.get_channel().await.member_count, 2);
+    let (bob_cek_account, _) =
+        get_cek_account_address_with_seed(&id(), &bob_did, &context.channel.unwrap());
+    assert!(context
+        .get_cek_account(bob_cek_account)
+        .await
+        .ceks
+        .is_empty());
+
+    // bob can post once a member has wrapped the CEK for him
+    let result = context.try_post_as_bob_with_epoch("too early", 0).await;
+    assert!(result.is_err());
+    context
+        .try_provision_cek_for_bob(SolariumContext::make_dummy_cekdata("default"))
+        .await
+        .unwrap();
+    context
+        .try_post_as_bob_with_epoch("hello", 0)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn redeem_expired_invite_code() {
+    let invite = Keypair::new();
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+    let now = context.get_unix_timestamp().await;
+    context
+        .create_invite_code(&invite.pubkey(), now + 60, 2)
+        .await;
+    context.set_unix_timestamp(now + 61).await;
+
+    let result = context.try_redeem_invite_as_bob(&invite).await;
+    assert!(result.is_err());
+    assert_eq!(context.get_invite_code(&invite.pubkey()).await.uses, 0);
+}
+
+#[tokio::test]
+async fn create_invite_code_rejects_unredeemable_codes() {
+    let invite = Keypair::new();
+    let mut context = SolariumContext::new().await;
+
+    context.create_channel().await;
+    let now = context.get_unix_timestamp().await;
+
+    let result = context
+        .try_create_invite_code(&invite.pubkey(), now + 60, 0)
+        .await;
+    assert!(result.is_err());
+    let result = context
+        .try_create_invite_code(&invite.pubkey(), now - 1, 2)
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn redeemed_member_without_ceks_cannot_act_for_the_channel() {
+    let invite = Keypair::new();
+    let mut context = SolariumContext::new().await;
+    let alice_did = context.alice_did;
+
+    context.create_channel().await;
+    let now = context.get_unix_timestamp().await;
+    context
+        .create_invite_code(&invite.pubkey(), now + 3600, 2)
+        .await;
+    context.try_redeem_invite_as_bob(&invite).await.unwrap();
+
+    // bob's CEK account has no CEK for the current epoch until one is provisioned
+    let result = context
+        .try_create_invite_code_as_bob(&Keypair::new().pubkey(), now + 3600, 1)
+        .await;
+    assert!(result.is_err());
+    let result = context
+        .try_rotate_channel_key_as_bob(1, vec![alice_did])
+        .await;
+    assert!(result.is_err());
+    assert_eq!(context.get_channel().await.epoch, 0);
 }
@@ -1,19 +1,28 @@
+use borsh::BorshSerialize;
 use sol_did::{
-    id as did_program_id, instruction as did_instruction,
+    id as did_program_id,
     state::{get_sol_address_with_seed, SolData},
 };
-use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_program_test::{
+    processor, BanksClientError, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext,
+};
 use solana_sdk::{
-    pubkey::Pubkey, signature::Keypair, signature::Signer, system_instruction::create_account,
-    transaction::Transaction,
+    account::Account, clock::Clock, pubkey::Pubkey, rent::Rent, signature::Keypair,
+    signature::Signer, system_instruction::create_account, transaction::Transaction,
 };
 use solarium::state::{
-    get_channel_address_with_seed, get_notifications_account_address_with_seed,
-    get_userdetails_account_address_with_seed, CEKAccountData, Message, NotificationType,
-    Notifications, UserDetails,
+    get_address_book_account_address_with_seed, get_alias_account_address_with_seed,
+    get_channel_address_with_seed, get_channel_members_account_address_with_seed,
+    get_invite_code_account_address_with_seed, get_membership_seed,
+    get_notifications_account_address_with_seed, get_prekey_bundle_account_address_with_seed,
+    get_private_cek_account_address_with_seed, get_userdetails_account_address_with_seed,
+    AddressBookPage, AliasRecord, CEKAccountData, CEKHeader, ChannelMembersPage, ContactEntry,
+    InviteCode, JweAlgorithm, JweHeader, Message, NotificationType, Notifications, OneTimePreKey,
+    PreKeyBundle, SignedPreKey, UserDetails,
 };
 use solarium::{
-    borsh as program_borsh, id, instruction,
+    borsh as program_borsh, id,
+    instruction::{self, NotificationsAck},
     processor::process_instruction,
     state::get_cek_account_address_with_seed,
     state::{CEKData, ChannelData},
@@ -31,37 +40,36 @@ pub struct SolariumContext {
     pub alice_notifications: Option<Pubkey>,
 }
 impl SolariumContext {
-    async fn make_did(context: &mut ProgramTestContext, authority: &Keypair) -> Pubkey {
+    // Solarium only reads DID accounts, so they are added to the genesis accounts as the
+    // sol-did program would initialize them, rather than loading the sol-did program itself
+    fn add_did(test: &mut ProgramTest, authority: &Keypair) -> Pubkey {
         let (did_address, _) = get_sol_address_with_seed(&authority.pubkey());
-        let transaction = Transaction::new_signed_with_payer(
-            &[did_instruction::initialize(
-                &context.payer.pubkey(),
-                &authority.pubkey(),
-                SolData::DEFAULT_SIZE as u64,
-                SolData::default(),
-            )],
-            Some(&context.payer.pubkey()),
-            &[&context.payer],
-            context.last_blockhash,
-        );
-        context
-            .banks_client
-            .process_transaction(transaction)
-            .await
+        let mut data = SolData::new_sparse(authority.pubkey())
+            .try_to_vec()
             .unwrap();
+        data.resize(SolData::DEFAULT_SIZE, 0);
+        test.add_account(
+            did_address,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: did_program_id(),
+                ..Account::default()
+            },
+        );
 
         did_address
     }
 
     pub async fn new() -> Self {
         let mut test = ProgramTest::new("solarium", id(), processor!(process_instruction));
-        test.add_program("sol_did", did_program_id(), None);
-        let mut context = test.start_with_context().await;
 
         let alice = Keypair::new();
         let bob = Keypair::new();
-        let alice_did = SolariumContext::make_did(&mut context, &alice).await;
-        let bob_did = SolariumContext::make_did(&mut context, &bob).await;
+        let alice_did = SolariumContext::add_did(&mut test, &alice);
+        let bob_did = SolariumContext::add_did(&mut test, &bob);
+
+        let context = test.start_with_context().await;
 
         Self {
             context,
@@ -123,11 +131,22 @@ impl SolariumContext {
     }
 
     pub async fn create_direct_channel(&mut self) {
+        self.try_create_direct_channel().await.unwrap();
+    }
+
+    pub async fn try_create_direct_channel(&mut self) -> Result<(), BanksClientError> {
+        self.try_create_direct_channel_with_accounts(None).await
+    }
+
+    pub async fn try_create_direct_channel_with_accounts(
+        &mut self,
+        account_count: Option<usize>,
+    ) -> Result<(), BanksClientError> {
         let alice_ceks = vec![SolariumContext::make_dummy_cekdata("key1")];
         let bob_ceks = vec![SolariumContext::make_dummy_cekdata("key1")];
 
         let (channel, _) = get_channel_address_with_seed(&id(), &self.alice_did, &self.bob_did);
-        let initialize_direct_channel = instruction::initialize_direct_channel(
+        let mut initialize_direct_channel = instruction::initialize_direct_channel(
             &self.context.payer.pubkey(),
             &channel,
             &self.alice_did,
@@ -136,6 +155,9 @@ impl SolariumContext {
             alice_ceks,
             bob_ceks,
         );
+        if let Some(account_count) = account_count {
+            initialize_direct_channel.accounts.truncate(account_count);
+        }
 
         let transaction = Transaction::new_signed_with_payer(
             &[initialize_direct_channel],
@@ -146,13 +168,14 @@ impl SolariumContext {
         self.context
             .banks_client
             .process_transaction(transaction)
-            .await
-            .unwrap();
+            .await?;
 
         let (alice_cek_account, _) =
             get_cek_account_address_with_seed(&id(), &self.alice_did, &channel);
         self.alice_cek = Some(alice_cek_account);
         self.channel = Some(channel);
+
+        Ok(())
     }
 
     pub async fn add_to_channel(&mut self) {
@@ -164,6 +187,7 @@ impl SolariumContext {
             &self.bob_did,
             &self.alice_did,
             &self.alice.pubkey(),
+            0,
             bob_ceks,
         );
         let transaction = Transaction::new_signed_with_payer(
@@ -213,45 +237,106 @@ impl SolariumContext {
             .unwrap();
     }
 
-    pub fn make_dummy_cekdata(kid: &str) -> CEKData {
-        CEKData {
-            header: "".to_string(),
-            kid: kid.to_string(),
-            encrypted_key: "".to_string(),
-        }
+    pub async fn post_as_bob_with_mentions(&mut self, message: &str, mentions: Vec<Pubkey>) {
+        self.try_post_as_bob_with_mentions(message, mentions)
+            .await
+            .unwrap();
     }
 
-    pub async fn get_channel(&mut self) -> ChannelData {
-        let account_info = &self
-            .context
+    pub async fn try_post_as_bob_with_mentions(
+        &mut self,
+        message: &str,
+        mentions: Vec<Pubkey>,
+    ) -> Result<(), BanksClientError> {
+        let message_obj = Message::new(self.bob_did, message.to_string());
+
+        let post = instruction::post_with_mentions(
+            &self.channel.unwrap(),
+            &self.bob.pubkey(),
+            &message_obj,
+            mentions,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[post],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
             .banks_client
-            .get_account(self.channel.unwrap())
+            .process_transaction(transaction)
             .await
-            .unwrap()
-            .unwrap();
-        program_borsh::try_from_slice_incomplete::<ChannelData>(&account_info.data).unwrap()
     }
 
-    pub async fn get_cek_account(&mut self, address: Pubkey) -> CEKAccountData {
-        let account_info = &self
-            .context
+    pub async fn try_post_with_epoch(
+        &mut self,
+        message: &str,
+        epoch: u32,
+    ) -> Result<(), BanksClientError> {
+        let message_obj = Message::new(self.alice_did, message.to_string()).with_epoch(epoch);
+
+        let post = instruction::post(&self.channel.unwrap(), &self.alice.pubkey(), &message_obj);
+        let transaction = Transaction::new_signed_with_payer(
+            &[post],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
             .banks_client
-            .get_account(address)
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn try_post_as_bob_with_epoch(
+        &mut self,
+        message: &str,
+        epoch: u32,
+    ) -> Result<(), BanksClientError> {
+        let message_obj = Message::new(self.bob_did, message.to_string()).with_epoch(epoch);
+
+        let post = instruction::post(&self.channel.unwrap(), &self.bob.pubkey(), &message_obj);
+        let transaction = Transaction::new_signed_with_payer(
+            &[post],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn rotate_channel_key(&mut self, epoch: u32, members: Vec<Pubkey>) {
+        let member_ceks = members
+            .into_iter()
+            .map(|member| {
+                let mut cek = SolariumContext::make_dummy_cekdata("default");
+                cek.epoch = epoch;
+                (member, vec![cek])
+            })
+            .collect();
+
+        self.try_rotate_channel_key_with_ceks(epoch, member_ceks)
             .await
-            .unwrap()
             .unwrap();
-        program_borsh::try_from_slice_incomplete::<CEKAccountData>(&account_info.data).unwrap()
     }
 
-    pub async fn add_cek(&mut self, cek: CEKData) {
-        let add_cek = instruction::add_cek(
+    pub async fn try_rotate_channel_key_with_ceks(
+        &mut self,
+        epoch: u32,
+        member_ceks: Vec<(Pubkey, Vec<CEKData>)>,
+    ) -> Result<(), BanksClientError> {
+        let rotate_channel_key = instruction::rotate_channel_key(
+            &self.channel.unwrap(),
             &self.alice_did,
             &self.alice.pubkey(),
-            &self.channel.unwrap(),
-            cek,
+            epoch,
+            member_ceks,
         );
         let transaction = Transaction::new_signed_with_payer(
-            &[add_cek],
+            &[rotate_channel_key],
             Some(&self.context.payer.pubkey()),
             &[&self.context.payer, &self.alice],
             self.context.last_blockhash,
@@ -260,44 +345,92 @@ impl SolariumContext {
             .banks_client
             .process_transaction(transaction)
             .await
-            .unwrap_or_else(|e| println!("{:#?}", e));
     }
 
-    pub async fn remove_cek(&mut self, kid: &str) {
-        let remove_cek = instruction::remove_cek(
-            &self.alice_did,
-            &self.alice.pubkey(),
+    pub async fn try_rotate_channel_key_as_bob(
+        &mut self,
+        epoch: u32,
+        members: Vec<Pubkey>,
+    ) -> Result<(), BanksClientError> {
+        let member_ceks = members
+            .into_iter()
+            .map(|member| {
+                let mut cek = SolariumContext::make_dummy_cekdata("default");
+                cek.epoch = epoch;
+                (member, vec![cek])
+            })
+            .collect();
+
+        let rotate_channel_key = instruction::rotate_channel_key(
             &self.channel.unwrap(),
-            kid.to_string(),
+            &self.bob_did,
+            &self.bob.pubkey(),
+            epoch,
+            member_ceks,
         );
         let transaction = Transaction::new_signed_with_payer(
-            &[remove_cek],
+            &[rotate_channel_key],
             Some(&self.context.payer.pubkey()),
-            &[&self.context.payer, &self.alice],
+            &[&self.context.payer, &self.bob],
             self.context.last_blockhash,
         );
         self.context
             .banks_client
             .process_transaction(transaction)
             .await
-            .unwrap();
     }
 
-    pub async fn create_user_details(&mut self) {
-        let (alice_user_details, _) =
-            get_userdetails_account_address_with_seed(&id(), &self.alice_did);
+    pub fn make_dummy_header(nonce: u8) -> CEKHeader {
+        CEKHeader::Jwe(JweHeader {
+            alg: JweAlgorithm::EcdhEsXc20pkw,
+            epk: [1; 32],
+            nonce: [nonce; 24],
+            tag: [0; 16],
+        })
+    }
 
-        let create_user_details_account = instruction::create_user_details(
+    pub fn make_dummy_cekdata(kid: &str) -> CEKData {
+        CEKData {
+            header: SolariumContext::make_dummy_header(1),
+            kid: kid.to_string(),
+            encrypted_key: "".to_string(),
+            epoch: 0,
+        }
+    }
+
+    pub async fn create_private_channel(&mut self, membership_key: &Keypair) {
+        let channel = Keypair::new();
+        let alice_ceks = vec![SolariumContext::make_dummy_cekdata("key1")];
+
+        let channel_size = ChannelData::size_bytes();
+        let lamports = self
+            .context
+            .banks_client
+            .get_rent()
+            .await
+            .unwrap()
+            .minimum_balance(channel_size as usize);
+        let create_channel = create_account(
             &self.context.payer.pubkey(),
+            &channel.pubkey(),
+            lamports,
+            channel_size,
+            &id(),
+        );
+
+        let initialize_private_channel = instruction::initialize_private_channel(
+            &self.context.payer.pubkey(),
+            &channel.pubkey(),
+            "private channel".to_string(),
             &self.alice_did,
             &self.alice.pubkey(),
-            "Alice".to_string(),
-            UserDetails::DEFAULT_SIZE_BYTES,
+            &membership_key.pubkey(),
+            alice_ceks,
         );
         let transaction = Transaction::new_signed_with_payer(
-            &[create_user_details_account],
+            &[create_channel, initialize_private_channel],
             Some(&self.context.payer.pubkey()),
-            &[&self.context.payer, &self.alice],
+            &[&self.context.payer, &channel, &self.alice, membership_key],
             self.context.last_blockhash,
         );
         self.context
@@ -306,86 +439,168 @@ impl SolariumContext {
             .await
             .unwrap();
 
-        self.alice_user_details = Some(alice_user_details);
+        let membership_seed =
+            get_membership_seed(&self.alice_did, &channel.pubkey(), &membership_key.pubkey());
+        let (alice_cek_account, _) =
+            get_private_cek_account_address_with_seed(&id(), &channel.pubkey(), &membership_seed);
+        self.alice_cek = Some(alice_cek_account);
+        self.channel = Some(channel.pubkey());
     }
 
-    pub async fn update_user_details(&mut self, new_alias: &str, new_address_book: &str) {
-        let update_user_details_account = instruction::update_user_details(
+    pub async fn add_bob_to_private_channel(
+        &mut self,
+        alice_membership_key: &Keypair,
+        bob_membership_key: &Pubkey,
+    ) {
+        let bob_membership_seed =
+            get_membership_seed(&self.bob_did, &self.channel.unwrap(), bob_membership_key);
+        self.try_add_to_private_channel(alice_membership_key, bob_membership_seed)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_add_to_private_channel(
+        &mut self,
+        alice_membership_key: &Keypair,
+        invitee_membership_seed: [u8; 32],
+    ) -> Result<(), BanksClientError> {
+        let add_to_private_channel = instruction::add_to_private_channel(
+            &self.context.payer.pubkey(),
+            &self.channel.unwrap(),
             &self.alice_did,
             &self.alice.pubkey(),
-            new_alias.to_string(),
-            new_address_book.to_string(),
+            &alice_membership_key.pubkey(),
+            invitee_membership_seed,
+            vec![SolariumContext::make_dummy_cekdata("key1")],
         );
         let transaction = Transaction::new_signed_with_payer(
-            &[update_user_details_account],
+            &[add_to_private_channel],
             Some(&self.context.payer.pubkey()),
-            &[&self.context.payer, &self.alice],
+            &[&self.context.payer, &self.alice, alice_membership_key],
             self.context.last_blockhash,
         );
         self.context
             .banks_client
             .process_transaction(transaction)
             .await
-            .unwrap();
     }
 
-    pub async fn get_user_details(&mut self) -> UserDetails {
-        let account_info = &self
-            .context
+    pub async fn try_post_private_as_bob(
+        &mut self,
+        message: &str,
+        membership_key: &Keypair,
+    ) -> Result<(), BanksClientError> {
+        self.try_post_private_as_bob_with_epoch(message, membership_key, 0)
+            .await
+    }
+
+    pub async fn try_post_private_as_bob_with_epoch(
+        &mut self,
+        message: &str,
+        membership_key: &Keypair,
+        epoch: u32,
+    ) -> Result<(), BanksClientError> {
+        let message_obj = Message::new(self.bob_did, message.to_string()).with_epoch(epoch);
+
+        let post_private = instruction::post_private(
+            &self.channel.unwrap(),
+            &self.bob.pubkey(),
+            &membership_key.pubkey(),
+            &message_obj,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[post_private],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob, membership_key],
+            self.context.last_blockhash,
+        );
+        self.context
             .banks_client
-            .get_account(self.alice_user_details.unwrap())
+            .process_transaction(transaction)
             .await
-            .unwrap()
-            .unwrap();
-        program_borsh::try_from_slice_incomplete::<UserDetails>(&account_info.data).unwrap()
     }
 
-    pub async fn create_notifications(&mut self) {
-        let (alice_notifications, _) =
-            get_notifications_account_address_with_seed(&id(), &self.alice_did);
+    pub async fn try_add_private_cek_as_bob(
+        &mut self,
+        membership_key: &Keypair,
+        cek: CEKData,
+    ) -> Result<(), BanksClientError> {
+        let add_private_cek = instruction::add_private_cek(
+            &self.bob_did,
+            &self.bob.pubkey(),
+            &membership_key.pubkey(),
+            &self.channel.unwrap(),
+            cek,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[add_private_cek],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob, membership_key],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
 
-        let create_notifications_account = instruction::create_notifications(
-            &self.context.payer.pubkey(),
+    pub async fn try_rotate_private_channel_key(
+        &mut self,
+        alice_membership_key: &Keypair,
+        epoch: u32,
+        member_ceks: Vec<([u8; 32], Vec<CEKData>)>,
+    ) -> Result<(), BanksClientError> {
+        let rotate_private_channel_key = instruction::rotate_private_channel_key(
+            &self.channel.unwrap(),
             &self.alice_did,
             &self.alice.pubkey(),
-            Notifications::DEFAULT_SIZE,
+            &alice_membership_key.pubkey(),
+            epoch,
+            member_ceks,
         );
         let transaction = Transaction::new_signed_with_payer(
-            &[create_notifications_account],
+            &[rotate_private_channel_key],
             Some(&self.context.payer.pubkey()),
-            &[&self.context.payer, &self.alice],
+            &[&self.context.payer, &self.alice, alice_membership_key],
             self.context.last_blockhash,
         );
         self.context
             .banks_client
             .process_transaction(transaction)
             .await
-            .unwrap();
-
-        self.alice_notifications = Some(alice_notifications);
     }
 
-    pub async fn get_notifications(&mut self) -> Notifications {
-        let account_info = &self
-            .context
+    pub async fn leave_private_channel_as_bob(&mut self, membership_key: &Keypair) {
+        let leave_private_channel = instruction::leave_private_channel(
+            &self.bob_did,
+            &self.bob.pubkey(),
+            &membership_key.pubkey(),
+            &self.channel.unwrap(),
+            &self.bob.pubkey(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[leave_private_channel],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob, membership_key],
+            self.context.last_blockhash,
+        );
+        self.context
             .banks_client
-            .get_account(self.alice_notifications.unwrap())
+            .process_transaction(transaction)
             .await
-            .unwrap()
             .unwrap();
-        program_borsh::try_from_slice_incomplete::<Notifications>(&account_info.data).unwrap()
     }
 
-    pub async fn add_notification(&mut self, notification_type: NotificationType, pubkey: &Pubkey) {
-        let add_notification = instruction::add_notification(
-            notification_type,
-            pubkey,
-            &self.alice_did,
+    pub async fn leave_channel_as_bob(&mut self, members_page: u16) {
+        let leave_channel = instruction::leave_channel(
             &self.bob_did,
             &self.bob.pubkey(),
+            &self.channel.unwrap(),
+            members_page,
+            &self.bob.pubkey(),
         );
         let transaction = Transaction::new_signed_with_payer(
-            &[add_notification],
+            &[leave_channel],
             Some(&self.context.payer.pubkey()),
             &[&self.context.payer, &self.bob],
             self.context.last_blockhash,
@@ -396,4 +611,1102 @@ impl SolariumContext {
             .await
             .unwrap();
     }
+
+    pub async fn remove_bob_from_channel(&mut self, members_page: u16) {
+        self.try_remove_bob_from_channel(members_page)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_remove_bob_from_channel(
+        &mut self,
+        members_page: u16,
+    ) -> Result<(), BanksClientError> {
+        self.try_remove_bob_from_channel_refunding(members_page, self.bob.pubkey())
+            .await
+    }
+
+    pub async fn try_remove_bob_from_channel_refunding(
+        &mut self,
+        members_page: u16,
+        recipient: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let remove_from_channel = instruction::remove_from_channel(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            &self.bob_did,
+            &self.channel.unwrap(),
+            members_page,
+            &recipient,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[remove_from_channel],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn get_channel_members_page(&mut self, page: u16) -> ChannelMembersPage {
+        let (channel_members_account, _) =
+            get_channel_members_account_address_with_seed(&id(), &self.channel.unwrap(), page);
+        let account_info = &self
+            .context
+            .banks_client
+            .get_account(channel_members_account)
+            .await
+            .unwrap()
+            .unwrap();
+        program_borsh::try_from_slice_incomplete::<ChannelMembersPage>(&account_info.data).unwrap()
+    }
+
+    pub async fn invite_bob(&mut self, kid: &str) {
+        let invite_to_channel = instruction::invite_to_channel(
+            &self.context.payer.pubkey(),
+            &self.channel.unwrap(),
+            &self.bob_did,
+            &self.alice_did,
+            &self.alice.pubkey(),
+            vec![SolariumContext::make_dummy_cekdata(kid)],
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[invite_to_channel],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn accept_invite_as_bob(&mut self) {
+        let accept_invite = instruction::accept_invite(
+            &self.context.payer.pubkey(),
+            &self.channel.unwrap(),
+            &self.bob_did,
+            &self.bob.pubkey(),
+            &self.context.payer.pubkey(),
+            0,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[accept_invite],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn decline_invite_as_bob(&mut self) {
+        let decline_invite = instruction::decline_invite(
+            &self.channel.unwrap(),
+            &self.bob_did,
+            &self.bob.pubkey(),
+            &self.context.payer.pubkey(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[decline_invite],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn revoke_invite_to_bob(&mut self) {
+        let revoke_invite = instruction::revoke_invite(
+            &self.channel.unwrap(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            &self.bob_did,
+            &self.context.payer.pubkey(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[revoke_invite],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn get_unix_timestamp(&mut self) -> i64 {
+        self.context
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .unwrap()
+            .unix_timestamp
+    }
+
+    pub async fn set_unix_timestamp(&mut self, unix_timestamp: i64) {
+        let mut clock = self
+            .context
+            .banks_client
+            .get_sysvar::<Clock>()
+            .await
+            .unwrap();
+        clock.unix_timestamp = unix_timestamp;
+        self.context.set_sysvar(&clock);
+    }
+
+    pub async fn create_invite_code(&mut self, invite: &Pubkey, expiry: i64, max_uses: u16) {
+        self.try_create_invite_code(invite, expiry, max_uses)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_create_invite_code(
+        &mut self,
+        invite: &Pubkey,
+        expiry: i64,
+        max_uses: u16,
+    ) -> Result<(), BanksClientError> {
+        let create_invite_code = instruction::create_invite_code(
+            &self.context.payer.pubkey(),
+            &self.channel.unwrap(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            invite,
+            expiry,
+            max_uses,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[create_invite_code],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn try_create_invite_code_as_bob(
+        &mut self,
+        invite: &Pubkey,
+        expiry: i64,
+        max_uses: u16,
+    ) -> Result<(), BanksClientError> {
+        let create_invite_code = instruction::create_invite_code(
+            &self.context.payer.pubkey(),
+            &self.channel.unwrap(),
+            &self.bob_did,
+            &self.bob.pubkey(),
+            invite,
+            expiry,
+            max_uses,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[create_invite_code],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn try_redeem_invite_as_bob(
+        &mut self,
+        invite: &Keypair,
+    ) -> Result<(), BanksClientError> {
+        let redeem_invite = instruction::redeem_invite(
+            &self.context.payer.pubkey(),
+            &self.channel.unwrap(),
+            &self.bob_did,
+            &self.bob.pubkey(),
+            &invite.pubkey(),
+            0,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[redeem_invite],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob, invite],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn get_invite_code(&mut self, invite: &Pubkey) -> InviteCode {
+        let (invite_code_account, _) =
+            get_invite_code_account_address_with_seed(&id(), &self.channel.unwrap(), invite);
+        let account_info = &self
+            .context
+            .banks_client
+            .get_account(invite_code_account)
+            .await
+            .unwrap()
+            .unwrap();
+        program_borsh::try_from_slice_incomplete::<InviteCode>(&account_info.data).unwrap()
+    }
+
+    pub async fn get_channel(&mut self) -> ChannelData {
+        let account_info = &self
+            .context
+            .banks_client
+            .get_account(self.channel.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        program_borsh::try_from_slice_incomplete::<ChannelData>(&account_info.data).unwrap()
+    }
+
+    pub async fn get_cek_account(&mut self, address: Pubkey) -> CEKAccountData {
+        let account_info = &self
+            .context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+            .unwrap();
+        program_borsh::try_from_slice_incomplete::<CEKAccountData>(&account_info.data).unwrap()
+    }
+
+    pub async fn add_cek(&mut self, cek: CEKData) {
+        self.try_add_cek(cek)
+            .await
+            .unwrap_or_else(|e| println!("{:#?}", e));
+    }
+
+    pub async fn try_add_cek(&mut self, cek: CEKData) -> Result<(), BanksClientError> {
+        let add_cek = instruction::add_cek(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            &self.channel.unwrap(),
+            cek,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[add_cek],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn try_replace_cek(
+        &mut self,
+        kid: &str,
+        cek: CEKData,
+    ) -> Result<(), BanksClientError> {
+        let replace_cek = instruction::replace_cek(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            &self.channel.unwrap(),
+            kid.to_string(),
+            cek,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[replace_cek],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn add_ceks(&mut self, channel_ceks: Vec<(Pubkey, CEKData)>) {
+        let add_ceks = instruction::add_ceks(&self.alice_did, &self.alice.pubkey(), channel_ceks);
+        for add_ceks_instruction in add_ceks {
+            let transaction = Transaction::new_signed_with_payer(
+                &[add_ceks_instruction],
+                Some(&self.context.payer.pubkey()),
+                &[&self.context.payer, &self.alice],
+                self.context.last_blockhash,
+            );
+            self.context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap();
+        }
+    }
+
+    pub async fn try_provision_cek_for_bob(
+        &mut self,
+        cek: CEKData,
+    ) -> Result<(), BanksClientError> {
+        let provision_member_cek = instruction::provision_member_cek(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            &self.bob_did,
+            &self.channel.unwrap(),
+            cek,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[provision_member_cek],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn add_cek_and_prune(&mut self, cek: CEKData) {
+        let add_cek_and_prune = instruction::add_cek_and_prune(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            &self.channel.unwrap(),
+            cek,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[add_cek_and_prune],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn prune_stale_ceks(&mut self) {
+        self.try_prune_stale_ceks_at(self.alice_cek.unwrap())
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_prune_stale_ceks_at(
+        &mut self,
+        cek_account: Pubkey,
+    ) -> Result<(), BanksClientError> {
+        // pruning is permissionless, so only the payer signs
+        let mut prune_stale_ceks =
+            instruction::prune_stale_ceks(&self.alice_did, &self.channel.unwrap());
+        prune_stale_ceks.accounts[1].pubkey = cek_account;
+        let transaction = Transaction::new_signed_with_payer(
+            &[prune_stale_ceks],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn copy_account(&mut self, address: Pubkey) -> Pubkey {
+        let account = self
+            .context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+            .unwrap();
+        let copy = Pubkey::new_unique();
+        self.context.set_account(&copy, &account.into());
+        copy
+    }
+
+    pub async fn remove_cek(&mut self, kid: &str) {
+        let remove_cek = instruction::remove_cek(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            &self.channel.unwrap(),
+            kid.to_string(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[remove_cek],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn create_user_details(&mut self) {
+        let (alice_user_details, _) =
+            get_userdetails_account_address_with_seed(&id(), &self.alice_did);
+
+        let create_user_details_account = instruction::create_user_details(
+            &self.context.payer.pubkey(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            "Alice".to_string(),
+            UserDetails::DEFAULT_SIZE_BYTES,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[create_user_details_account],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        self.alice_user_details = Some(alice_user_details);
+    }
+
+    pub async fn update_user_details(&mut self, new_alias: &str, new_address_book: &str) {
+        self.update_user_details_fields(
+            Some(new_alias.to_string()),
+            Some(new_address_book.to_string()),
+            None,
+            None,
+        )
+        .await;
+    }
+
+    pub async fn update_user_details_fields(
+        &mut self,
+        alias: Option<String>,
+        address_book: Option<String>,
+        status: Option<String>,
+        avatar_uri: Option<String>,
+    ) {
+        self.try_update_user_details_fields(alias, address_book, status, avatar_uri)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_update_user_details_fields(
+        &mut self,
+        alias: Option<String>,
+        address_book: Option<String>,
+        status: Option<String>,
+        avatar_uri: Option<String>,
+    ) -> Result<(), BanksClientError> {
+        let update_user_details_account = instruction::update_user_details(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            alias,
+            address_book,
+            status,
+            avatar_uri,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[update_user_details_account],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn get_user_details(&mut self) -> UserDetails {
+        let account_info = &self
+            .context
+            .banks_client
+            .get_account(self.alice_user_details.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        program_borsh::try_from_slice_incomplete::<UserDetails>(&account_info.data).unwrap()
+    }
+
+    pub async fn create_notifications(&mut self) {
+        self.create_notifications_with_size(Notifications::DEFAULT_SIZE)
+            .await;
+    }
+
+    pub async fn create_notifications_with_size(&mut self, size: u16) {
+        self.try_create_notifications_with_size(size).await.unwrap();
+    }
+
+    pub async fn try_create_notifications_with_size(
+        &mut self,
+        size: u16,
+    ) -> Result<(), BanksClientError> {
+        let (alice_notifications, _) =
+            get_notifications_account_address_with_seed(&id(), &self.alice_did);
+
+        let create_notifications_account = instruction::create_notifications(
+            &self.context.payer.pubkey(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            size,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[create_notifications_account],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await?;
+
+        self.alice_notifications = Some(alice_notifications);
+        Ok(())
+    }
+
+    pub async fn get_notifications(&mut self) -> Notifications {
+        self.get_notifications_at(self.alice_notifications.unwrap())
+            .await
+    }
+
+    pub async fn get_notifications_at(&mut self, address: Pubkey) -> Notifications {
+        let account_info = &self
+            .context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+            .unwrap();
+        program_borsh::try_from_slice_incomplete::<Notifications>(&account_info.data).unwrap()
+    }
+
+    pub async fn add_notification(&mut self, notification_type: NotificationType, pubkey: &Pubkey) {
+        self.try_add_notification(notification_type, pubkey)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_add_notification(
+        &mut self,
+        notification_type: NotificationType,
+        pubkey: &Pubkey,
+    ) -> Result<(), BanksClientError> {
+        let add_notification = instruction::add_notification(
+            notification_type,
+            pubkey,
+            &self.alice_did,
+            &self.bob_did,
+            &self.bob.pubkey(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[add_notification],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn set_notifications_policy(&mut self, open: bool, allowlist: Vec<Pubkey>) {
+        let set_notifications_policy = instruction::set_notifications_policy(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            open,
+            allowlist,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_notifications_policy],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn block(&mut self, did: &Pubkey) {
+        let block = instruction::block(
+            &self.context.payer.pubkey(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            did,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[block],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn block_as_bob(&mut self, did: &Pubkey) {
+        let block = instruction::block(
+            &self.context.payer.pubkey(),
+            &self.bob_did,
+            &self.bob.pubkey(),
+            did,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[block],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn unblock(&mut self, did: &Pubkey) {
+        let unblock = instruction::unblock(&self.alice_did, &self.alice.pubkey(), did);
+        let transaction = Transaction::new_signed_with_payer(
+            &[unblock],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn ack_notifications(&mut self, ack: NotificationsAck) {
+        let ack_notifications =
+            instruction::ack_notifications(&self.alice_did, &self.alice.pubkey(), ack);
+        let transaction = Transaction::new_signed_with_payer(
+            &[ack_notifications],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn clear_notifications(&mut self) {
+        let clear_notifications =
+            instruction::clear_notifications(&self.alice_did, &self.alice.pubkey());
+        let transaction = Transaction::new_signed_with_payer(
+            &[clear_notifications],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn resize_notifications(&mut self, size: u16) {
+        self.try_resize_notifications(size).await.unwrap();
+    }
+
+    pub async fn try_resize_notifications(&mut self, size: u16) -> Result<(), BanksClientError> {
+        let resize_notifications = instruction::resize_notifications(
+            &self.context.payer.pubkey(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            size,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[resize_notifications],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn close_notifications(&mut self) {
+        let close_notifications =
+            instruction::close_notifications(&self.alice_did, &self.alice.pubkey());
+        let transaction = Transaction::new_signed_with_payer(
+            &[close_notifications],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn resize_user_details(&mut self, size: u32) {
+        self.try_resize_user_details(size).await.unwrap();
+    }
+
+    pub async fn try_resize_user_details(&mut self, size: u32) -> Result<(), BanksClientError> {
+        let resize_user_details = instruction::resize_user_details(
+            &self.context.payer.pubkey(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            size,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[resize_user_details],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn close_user_details(&mut self) {
+        let close_user_details =
+            instruction::close_user_details(&self.alice_did, &self.alice.pubkey());
+        let transaction = Transaction::new_signed_with_payer(
+            &[close_user_details],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn get_balance(&mut self, address: Pubkey) -> u64 {
+        self.context
+            .banks_client
+            .get_balance(address)
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_account_size(&mut self, address: Pubkey) -> Option<usize> {
+        self.context
+            .banks_client
+            .get_account(address)
+            .await
+            .unwrap()
+            .map(|account| account.data.len())
+    }
+
+    pub async fn create_notifications_as_bob(&mut self) -> Pubkey {
+        let (bob_notifications, _) =
+            get_notifications_account_address_with_seed(&id(), &self.bob_did);
+
+        let create_notifications_account = instruction::create_notifications(
+            &self.context.payer.pubkey(),
+            &self.bob_did,
+            &self.bob.pubkey(),
+            Notifications::DEFAULT_SIZE,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[create_notifications_account],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        bob_notifications
+    }
+
+    pub async fn claim_alias(&mut self, alias: &str) {
+        let claim_alias = instruction::claim_alias(
+            &self.context.payer.pubkey(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            alias.to_string(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[claim_alias],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_claim_alias_as_bob(&mut self, alias: &str) -> Result<(), BanksClientError> {
+        let claim_alias = instruction::claim_alias(
+            &self.context.payer.pubkey(),
+            &self.bob_did,
+            &self.bob.pubkey(),
+            alias.to_string(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[claim_alias],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn release_alias(&mut self, alias: &str) {
+        let release_alias = instruction::release_alias(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            alias,
+            &self.alice.pubkey(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[release_alias],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn transfer_alias(&mut self, alias: &str, new_owner_did: &Pubkey) {
+        let transfer_alias = instruction::transfer_alias(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            alias,
+            new_owner_did,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[transfer_alias],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn get_alias_record(&mut self, alias: &str) -> Option<AliasRecord> {
+        let (alias_account, _) = get_alias_account_address_with_seed(&id(), alias);
+        self.context
+            .banks_client
+            .get_account(alias_account)
+            .await
+            .unwrap()
+            .map(|account_info| {
+                program_borsh::try_from_slice_incomplete::<AliasRecord>(&account_info.data).unwrap()
+            })
+    }
+
+    pub async fn try_add_contact_entry(
+        &mut self,
+        page: u16,
+        entry: ContactEntry,
+    ) -> Result<(), BanksClientError> {
+        let add_contact_entry = instruction::add_contact_entry(
+            &self.context.payer.pubkey(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            page,
+            entry,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[add_contact_entry],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn add_contact_entry(&mut self, page: u16, entry: ContactEntry) {
+        self.try_add_contact_entry(page, entry).await.unwrap();
+    }
+
+    pub async fn update_contact_entry(&mut self, page: u16, entry: ContactEntry) {
+        let update_contact_entry =
+            instruction::update_contact_entry(&self.alice_did, &self.alice.pubkey(), page, entry);
+        let transaction = Transaction::new_signed_with_payer(
+            &[update_contact_entry],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn remove_contact_entry(&mut self, page: u16, id: u32) {
+        let remove_contact_entry =
+            instruction::remove_contact_entry(&self.alice_did, &self.alice.pubkey(), page, id);
+        let transaction = Transaction::new_signed_with_payer(
+            &[remove_contact_entry],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn get_address_book_page(&mut self, page: u16) -> Option<AddressBookPage> {
+        let (address_book_account, _) =
+            get_address_book_account_address_with_seed(&id(), &self.alice_did, page);
+        self.context
+            .banks_client
+            .get_account(address_book_account)
+            .await
+            .unwrap()
+            .map(|account_info| {
+                program_borsh::try_from_slice_incomplete::<AddressBookPage>(&account_info.data)
+                    .unwrap()
+            })
+    }
+
+    pub async fn create_prekey_bundle(&mut self, one_time_prekeys: Vec<OneTimePreKey>) {
+        let create_prekey_bundle = instruction::create_prekey_bundle(
+            &self.context.payer.pubkey(),
+            &self.alice_did,
+            &self.alice.pubkey(),
+            [1; 32],
+            SignedPreKey {
+                id: 1,
+                key: [2; 32],
+                signature: [3; 64],
+            },
+            one_time_prekeys,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[create_prekey_bundle],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    // use a fresh blockhash, so that repeating a transaction is not rejected as a duplicate
+    pub async fn refresh_blockhash(&mut self) {
+        self.context.last_blockhash = self
+            .context
+            .banks_client
+            .get_new_latest_blockhash(&self.context.last_blockhash)
+            .await
+            .unwrap();
+    }
+
+    pub async fn try_consume_one_time_prekey_as_bob(&mut self) -> Result<(), BanksClientError> {
+        // the instruction carries no data, so use a fresh blockhash to avoid duplicate transactions
+        self.refresh_blockhash().await;
+        let consume_one_time_prekey = instruction::consume_one_time_prekey(
+            &self.alice_did,
+            &self.bob_did,
+            &self.bob.pubkey(),
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[consume_one_time_prekey],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.bob],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+    }
+
+    pub async fn refill_prekeys(
+        &mut self,
+        signed_prekey: Option<SignedPreKey>,
+        one_time_prekeys: Vec<OneTimePreKey>,
+    ) {
+        let refill_prekeys = instruction::refill_prekeys(
+            &self.alice_did,
+            &self.alice.pubkey(),
+            signed_prekey,
+            one_time_prekeys,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[refill_prekeys],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer, &self.alice],
+            self.context.last_blockhash,
+        );
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    pub async fn get_prekey_bundle(&mut self) -> PreKeyBundle {
+        let (prekey_bundle_account, _) =
+            get_prekey_bundle_account_address_with_seed(&id(), &self.alice_did);
+        let account_info = &self
+            .context
+            .banks_client
+            .get_account(prekey_bundle_account)
+            .await
+            .unwrap()
+            .unwrap();
+        program_borsh::try_from_slice_incomplete::<PreKeyBundle>(&account_info.data).unwrap()
+    }
 }